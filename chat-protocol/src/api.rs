@@ -0,0 +1,515 @@
+//! JSON bodies of the REST endpoints under `/api`.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::ws::{is_false, ChatMessage, UserStatus};
+
+/// Body of any error response
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ErrorResponse {
+    pub error: String,
+}
+
+/// Body of responses that only confirm an action, e.g. signup or logout
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MessageResponse {
+    pub message: String,
+}
+
+/// `POST /api/user/signup`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SignupRequest {
+    pub username: String,
+    pub email: String,
+    pub password: String,
+}
+
+/// `POST /api/user/login`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LoginRequest {
+    pub email: String,
+    pub password: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LoginResponse {
+    pub message: String,
+    pub uid: i32,
+    pub username: String,
+    /// Session token to send as `Authorization: Bearer <token>`
+    pub token: String,
+    /// Seconds until the token expires
+    pub expires_in: i64,
+}
+
+/// `POST /api/user/fetch_status`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FetchStatusRequest {
+    pub room_id: i32,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FetchStatusResponse {
+    pub online_users: Vec<UserStatus>,
+}
+
+/// Who may join a room
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RoomVisibility {
+    /// Anyone who knows the room's ID
+    #[default]
+    Public,
+    /// Only users holding an invite
+    Private,
+}
+
+impl RoomVisibility {
+    pub fn is_public(&self) -> bool {
+        *self == RoomVisibility::Public
+    }
+}
+
+/// `POST /api/chatrooms`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CreateChatRoomRequest {
+    pub room_name: String,
+    #[serde(default, skip_serializing_if = "RoomVisibility::is_public")]
+    pub visibility: RoomVisibility,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CreateChatRoomResponse {
+    pub message: String,
+    pub room_id: i32,
+}
+
+/// `GET /api/chatrooms/{id}`, and the `room_updated` event
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ChatRoomDetails {
+    pub room_id: i32,
+    pub room_name: String,
+    /// What the room is about
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// What the room is talking about right now
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub topic: Option<String>,
+    #[serde(default, skip_serializing_if = "RoomVisibility::is_public")]
+    pub visibility: RoomVisibility,
+    /// Archived rooms can be read but nobody can post in them
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub archived: bool,
+}
+
+/// `PATCH /api/chatrooms/{id}`. Fields left unset are not changed, and an empty
+/// `description` or `topic` clears it.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct UpdateChatRoomRequest {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub room_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub topic: Option<String>,
+}
+
+/// `POST /api/chatrooms/join`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct JoinChatRoomRequest {
+    pub room_id: i32,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct JoinChatRoomResponse {
+    pub message: String,
+    pub room_name: String,
+}
+
+/// `POST /api/chatrooms/leave`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LeaveChatRoomRequest {
+    pub room_id: i32,
+}
+
+/// Order of the rooms listed by `GET /api/chatrooms`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RoomSort {
+    /// Most recently active first
+    #[default]
+    Activity,
+    /// Most members first
+    Members,
+    /// By name
+    Name,
+}
+
+/// Query string of `GET /api/chatrooms`, which lists public rooms
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct RoomListQuery {
+    /// Only list rooms whose name contains this, ignoring case
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub search: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sort: Option<RoomSort>,
+    /// Number of matching rooms to skip
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub offset: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u32>,
+}
+
+/// A room as listed by `GET /api/chatrooms` and `GET /api/users/me/rooms`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RoomSummary {
+    pub room_id: i32,
+    pub room_name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "RoomVisibility::is_public")]
+    pub visibility: RoomVisibility,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub archived: bool,
+    pub member_count: u32,
+    /// When the last message was posted, or the room was created if it has none
+    pub last_activity: DateTime<Utc>,
+    /// The caller's role, only listed for their own rooms
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub role: Option<RoomRole>,
+    /// Messages from others the caller has not read yet, only listed for their own rooms
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unread_count: Option<u32>,
+}
+
+/// `GET /api/chatrooms` and `GET /api/users/me/rooms`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RoomListResponse {
+    pub rooms: Vec<RoomSummary>,
+    /// Number of rooms matching the query, on this page and others
+    pub total: u32,
+}
+
+/// Query string of `GET /api/chatrooms/{id}/messages`
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct MessageHistoryQuery {
+    /// Only return messages older than this `message_id`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub before: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u32>,
+}
+
+/// A page of a room's history, newest first
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MessageHistoryResponse {
+    pub messages: Vec<ChatMessage>,
+    /// `before` value for the next older page, or `None` if there are no older messages
+    pub next_before: Option<i32>,
+}
+
+/// `PATCH /api/messages/{id}`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct EditMessageRequest {
+    pub content: String,
+}
+
+/// One earlier version of a message
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MessageEdit {
+    pub previous_content: String,
+    /// Who replaced this version, `None` if that user was deleted
+    pub edited_by: Option<i32>,
+    pub edited_at: DateTime<Utc>,
+}
+
+/// `GET /api/messages/{id}/edits`, oldest first
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MessageEditsResponse {
+    pub edits: Vec<MessageEdit>,
+}
+
+/// `GET /api/messages/{id}/thread`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ThreadResponse {
+    /// The message that started the thread
+    pub root: ChatMessage,
+    /// Every reply in the thread, oldest first
+    pub replies: Vec<ChatMessage>,
+}
+
+/// Query string of `GET /api/messages/search`, which searches the rooms the caller belongs to
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct MessageSearchQuery {
+    /// Words that must all appear in a message. Each one matches the start of a word, ignoring case.
+    #[serde(default)]
+    pub q: String,
+    /// Only search this room
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub room_id: Option<i32>,
+    /// Only search the messages of this user
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sender_id: Option<i32>,
+    /// Only search messages sent at or after this time
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from: Option<DateTime<Utc>>,
+    /// Only search messages sent before this time
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to: Option<DateTime<Utc>>,
+    /// Number of matching messages to skip
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub offset: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u32>,
+}
+
+/// A piece of a search result's snippet
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SnippetPart {
+    pub text: String,
+    /// Whether this piece is a word that matched the search
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub highlight: bool,
+}
+
+/// A message found by `GET /api/messages/search`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MessageSearchHit {
+    pub room_id: i32,
+    /// `None` for direct conversations
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub room_name: Option<String>,
+    pub message: ChatMessage,
+    /// The part of the message around the first match, split so the matching words can be highlighted
+    pub snippet: Vec<SnippetPart>,
+}
+
+/// `GET /api/messages/search`, newest first
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MessageSearchResponse {
+    pub results: Vec<MessageSearchHit>,
+    /// Number of matching messages, on this page and others
+    pub total: u32,
+}
+
+/// `POST /api/direct`: open the direct conversation with a user, creating it if needed
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct OpenDirectChatRequest {
+    pub user_id: i32,
+}
+
+/// A direct conversation between the caller and one other user. Its messages go through
+/// the chat room `room_id` like any other room's.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DirectChat {
+    pub room_id: i32,
+    /// The other participant
+    pub user_id: i32,
+    pub username: String,
+}
+
+/// `GET /api/direct`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DirectChatListResponse {
+    pub chats: Vec<DirectChat>,
+}
+
+/// `POST /api/chatrooms/{id}/invites`. Without `user_id` the invite is a shareable link.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct CreateInviteRequest {
+    /// Invite this user only. Such invites can be used once.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<i32>,
+    /// Seconds until the invite expires, never if unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_in: Option<i64>,
+    /// How many users may join with a link, unlimited if unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_uses: Option<u32>,
+}
+
+/// An invite to a room, either for one user or as a link anyone can use
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RoomInvite {
+    pub invite_id: i32,
+    pub room_id: i32,
+    /// The invited user, for invites that are not links
+    pub user_id: Option<i32>,
+    /// The link's token, accepted with `POST /api/invites/{token}`
+    pub token: Option<String>,
+    /// Who created the invite, `None` if that user was deleted
+    pub created_by: Option<i32>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub max_uses: Option<u32>,
+    /// How many users joined with this invite
+    pub uses: u32,
+}
+
+/// `GET /api/chatrooms/{id}/invites`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct InviteListResponse {
+    pub invites: Vec<RoomInvite>,
+}
+
+/// `POST /api/invites/{token}`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AcceptInviteResponse {
+    pub room_id: i32,
+    pub room_name: String,
+}
+
+/// What a member may do in a room, from least to most privileged. Comparing two roles
+/// compares their privileges.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RoomRole {
+    /// Can read the room but not post or react
+    ReadOnly,
+    #[default]
+    Member,
+    /// Can also edit and delete other members' messages
+    Moderator,
+    /// Can also manage invites and the roles below admin
+    Admin,
+    /// Can also appoint admins and hand the room over. Every room has one owner, except
+    /// direct conversations, which have none.
+    Owner,
+}
+
+impl RoomRole {
+    /// The name used on the wire and in the database
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RoomRole::ReadOnly => "read_only",
+            RoomRole::Member => "member",
+            RoomRole::Moderator => "moderator",
+            RoomRole::Admin => "admin",
+            RoomRole::Owner => "owner",
+        }
+    }
+
+    /// The inverse of `as_str`
+    pub fn parse(role: &str) -> Option<RoomRole> {
+        match role {
+            "read_only" => Some(RoomRole::ReadOnly),
+            "member" => Some(RoomRole::Member),
+            "moderator" => Some(RoomRole::Moderator),
+            "admin" => Some(RoomRole::Admin),
+            "owner" => Some(RoomRole::Owner),
+            _ => None,
+        }
+    }
+}
+
+/// A member of a room and their role in it
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RoomMember {
+    pub user_id: i32,
+    pub username: String,
+    pub role: RoomRole,
+    /// The newest message the member has read, if they read any since joining
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_read_message_id: Option<i32>,
+}
+
+/// `GET /api/chatrooms/{id}/members`, most privileged first
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MemberListResponse {
+    pub members: Vec<RoomMember>,
+}
+
+/// `PUT /api/chatrooms/{id}/members/{user_id}/role`: promote or demote a member. Use a
+/// transfer to change the owner.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SetRoleRequest {
+    pub role: RoomRole,
+}
+
+/// `POST /api/chatrooms/{id}/transfer`: make another member the owner. The previous owner
+/// stays on as an admin.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TransferOwnershipRequest {
+    pub user_id: i32,
+}
+
+/// `POST /api/chatrooms/{id}/kick`, `POST /api/chatrooms/{id}/bans` and
+/// `POST /api/chatrooms/{id}/mutes`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ModerationRequest {
+    pub user_id: i32,
+    /// Shown to the room and kept in the moderation log
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// Seconds until a mute ends, never if unset. Kicks and bans ignore it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration: Option<i64>,
+}
+
+/// `PUT /api/chatrooms/{id}/slow_mode`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SlowModeRequest {
+    /// Seconds a member has to wait between two messages, 0 to turn slow mode off
+    pub interval: u32,
+}
+
+/// What a moderator did
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ModerationAction {
+    Kick,
+    Ban,
+    Unban,
+    Mute,
+    Unmute,
+    SlowMode,
+}
+
+impl ModerationAction {
+    /// The name used on the wire and in the database
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ModerationAction::Kick => "kick",
+            ModerationAction::Ban => "ban",
+            ModerationAction::Unban => "unban",
+            ModerationAction::Mute => "mute",
+            ModerationAction::Unmute => "unmute",
+            ModerationAction::SlowMode => "slow_mode",
+        }
+    }
+
+    /// The inverse of `as_str`
+    pub fn parse(action: &str) -> Option<ModerationAction> {
+        match action {
+            "kick" => Some(ModerationAction::Kick),
+            "ban" => Some(ModerationAction::Ban),
+            "unban" => Some(ModerationAction::Unban),
+            "mute" => Some(ModerationAction::Mute),
+            "unmute" => Some(ModerationAction::Unmute),
+            "slow_mode" => Some(ModerationAction::SlowMode),
+            _ => None,
+        }
+    }
+}
+
+/// One entry of a room's moderation log
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ModerationLogEntry {
+    pub log_id: i32,
+    pub action: ModerationAction,
+    /// Who took the action, `None` if that user was deleted
+    pub moderator_id: Option<i32>,
+    /// The user the action was taken against, `None` for slow mode
+    pub user_id: Option<i32>,
+    pub reason: Option<String>,
+    /// The length of a mute or the new slow mode interval, in seconds
+    pub duration: Option<i64>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// `GET /api/chatrooms/{id}/moderation_log`, newest first
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ModerationLogResponse {
+    pub entries: Vec<ModerationLogEntry>,
+}
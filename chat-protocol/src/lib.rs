@@ -0,0 +1,6 @@
+//! Wire types shared by the RustChat server (`rust_chat_application`) and the Yew client
+//! (`chatroom-yew`). Both sides depend on this crate, so a field change shows up as a
+//! compile error on the other side instead of a silently broken payload.
+
+pub mod api;
+pub mod ws;
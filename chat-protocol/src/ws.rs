@@ -0,0 +1,266 @@
+//! Messages exchanged over the `/ws/{chat}` socket. Every frame is a JSON object whose
+//! `type` field names the event, e.g. `{"type": "message", "content": "hi", ...}`.
+
+use std::fmt::Display;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::api::{ChatRoomDetails, RoomRole};
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ChatMessage {
+    pub message_id: i32,
+    pub user_id: i32,
+    pub username: String,
+    /// Empty once the message has been deleted
+    pub content: String,
+    pub timestamp: DateTime<Utc>,
+    /// When the content was last edited, if ever
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub edited_at: Option<DateTime<Utc>>,
+    /// The message was deleted and is kept as a tombstone
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub deleted: bool,
+    /// The message this one replies to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reply_to: Option<i32>,
+    /// The first message of the thread this reply belongs to. Replies are only listed in
+    /// their thread, not in the room's history.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thread_root_id: Option<i32>,
+    /// Number of replies in the thread this message starts
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub reply_count: u32,
+    /// Reactions to the message, one entry per emoji in the order they were first used
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub reactions: Vec<Reaction>,
+    /// Files sent with the message, in the order they were attached
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<Attachment>,
+}
+
+/// Everyone who reacted to a message with one emoji
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Reaction {
+    pub emoji: String,
+    pub count: u32,
+    /// The users who reacted, so a client can tell whether its own user is among them
+    pub user_ids: Vec<i32>,
+}
+
+/// A file uploaded to a room with `POST /api/chatrooms/{id}/attachments`. Members of the
+/// room download it from `GET /api/attachments/{attachment_id}`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Attachment {
+    pub attachment_id: i32,
+    pub file_name: String,
+    pub content_type: String,
+    /// Size in bytes
+    pub size: u64,
+    /// Dimensions in pixels, for images the server could read
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub width: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub height: Option<u32>,
+    /// Smaller copies of an image, smallest first. Images that are already small have none.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub thumbnails: Vec<Thumbnail>,
+}
+
+/// A scaled-down copy of an image attachment
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Thumbnail {
+    /// Path on the server, e.g. `/api/attachments/5/thumbnails/256`
+    pub url: String,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Attachment {
+    /// Whether clients can show the file inline as an image
+    pub fn is_image(&self) -> bool {
+        self.content_type.starts_with("image/")
+    }
+
+    /// The smallest thumbnail at least `width` pixels wide, or the largest one if none is,
+    /// `None` if the image has no thumbnails
+    pub fn thumbnail_for(&self, width: u32) -> Option<&Thumbnail> {
+        self.thumbnails
+            .iter()
+            .find(|thumbnail| thumbnail.width >= width)
+            .or_else(|| self.thumbnails.last())
+    }
+}
+
+pub(crate) fn is_false(value: &bool) -> bool {
+    !value
+}
+
+fn is_zero(value: &u32) -> bool {
+    *value == 0
+}
+
+impl Display for ChatMessage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.username, self.content)
+    }
+}
+
+/// The status of one member of a room
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct UserStatus {
+    pub user_id: i32,
+    pub status: PresenceStatus,
+}
+
+/// Whether a user has the app open, on any of their devices
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PresenceStatus {
+    /// Connected and recently active
+    Online,
+    /// Connected, but idle everywhere
+    Away,
+    /// Not connected
+    #[default]
+    Offline,
+}
+
+/// Events sent by a client
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientEvent {
+    /// Post a message to the room. The server assigns its `message_id` and timestamp and
+    /// answers with an `ack` (or an `error`) carrying the same client-generated `nonce`.
+    Message {
+        content: String,
+        nonce: String,
+        /// Post the message as a reply in the thread of this message
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        reply_to: Option<i32>,
+        /// Files the user uploaded to the room and sends with this message. The content
+        /// may be empty when there is at least one.
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        attachments: Vec<i32>,
+    },
+    /// The user started or stopped composing a message
+    Typing { is_typing: bool },
+    /// Replace the content of a message. Only its author or a room moderator may edit it.
+    Edit { message_id: i32, content: String },
+    /// Delete a message, leaving a tombstone. Only its author or a room moderator may delete it.
+    Delete { message_id: i32 },
+    /// React to a message in the room. Reacting twice with the same emoji has no effect.
+    AddReaction { message_id: i32, emoji: String },
+    /// Take back a reaction
+    RemoveReaction { message_id: i32, emoji: String },
+    /// Ask for a page of older messages, answered with a `history_batch`. Both fields are
+    /// optional: `before` defaults to the newest message and `limit` to the server's page size.
+    FetchHistory {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        before: Option<i32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        limit: Option<u32>,
+    },
+    /// The user has read the room up to this message. Older positions are ignored.
+    MarkRead { message_id: i32 },
+    /// Keeps the connection alive; the server closes connections that stop sending them.
+    /// `active` tells whether the user did anything in the page since the last heartbeat.
+    Heartbeat { active: bool },
+}
+
+/// Events sent by the server
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerEvent {
+    /// A message posted to the room
+    Message(ChatMessage),
+    /// A message in the room was edited
+    MessageEdited {
+        message_id: i32,
+        content: String,
+        edited_at: DateTime<Utc>,
+    },
+    /// A message in the room was deleted
+    MessageDeleted { message_id: i32 },
+    /// A user reacted to a message in the room
+    ReactionAdded {
+        message_id: i32,
+        user_id: i32,
+        emoji: String,
+    },
+    /// A user took back a reaction
+    ReactionRemoved {
+        message_id: i32,
+        user_id: i32,
+        emoji: String,
+    },
+    /// A user connected to the room
+    Join {
+        user_id: i32,
+        username: String,
+        timestamp: DateTime<Utc>,
+    },
+    /// A user disconnected from the room
+    Leave {
+        user_id: i32,
+        username: String,
+        timestamp: DateTime<Utc>,
+    },
+    /// Another user started or stopped composing a message
+    Typing {
+        user_id: i32,
+        username: String,
+        is_typing: bool,
+    },
+    /// A member has read the room up to this message
+    ReadReceipt { user_id: i32, message_id: i32 },
+    /// Something went wrong handling this client's request. `nonce` is set when the
+    /// request was a message that could not be stored.
+    Error {
+        message: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        nonce: Option<String>,
+    },
+    /// A page of earlier messages, newest first. Pass `next_before` as `before` to fetch
+    /// the page preceding it; it is `None` once the oldest message has been sent.
+    HistoryBatch {
+        messages: Vec<ChatMessage>,
+        next_before: Option<i32>,
+    },
+    /// The client's message with this `nonce` was stored under `message_id`. Sent before
+    /// the message itself is broadcast to the room.
+    Ack { nonce: String, message_id: i32 },
+    /// The status of every member of the room
+    Presence { users: Vec<UserStatus> },
+    /// A member came online, went idle or went offline
+    PresenceChanged { user_id: i32, status: PresenceStatus },
+    /// A member's role in the room changed
+    RoleChanged { user_id: i32, role: RoomRole },
+    /// A member was removed from the room. Their sockets are closed after this event.
+    Kicked {
+        user_id: i32,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        reason: Option<String>,
+    },
+    /// A member was removed from the room for good. Their sockets are closed after this
+    /// event.
+    Banned {
+        user_id: i32,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        reason: Option<String>,
+    },
+    /// A member may not post until `until`, or until unmuted if it is `None`
+    Muted {
+        user_id: i32,
+        until: Option<DateTime<Utc>>,
+    },
+    Unmuted { user_id: i32 },
+    /// Members now have to wait `interval` seconds between two messages; 0 turns slow
+    /// mode off
+    SlowModeChanged { interval: u32 },
+    /// The room was renamed, described, given a topic, archived or unarchived
+    RoomUpdated(ChatRoomDetails),
+    /// The room was deleted. Its sockets are closed after this event.
+    RoomDeleted,
+}
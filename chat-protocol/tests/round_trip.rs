@@ -0,0 +1,210 @@
+use chat_protocol::api::*;
+use chat_protocol::ws::*;
+use chrono::{TimeZone, Utc};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{json, Value};
+
+/// Serialize `value`, check it matches the expected wire format, and parse it back
+fn assert_wire_format<T>(value: T, expected: Value)
+where
+    T: Serialize + DeserializeOwned + PartialEq + std::fmt::Debug,
+{
+    let encoded = serde_json::to_value(&value).unwrap();
+    assert_eq!(encoded, expected);
+    let decoded: T = serde_json::from_value(encoded).unwrap();
+    assert_eq!(decoded, value);
+}
+
+fn sample_message() -> ChatMessage {
+    ChatMessage {
+        message_id: 42,
+        user_id: 7,
+        username: String::from("alice"),
+        content: String::from("hello"),
+        timestamp: Utc.with_ymd_and_hms(2024, 12, 1, 12, 0, 0).unwrap(),
+        edited_at: None,
+        deleted: false,
+        reply_to: None,
+        thread_root_id: None,
+        reply_count: 0,
+        reactions: Vec::new(),
+        attachments: Vec::new(),
+    }
+}
+
+#[test]
+fn client_message_event() {
+    assert_wire_format(
+        ClientEvent::Message { content: String::from("hello"), nonce: String::from("n-1"), reply_to: None, attachments: Vec::new() },
+        json!({"type": "message", "content": "hello", "nonce": "n-1"}),
+    );
+    assert_wire_format(
+        ClientEvent::Message { content: String::from("hi"), nonce: String::from("n-2"), reply_to: Some(42), attachments: Vec::new() },
+        json!({"type": "message", "content": "hi", "nonce": "n-2", "reply_to": 42}),
+    );
+}
+
+#[test]
+fn client_typing_event() {
+    assert_wire_format(
+        ClientEvent::Typing { is_typing: true },
+        json!({"type": "typing", "is_typing": true}),
+    );
+}
+
+#[test]
+fn server_message_event_is_flattened() {
+    assert_wire_format(
+        ServerEvent::Message(sample_message()),
+        json!({
+            "type": "message",
+            "message_id": 42,
+            "user_id": 7,
+            "username": "alice",
+            "content": "hello",
+            "timestamp": "2024-12-01T12:00:00Z"
+        }),
+    );
+}
+
+#[test]
+fn server_join_and_leave_events() {
+    let timestamp = Utc.with_ymd_and_hms(2024, 12, 1, 12, 0, 0).unwrap();
+    assert_wire_format(
+        ServerEvent::Join { user_id: 7, username: String::from("alice"), timestamp },
+        json!({"type": "join", "user_id": 7, "username": "alice", "timestamp": "2024-12-01T12:00:00Z"}),
+    );
+    assert_wire_format(
+        ServerEvent::Leave { user_id: 7, username: String::from("alice"), timestamp },
+        json!({"type": "leave", "user_id": 7, "username": "alice", "timestamp": "2024-12-01T12:00:00Z"}),
+    );
+}
+
+#[test]
+fn server_control_events() {
+    assert_wire_format(
+        ServerEvent::Typing { user_id: 7, username: String::from("alice"), is_typing: false },
+        json!({"type": "typing", "user_id": 7, "username": "alice", "is_typing": false}),
+    );
+    assert_wire_format(
+        ServerEvent::Error { message: String::from("boom"), nonce: None },
+        json!({"type": "error", "message": "boom"}),
+    );
+    assert_wire_format(
+        ServerEvent::Error { message: String::from("boom"), nonce: Some(String::from("n-1")) },
+        json!({"type": "error", "message": "boom", "nonce": "n-1"}),
+    );
+    assert_wire_format(
+        ServerEvent::Ack { nonce: String::from("n-1"), message_id: 42 },
+        json!({"type": "ack", "nonce": "n-1", "message_id": 42}),
+    );
+    assert_wire_format(
+        ServerEvent::Presence { users: vec![UserStatus { user_id: 7, status: PresenceStatus::Online }] },
+        json!({"type": "presence", "users": [{"user_id": 7, "status": "online"}]}),
+    );
+}
+
+#[test]
+fn server_history_batch_event() {
+    assert_wire_format(
+        ServerEvent::HistoryBatch { messages: vec![sample_message()], next_before: Some(42) },
+        json!({
+            "type": "history_batch",
+            "messages": [{
+                "message_id": 42,
+                "user_id": 7,
+                "username": "alice",
+                "content": "hello",
+                "timestamp": "2024-12-01T12:00:00Z"
+            }],
+            "next_before": 42
+        }),
+    );
+}
+
+#[test]
+fn auth_payloads() {
+    assert_wire_format(
+        SignupRequest {
+            username: String::from("alice"),
+            email: String::from("alice@example.com"),
+            password: String::from("secret"),
+        },
+        json!({"username": "alice", "email": "alice@example.com", "password": "secret"}),
+    );
+    assert_wire_format(
+        LoginRequest { email: String::from("alice@example.com"), password: String::from("secret") },
+        json!({"email": "alice@example.com", "password": "secret"}),
+    );
+    assert_wire_format(
+        LoginResponse {
+            message: String::from("User logged in successfully"),
+            uid: 7,
+            username: String::from("alice"),
+            token: String::from("abc"),
+            expires_in: 3600,
+        },
+        json!({
+            "message": "User logged in successfully",
+            "uid": 7,
+            "username": "alice",
+            "token": "abc",
+            "expires_in": 3600
+        }),
+    );
+    assert_wire_format(
+        FetchStatusResponse { online_users: vec![UserStatus { user_id: 7, status: PresenceStatus::Offline }] },
+        json!({"online_users": [{"user_id": 7, "status": "offline"}]}),
+    );
+}
+
+#[test]
+fn chat_room_payloads() {
+    assert_wire_format(
+        CreateChatRoomRequest { room_name: String::from("general"), visibility: RoomVisibility::Public },
+        json!({"room_name": "general"}),
+    );
+    assert_wire_format(
+        CreateChatRoomRequest { room_name: String::from("staff"), visibility: RoomVisibility::Private },
+        json!({"room_name": "staff", "visibility": "private"}),
+    );
+    assert_wire_format(
+        CreateChatRoomResponse { message: String::from("Chat room created"), room_id: 3 },
+        json!({"message": "Chat room created", "room_id": 3}),
+    );
+    assert_wire_format(JoinChatRoomRequest { room_id: 3 }, json!({"room_id": 3}));
+    assert_wire_format(
+        JoinChatRoomResponse { message: String::from("Joined chat room"), room_name: String::from("general") },
+        json!({"message": "Joined chat room", "room_name": "general"}),
+    );
+    assert_wire_format(LeaveChatRoomRequest { room_id: 3 }, json!({"room_id": 3}));
+    assert_wire_format(
+        ErrorResponse { error: String::from("Chat room not found") },
+        json!({"error": "Chat room not found"}),
+    );
+}
+
+#[test]
+fn thumbnail_for_picks_the_smallest_wide_enough() {
+    let thumbnail = |size: u32| Thumbnail {
+        url: format!("/api/attachments/5/thumbnails/{size}"),
+        width: size,
+        height: size * 3 / 4,
+    };
+    let photo = Attachment {
+        attachment_id: 5,
+        file_name: String::from("screenshot.png"),
+        content_type: String::from("image/png"),
+        size: 1_500_000,
+        width: Some(2560),
+        height: Some(1920),
+        thumbnails: vec![thumbnail(256), thumbnail(1024)],
+    };
+    assert!(photo.is_image());
+    assert_eq!(photo.thumbnail_for(200).map(|thumbnail| thumbnail.width), Some(256));
+    assert_eq!(photo.thumbnail_for(300).map(|thumbnail| thumbnail.width), Some(1024));
+    assert_eq!(photo.thumbnail_for(2000).map(|thumbnail| thumbnail.width), Some(1024));
+    let document = Attachment { content_type: String::from("application/pdf"), thumbnails: Vec::new(), ..photo };
+    assert!(!document.is_image());
+    assert_eq!(document.thumbnail_for(256), None);
+}
@@ -43,9 +43,10 @@ pub fn Login() -> Html {
                     Ok(response) => {
                         let token = Token {
                             user_id: response.uid,
-                            username: response.username.clone(),
+                            username: response.username,
+                            token: response.token,
                         };
-                        auth_ctx.login.emit((response.uid, response.username));
+                        auth_ctx.login.emit(token.clone());
                         log::info!("Login successful");
                         window().unwrap().alert_with_message("Login successful").unwrap();
                         let token = serde_json::to_string(&token).unwrap();
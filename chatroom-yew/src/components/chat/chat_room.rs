@@ -1,34 +1,178 @@
 use std::rc::Rc;
 use std::sync::Arc;
+use std::time::Duration;
 
+use chrono::{DateTime, Utc};
 use futures::lock::Mutex;
-use yew::platform::spawn_local;
+use yew::platform::{spawn_local, time::sleep};
 use yew::prelude::*;
 use yew_router::prelude::RouterScopeExt;
 
 use crate::{config, Route};
 use crate::context::auth::AuthContext;
-use crate::services::websocket::WebSocketService;
-use crate::types::chat::ChatMessage;
+use crate::services::chat_room::{
+    attachment_url, ban_user, fetch_thread, get_chat_room, kick_member, list_members, mute_member, open_direct_chat, set_member_role,
+    set_slow_mode, thumbnail_url, transfer_ownership, upload_attachment,
+};
+use crate::services::websocket::{ConnectionStatus, WebSocketService};
+use crate::types::chat::{Attachment, ChatMessage, ClientEvent, PresenceStatus, Reaction, ServerEvent, UserStatus};
+use crate::types::chat_room::{ChatRoomDetails, DirectChat, MemberListResponse, ModerationRequest, RoomMember, RoomRole, ThreadResponse};
+use crate::components::chat::invite_panel::InvitePanel;
+use crate::components::chat::member_panel::MemberPanel;
+use crate::components::chat::room_settings_panel::RoomSettingsPanel;
+use crate::components::chat::thread_panel::ThreadPanel;
 use crate::components::layout::Header;
 
+/// Older history is requested once the message list is scrolled this close to the top (px)
+const LOAD_HISTORY_THRESHOLD: i32 = 50;
+/// Reactions offered on every message, besides the ones already on it
+const QUICK_REACTIONS: [&str; 4] = ["👍", "❤️", "😂", "🎉"];
+/// How long the Mute button in the member panel mutes for, in seconds
+const MUTE_DURATION_SECS: i64 = 10 * 60;
+/// The message list counts as scrolled to the bottom within this distance of it (px)
+const READ_THRESHOLD: i32 = 20;
+/// While we keep typing, the typing event is repeated this often (ms), well within the
+/// server's timeout
+const TYPING_REPEAT_MS: i64 = 3000;
+/// We count as having stopped typing after this long without input
+const TYPING_IDLE: Duration = Duration::from_secs(4);
+/// Longest side of images shown in messages, in CSS pixels, as in room.css
+const IMAGE_PREVIEW_SIZE: u32 = 240;
+
 #[derive(Properties, PartialEq)]
 pub struct Props {
     pub id: String,
 }
 
+/// Delivery state of a message posted from this client
+#[derive(Clone, Copy, PartialEq)]
+enum Delivery {
+    /// Sent over the socket, waiting for the server's ack
+    Pending,
+    /// Stored by the server
+    Sent,
+    /// Could not be sent or stored
+    Failed,
+}
+
+/// One line in the message list
+enum ChatEntry {
+    Message(ChatMessage),
+    /// A message posted from this client, matched to the server's ack by its nonce. Its
+    /// `message_id` is only known once it has been acked.
+    Outgoing { nonce: String, message: ChatMessage, delivery: Delivery },
+    /// A notice from the server, e.g. someone joining or an error
+    Notice { text: String, timestamp: DateTime<Utc> },
+}
+
+/// The thread shown in the side panel
+struct OpenThread {
+    root_id: i32,
+    /// `None` until the thread has been fetched
+    root: Option<ChatMessage>,
+    replies: Vec<ChatMessage>,
+}
+
 pub struct ChatRoom
 {
-    messages: Vec<ChatMessage>,
+    entries: Vec<ChatEntry>,
+    members: Vec<UserStatus>,
     wss: Arc<Mutex<Option<WebSocketService>>>,
+    connection: ConnectionStatus,
     current_message: String,
+    /// The message being edited in the input box, if any
+    editing: Option<i32>,
+    /// Files uploaded to send with the next message
+    pending_attachments: Vec<Attachment>,
+    /// Number of uploads still in progress
+    uploading: usize,
+    /// When we last told the room we are typing, `None` while we are not
+    typing_sent_at: Option<DateTime<Utc>>,
+    /// Counts keystrokes, so that only the idle timer of the last one stops typing
+    typing_input: u32,
+    /// Other members composing a message, by user_id and username
+    typing: Vec<(i32, String)>,
+    /// The newest message we told the server we have read
+    last_read_sent: i32,
     is_authenticated: bool,
+    token: String,
+    user_id: i32,
+    username: String,
+    /// Counter that makes the nonces of outgoing messages unique
+    next_nonce: u32,
+    messages_ref: NodeRef,
+    thread: Option<OpenThread>,
+    /// Whether the invite panel is shown
+    invites_open: bool,
+    /// The room's members with their roles
+    roles: Vec<RoomMember>,
+    /// Whether the member panel is shown
+    members_open: bool,
+    /// The room's name, description and topic, `None` until loaded
+    details: Option<ChatRoomDetails>,
+    /// Whether the owner's settings panel is shown
+    settings_open: bool,
+    /// We were kicked or banned, or the room was deleted, so the socket is closed for good
+    removed: bool,
+    /// Cursor for the next older page of history, `None` once the first message is loaded
+    next_before: Option<i32>,
+    /// A page of history has been requested and not yet received
+    loading_history: bool,
+    /// Scroll height of the message list before older messages were prepended, used to
+    /// keep the visible messages in place
+    scroll_anchor: Option<i32>,
 }
 
 pub enum Msg {
     SendMessage,
+    /// The message with this nonce could not be written to the socket
+    SendFailed(String),
     UpdateMessage(String),
-    ReceiveMessage(ChatMessage),
+    /// No input since the keystroke with this count, see `typing_input`
+    TypingIdle(u32),
+    /// Load one of our messages into the input box to edit it
+    StartEdit(i32),
+    CancelEdit,
+    /// Upload files picked to send with the next message
+    AttachFiles(Vec<web_sys::File>),
+    AttachmentUploaded(Result<Attachment, String>),
+    /// Take a pending attachment off the next message
+    RemoveAttachment(i32),
+    DeleteMessage(i32),
+    /// Add our reaction with this emoji to a message, or take it back if we already reacted
+    ToggleReaction(i32, String),
+    /// Show the thread started by this message in the side panel
+    OpenThread(i32),
+    ThreadLoaded(i32, Result<ThreadResponse, String>),
+    CloseThread,
+    ToggleInvites,
+    ToggleMembers,
+    /// Reload the members and their roles, e.g. after someone joined
+    LoadMembers,
+    MembersLoaded(Result<MemberListResponse, String>),
+    DetailsLoaded(Result<ChatRoomDetails, String>),
+    ToggleSettings,
+    /// Give a member another role
+    SetRole(i32, RoomRole),
+    /// Hand the room over to a member
+    TransferOwnership(i32),
+    Kick(i32),
+    Ban(i32),
+    /// Mute a member for `MUTE_DURATION_SECS`
+    Mute(i32),
+    SetSlowMode(u32),
+    /// A role change or moderation action failed; the room updates through the socket
+    /// when one succeeds
+    MemberRequestFailed(String),
+    SendReply(String),
+    /// Start or resume the direct conversation with the author of a message
+    OpenDirectChat(i32),
+    DirectChatOpened(Result<DirectChat, String>),
+    ScrollMessages,
+    /// The user pressed a key, clicked or scrolled somewhere in the room
+    UserActivity,
+    ReceiveEvent(ServerEvent),
+    ConnectionChanged(ConnectionStatus),
     LeaveRoom,
 }
 
@@ -45,29 +189,88 @@ impl Component for ChatRoom {
         
         if !is_authenticated {
             return Self {
-                messages: Vec::new(),
+                entries: Vec::new(),
+                members: Vec::new(),
                 wss: Arc::new(Mutex::new(None)),
+                connection: ConnectionStatus::Connected,
                 current_message: String::new(),
+                editing: None,
+                pending_attachments: Vec::new(),
+                uploading: 0,
+                typing_sent_at: None,
+                typing_input: 0,
+                typing: Vec::new(),
+                last_read_sent: 0,
                 is_authenticated: false,
+                token: String::new(),
+                user_id: 0,
+                username: String::new(),
+                next_nonce: 0,
+                messages_ref: NodeRef::default(),
+                thread: None,
+                invites_open: false,
+                roles: Vec::new(),
+                members_open: false,
+                details: None,
+                settings_open: false,
+                removed: false,
+                next_before: None,
+                loading_history: false,
+                scroll_anchor: None,
             }
         }
 
         let link = ctx.link().clone();
-        let on_message = link.callback(Msg::ReceiveMessage);
+        let on_message = link.callback(Msg::ReceiveEvent);
+        let on_status = link.callback(Msg::ConnectionChanged);
 
         let wss = WebSocketService::new(
-            &format!("{}{}?user_id={}&username={}", config::WS_BASE_URL, ctx.props().id, auth_ctx.state.user_id.unwrap(), auth_ctx.state.username.clone().unwrap()),
-            auth_ctx.state.user_id.unwrap(),
-            auth_ctx.state.username.clone().unwrap(),
+            &format!("{}{}?token={}", config::WS_BASE_URL, ctx.props().id, auth_ctx.state.token.clone().unwrap()),
             on_message,
+            on_status,
         );
 
+        link.send_message(Msg::LoadMembers);
+        {
+            let token = auth_ctx.state.token.clone().unwrap_or_default();
+            let room_id = ctx.props().id.parse().unwrap_or_default();
+            let link = link.clone();
+            spawn_local(async move {
+                link.send_message(Msg::DetailsLoaded(get_chat_room(token, room_id).await));
+            });
+        }
+
         log::debug!("ChatRoom create() finished");
         Self {
-            messages: Vec::new(),
+            entries: Vec::new(),
+            members: Vec::new(),
             wss: Arc::new(Mutex::new(Some(wss))),
+            connection: ConnectionStatus::Connected,
             current_message: String::new(),
+            editing: None,
+            pending_attachments: Vec::new(),
+            uploading: 0,
+            typing_sent_at: None,
+            typing_input: 0,
+            typing: Vec::new(),
+            last_read_sent: 0,
             is_authenticated,
+            token: auth_ctx.state.token.clone().unwrap_or_default(),
+            user_id: auth_ctx.state.user_id.unwrap_or_default(),
+            username: auth_ctx.state.username.clone().unwrap_or_default(),
+            next_nonce: 0,
+            messages_ref: NodeRef::default(),
+            thread: None,
+            invites_open: false,
+            roles: Vec::new(),
+            members_open: false,
+            details: None,
+            settings_open: false,
+            removed: false,
+            next_before: None,
+            // The server sends the newest page as soon as the socket opens
+            loading_history: true,
+            scroll_anchor: None,
         }
     }
 
@@ -78,18 +281,59 @@ impl Component for ChatRoom {
         match msg {
             Msg::SendMessage => {
                 log::debug!("Msg::SendMessage received");
+                if let Some(message_id) = self.editing {
+                    if message_clone.is_empty() {
+                        return false;
+                    }
+                    self.editing = None;
+                    self.send_event(ClientEvent::Edit { message_id, content: message_clone });
+                    self.current_message.clear();
+                    return true;
+                }
+                if message_clone.is_empty() && self.pending_attachments.is_empty() {
+                    log::debug!("Message is empty, skipping...");
+                    return false;
+                }
+                self.stop_typing();
+                // Wait for the uploads, so none of them is left behind
+                if self.uploading > 0 {
+                    return false;
+                }
+                let attachments = std::mem::take(&mut self.pending_attachments);
+                let attachment_ids: Vec<i32> = attachments.iter().map(|attachment| attachment.attachment_id).collect();
+
+                // Show the message right away and track it until the server acks it
+                self.next_nonce += 1;
+                let nonce = format!("{}-{}", Utc::now().timestamp_millis(), self.next_nonce);
+                self.entries.push(ChatEntry::Outgoing {
+                    nonce: nonce.clone(),
+                    message: ChatMessage {
+                        message_id: 0,
+                        user_id: self.user_id,
+                        username: self.username.clone(),
+                        content: message_clone.clone(),
+                        timestamp: Utc::now(),
+                        edited_at: None,
+                        deleted: false,
+                        reply_to: None,
+                        thread_root_id: None,
+                        reply_count: 0,
+                        reactions: Vec::new(),
+                        attachments,
+                    },
+                    delivery: Delivery::Pending,
+                });
+
+                let link = ctx.link().clone();
                 spawn_local(async move {
                     let mut wss = wss_clone.lock().await;
                     match wss.as_mut() {
                         Some(ref mut wss) => {
-                            if message_clone.is_empty() {
-                                log::debug!("Message is empty, skipping...");
-                                return;
-                            }
                             log::debug!("Calling wss.send_message()...");
-                            match wss.send_message(&message_clone).await {
+                            match wss.send_message(&message_clone, &nonce, None, attachment_ids).await {
                                 Some(err) => {
                                     log::error!("Error sending message: {:?}", err);
+                                    link.send_message(Msg::SendFailed(nonce));
                                 }
                                 None => {
                                     log::debug!("Message sent successfully");
@@ -98,18 +342,459 @@ impl Component for ChatRoom {
                         }
                         None => {
                             log::error!("WebSocket connection not established");
+                            link.send_message(Msg::SendFailed(nonce));
                         }
                     }
                 });
                 self.current_message.clear();
                 true
             }
+            Msg::SendFailed(nonce) => self.set_delivery(&nonce, None, Delivery::Failed),
             Msg::UpdateMessage(msg) => {
                 self.current_message = msg;
+                if self.editing.is_none() {
+                    self.typed(ctx);
+                }
+                true
+            }
+            Msg::TypingIdle(input) => {
+                if input == self.typing_input {
+                    self.stop_typing();
+                }
+                false
+            }
+            Msg::StartEdit(message_id) => {
+                let Some(content) = self.find_message_mut(message_id).map(|message| message.content.clone()) else {
+                    return false;
+                };
+                self.stop_typing();
+                self.current_message = content;
+                self.editing = Some(message_id);
+                true
+            }
+            Msg::CancelEdit => {
+                self.editing = None;
+                self.current_message.clear();
+                true
+            }
+            Msg::AttachFiles(files) => {
+                self.uploading += files.len();
+                let room_id = self.room_id(ctx);
+                for file in files {
+                    let (token, link) = (self.token.clone(), ctx.link().clone());
+                    spawn_local(async move {
+                        link.send_message(Msg::AttachmentUploaded(upload_attachment(token, room_id, file).await));
+                    });
+                }
+                true
+            }
+            Msg::AttachmentUploaded(result) => {
+                self.uploading = self.uploading.saturating_sub(1);
+                match result {
+                    Ok(attachment) => self.pending_attachments.push(attachment),
+                    Err(err) => self.entries.push(ChatEntry::Notice { text: format!("Could not attach the file: {}", err), timestamp: Utc::now() }),
+                }
+                true
+            }
+            Msg::RemoveAttachment(attachment_id) => {
+                self.pending_attachments.retain(|attachment| attachment.attachment_id != attachment_id);
+                true
+            }
+            Msg::ToggleReaction(message_id, emoji) => {
+                let user_id = self.user_id;
+                let reacted = self.copies_mut(message_id).next().is_some_and(|message| {
+                    message
+                        .reactions
+                        .iter()
+                        .any(|reaction| reaction.emoji == emoji && reaction.user_ids.contains(&user_id))
+                });
+                // The message updates once the server broadcasts the change
+                if reacted {
+                    self.send_event(ClientEvent::RemoveReaction { message_id, emoji });
+                } else {
+                    self.send_event(ClientEvent::AddReaction { message_id, emoji });
+                }
+                false
+            }
+            Msg::DeleteMessage(message_id) => {
+                self.send_event(ClientEvent::Delete { message_id });
+                false
+            }
+            Msg::OpenThread(root_id) => {
+                self.thread = Some(OpenThread { root_id, root: None, replies: Vec::new() });
+                let token = self.token.clone();
+                let link = ctx.link().clone();
+                spawn_local(async move {
+                    link.send_message(Msg::ThreadLoaded(root_id, fetch_thread(token, root_id).await));
+                });
+                true
+            }
+            Msg::ThreadLoaded(root_id, result) => match (result, self.thread.as_mut()) {
+                // Ignore threads that were closed or replaced while loading
+                (Ok(response), Some(thread)) if thread.root_id == root_id => {
+                    thread.root = Some(response.root);
+                    thread.replies = response.replies;
+                    true
+                }
+                (Err(err), _) => {
+                    self.thread = None;
+                    self.entries.push(ChatEntry::Notice { text: err, timestamp: Utc::now() });
+                    true
+                }
+                _ => false,
+            },
+            Msg::CloseThread => {
+                self.thread = None;
+                true
+            }
+            Msg::ToggleInvites => {
+                self.invites_open = !self.invites_open;
+                true
+            }
+            Msg::ToggleMembers => {
+                self.members_open = !self.members_open;
+                true
+            }
+            Msg::LoadMembers => {
+                let token = self.token.clone();
+                let room_id = self.room_id(ctx);
+                let link = ctx.link().clone();
+                spawn_local(async move {
+                    link.send_message(Msg::MembersLoaded(list_members(token, room_id).await));
+                });
+                false
+            }
+            Msg::MembersLoaded(Ok(response)) => {
+                self.roles = response.members;
+                true
+            }
+            Msg::MembersLoaded(Err(err)) => {
+                log::error!("Failed to load members: {}", err);
+                false
+            }
+            Msg::DetailsLoaded(Ok(details)) => {
+                self.details = Some(details);
+                true
+            }
+            Msg::DetailsLoaded(Err(err)) => {
+                log::error!("Failed to load room details: {}", err);
+                false
+            }
+            Msg::ToggleSettings => {
+                self.settings_open = !self.settings_open;
+                true
+            }
+            Msg::SetRole(user_id, role) => {
+                let token = self.token.clone();
+                let room_id = self.room_id(ctx);
+                let link = ctx.link().clone();
+                spawn_local(async move {
+                    if let Err(err) = set_member_role(token, room_id, user_id, role).await {
+                        link.send_message(Msg::MemberRequestFailed(err));
+                    }
+                });
+                false
+            }
+            Msg::TransferOwnership(user_id) => {
+                let token = self.token.clone();
+                let room_id = self.room_id(ctx);
+                let link = ctx.link().clone();
+                spawn_local(async move {
+                    if let Err(err) = transfer_ownership(token, room_id, user_id).await {
+                        link.send_message(Msg::MemberRequestFailed(err));
+                    }
+                });
+                false
+            }
+            Msg::Kick(user_id) => {
+                let request = ModerationRequest { user_id, reason: None, duration: None };
+                let (token, room_id, link) = (self.token.clone(), self.room_id(ctx), ctx.link().clone());
+                spawn_local(async move {
+                    if let Err(err) = kick_member(token, room_id, request).await {
+                        link.send_message(Msg::MemberRequestFailed(err));
+                    }
+                });
+                false
+            }
+            Msg::Ban(user_id) => {
+                let request = ModerationRequest { user_id, reason: None, duration: None };
+                let (token, room_id, link) = (self.token.clone(), self.room_id(ctx), ctx.link().clone());
+                spawn_local(async move {
+                    if let Err(err) = ban_user(token, room_id, request).await {
+                        link.send_message(Msg::MemberRequestFailed(err));
+                    }
+                });
+                false
+            }
+            Msg::Mute(user_id) => {
+                let request = ModerationRequest { user_id, reason: None, duration: Some(MUTE_DURATION_SECS) };
+                let (token, room_id, link) = (self.token.clone(), self.room_id(ctx), ctx.link().clone());
+                spawn_local(async move {
+                    if let Err(err) = mute_member(token, room_id, request).await {
+                        link.send_message(Msg::MemberRequestFailed(err));
+                    }
+                });
+                false
+            }
+            Msg::SetSlowMode(interval) => {
+                let (token, room_id, link) = (self.token.clone(), self.room_id(ctx), ctx.link().clone());
+                spawn_local(async move {
+                    if let Err(err) = set_slow_mode(token, room_id, interval).await {
+                        link.send_message(Msg::MemberRequestFailed(err));
+                    }
+                });
+                false
+            }
+            Msg::MemberRequestFailed(err) => {
+                self.entries.push(ChatEntry::Notice { text: err, timestamp: Utc::now() });
                 true
             }
-            Msg::ReceiveMessage(msg) => {
-                self.messages.push(msg);
+            Msg::SendReply(content) => {
+                let Some(root_id) = self.thread.as_ref().map(|thread| thread.root_id) else {
+                    return false;
+                };
+                // Replies show up in the panel once the server broadcasts them
+                self.next_nonce += 1;
+                let nonce = format!("{}-{}", Utc::now().timestamp_millis(), self.next_nonce);
+                self.send_event(ClientEvent::Message { content, nonce, reply_to: Some(root_id), attachments: Vec::new() });
+                false
+            }
+            Msg::ScrollMessages => {
+                self.mark_read();
+                let Some(list) = self.messages_ref.cast::<web_sys::Element>() else {
+                    return false;
+                };
+                if self.loading_history || list.scroll_top() > LOAD_HISTORY_THRESHOLD {
+                    return false;
+                }
+                let Some(before) = self.next_before else {
+                    return false;
+                };
+
+                log::debug!("Loading history before message {}", before);
+                self.loading_history = true;
+                self.scroll_anchor = Some(list.scroll_height());
+                self.send_event(ClientEvent::FetchHistory { before: Some(before), limit: None });
+                true
+            }
+            Msg::UserActivity => {
+                // Reported with the next heartbeat. If the socket is busy sending, whatever
+                // it sends already tells the server we are here.
+                if let Some(wss) = self.wss.try_lock() {
+                    if let Some(wss) = wss.as_ref() {
+                        wss.mark_active();
+                    }
+                }
+                false
+            }
+            Msg::ReceiveEvent(event) => match event {
+                ServerEvent::Message(msg @ ChatMessage { thread_root_id: Some(root_id), .. }) => {
+                    if let Some(root) = self.find_message_mut(root_id) {
+                        root.reply_count += 1;
+                    }
+                    if let Some(thread) = self.thread.as_mut().filter(|thread| thread.root_id == root_id) {
+                        if !thread.replies.iter().any(|reply| reply.message_id == msg.message_id) {
+                            thread.replies.push(msg);
+                        }
+                    }
+                    true
+                }
+                ServerEvent::Message(msg) => {
+                    self.typing.retain(|(user_id, _)| *user_id != msg.user_id);
+                    // Our own acked messages and messages replayed after a reconnect are
+                    // already listed; take the server's copy of them
+                    match self.find_message_mut(msg.message_id) {
+                        Some(message) => *message = msg,
+                        None => self.entries.push(ChatEntry::Message(msg)),
+                    }
+                    true
+                }
+                ServerEvent::MessageEdited { message_id, content, edited_at } => {
+                    for message in self.copies_mut(message_id) {
+                        message.content = content.clone();
+                        message.edited_at = Some(edited_at);
+                    }
+                    true
+                }
+                ServerEvent::ReactionAdded { message_id, user_id, emoji } => {
+                    for message in self.copies_mut(message_id) {
+                        add_reaction(&mut message.reactions, &emoji, user_id);
+                    }
+                    true
+                }
+                ServerEvent::ReactionRemoved { message_id, user_id, emoji } => {
+                    for message in self.copies_mut(message_id) {
+                        remove_reaction(&mut message.reactions, &emoji, user_id);
+                    }
+                    true
+                }
+                ServerEvent::MessageDeleted { message_id } => {
+                    if self.editing == Some(message_id) {
+                        self.editing = None;
+                        self.current_message.clear();
+                    }
+                    for message in self.copies_mut(message_id) {
+                        message.content.clear();
+                        message.deleted = true;
+                    }
+                    true
+                }
+                ServerEvent::Ack { nonce, message_id } => self.set_delivery(&nonce, Some(message_id), Delivery::Sent),
+                ServerEvent::Error { message, nonce: Some(nonce) } => {
+                    log::error!("Message {} was not stored: {}", nonce, message);
+                    // Thread replies are not tracked, so report those as a notice
+                    if !self.set_delivery(&nonce, None, Delivery::Failed) {
+                        self.entries.push(ChatEntry::Notice { text: message, timestamp: Utc::now() });
+                    }
+                    true
+                }
+                ServerEvent::HistoryBatch { messages, next_before } => {
+                    // Pages arrive newest first and each one is older than everything shown
+                    self.entries.splice(0..0, messages.into_iter().rev().map(ChatEntry::Message));
+                    self.next_before = next_before;
+                    self.loading_history = false;
+                    true
+                }
+                ServerEvent::Join { user_id, username, timestamp } => {
+                    if !self.roles.iter().any(|member| member.user_id == user_id) {
+                        ctx.link().send_message(Msg::LoadMembers);
+                    }
+                    self.entries.push(ChatEntry::Notice {
+                        text: format!("User {} (user_id: {}) joined the chat room", username, user_id),
+                        timestamp,
+                    });
+                    true
+                }
+                ServerEvent::Leave { user_id, username, timestamp } => {
+                    self.typing.retain(|(typing_id, _)| *typing_id != user_id);
+                    self.entries.push(ChatEntry::Notice {
+                        text: format!("User {} (user_id: {}) left the chat room", username, user_id),
+                        timestamp,
+                    });
+                    true
+                }
+                ServerEvent::Error { message, nonce: None } => {
+                    self.loading_history = false;
+                    self.scroll_anchor = None;
+                    self.entries.push(ChatEntry::Notice { text: message, timestamp: Utc::now() });
+                    true
+                }
+                ServerEvent::Presence { users } => {
+                    self.members = users;
+                    true
+                }
+                ServerEvent::PresenceChanged { user_id, status } => {
+                    match self.members.iter_mut().find(|member| member.user_id == user_id) {
+                        Some(member) => member.status = status,
+                        None => self.members.push(UserStatus { user_id, status }),
+                    }
+                    true
+                }
+                ServerEvent::RoleChanged { user_id, role } => {
+                    match self.roles.iter_mut().find(|member| member.user_id == user_id) {
+                        Some(member) => member.role = role,
+                        None => ctx.link().send_message(Msg::LoadMembers),
+                    }
+                    // Keep the most privileged members first
+                    self.roles.sort_by(|a, b| b.role.cmp(&a.role).then_with(|| a.username.cmp(&b.username)));
+                    true
+                }
+                ServerEvent::Kicked { user_id, reason } => {
+                    self.member_removed(user_id, "kicked from", reason);
+                    true
+                }
+                ServerEvent::Banned { user_id, reason } => {
+                    self.member_removed(user_id, "banned from", reason);
+                    true
+                }
+                ServerEvent::Muted { user_id, until } => {
+                    let text = match until {
+                        Some(until) => format!("{} was muted until {}", self.member_name(user_id), until.format("%H:%M")),
+                        None => format!("{} was muted", self.member_name(user_id)),
+                    };
+                    self.entries.push(ChatEntry::Notice { text, timestamp: Utc::now() });
+                    true
+                }
+                ServerEvent::Unmuted { user_id } => {
+                    let text = format!("{} was unmuted", self.member_name(user_id));
+                    self.entries.push(ChatEntry::Notice { text, timestamp: Utc::now() });
+                    true
+                }
+                ServerEvent::SlowModeChanged { interval } => {
+                    let text = match interval {
+                        0 => "Slow mode is off".to_string(),
+                        secs => format!("Slow mode is on: one message every {} seconds", secs),
+                    };
+                    self.entries.push(ChatEntry::Notice { text, timestamp: Utc::now() });
+                    true
+                }
+                ServerEvent::RoomUpdated(details) => {
+                    if let Some(text) = self.details.as_ref().and_then(|current| describe_room_change(current, &details)) {
+                        self.entries.push(ChatEntry::Notice { text, timestamp: Utc::now() });
+                    }
+                    self.details = Some(details);
+                    true
+                }
+                ServerEvent::RoomDeleted => {
+                    self.entries.push(ChatEntry::Notice { text: "The chat room was deleted".to_string(), timestamp: Utc::now() });
+                    self.removed = true;
+                    self.members_open = false;
+                    self.settings_open = false;
+                    self.close_socket();
+                    true
+                }
+                ServerEvent::ReadReceipt { user_id, message_id } => {
+                    match self.roles.iter_mut().find(|member| member.user_id == user_id) {
+                        Some(member) => {
+                            member.last_read_message_id = member.last_read_message_id.max(Some(message_id));
+                            true
+                        }
+                        None => false,
+                    }
+                }
+                ServerEvent::Typing { user_id, username, is_typing } => {
+                    if user_id == self.user_id {
+                        return false;
+                    }
+                    self.typing.retain(|(typing_id, _)| *typing_id != user_id);
+                    if is_typing {
+                        self.typing.push((user_id, username));
+                    }
+                    true
+                }
+            },
+            Msg::ConnectionChanged(status) => {
+                if status == ConnectionStatus::Reconnecting {
+                    // Unacked messages may never be acked on the new connection. Any of them
+                    // that were stored come back in the replay.
+                    for entry in self.entries.iter_mut() {
+                        if let ChatEntry::Outgoing { delivery, .. } = entry {
+                            if *delivery == Delivery::Pending {
+                                *delivery = Delivery::Failed;
+                            }
+                        }
+                    }
+                    // Typing events are not replayed, and the new connection starts out idle
+                    self.typing.clear();
+                    self.typing_sent_at = None;
+                }
+                self.connection = status;
+                true
+            }
+            Msg::OpenDirectChat(user_id) => {
+                let token = self.token.clone();
+                let link = ctx.link().clone();
+                spawn_local(async move {
+                    link.send_message(Msg::DirectChatOpened(open_direct_chat(token, user_id).await));
+                });
+                false
+            }
+            Msg::DirectChatOpened(Ok(chat)) => {
+                ctx.link().navigator().unwrap().push(&Route::ChatRoom { id: chat.room_id });
+                false
+            }
+            Msg::DirectChatOpened(Err(err)) => {
+                self.entries.push(ChatEntry::Notice { text: err, timestamp: Utc::now() });
                 true
             }
             Msg::LeaveRoom => {
@@ -120,6 +805,17 @@ impl Component for ChatRoom {
         }
     }
 
+    fn rendered(&mut self, _ctx: &Context<Self>, _first_render: bool) {
+        // Keep the messages that were on screen in place after prepending older ones
+        if let Some(previous_height) = self.scroll_anchor.take() {
+            if let Some(list) = self.messages_ref.cast::<web_sys::Element>() {
+                list.set_scroll_top(list.scroll_top() + list.scroll_height() - previous_height);
+            }
+        }
+        // New messages that fit on screen without scrolling are read as well
+        self.mark_read();
+    }
+
     fn view(&self, ctx: &Context<Self>) -> Html {
         // Check if user is logged in yet
         if !self.is_authenticated {
@@ -146,48 +842,551 @@ impl Component for ChatRoom {
             Msg::UpdateMessage(input.value())
         });
 
+        let on_scroll = ctx.link().callback(|_: Event| Msg::ScrollMessages);
+        // Scroll events also fire when new messages scroll the list, so only input counts as activity
+        let on_key_activity = ctx.link().callback(|_: KeyboardEvent| Msg::UserActivity);
+        let on_pointer_activity = ctx.link().callback(|_: PointerEvent| Msg::UserActivity);
+        let on_wheel_activity = ctx.link().callback(|_: WheelEvent| Msg::UserActivity);
+
+        let on_attach = ctx.link().batch_callback(|e: Event| {
+            let input = e.target_unchecked_into::<web_sys::HtmlInputElement>();
+            let files: Vec<web_sys::File> = input
+                .files()
+                .map(|list| (0..list.length()).filter_map(|i| list.get(i)).collect())
+                .unwrap_or_default();
+            // Let the same file be picked again later
+            input.set_value("");
+            (!files.is_empty()).then_some(Msg::AttachFiles(files))
+        });
+
         let on_back = ctx.link().callback(|_: MouseEvent| {
             log::debug!("Back button clicked");
             Msg::LeaveRoom
         });
+        let own_role = self.own_role();
+        let archived = self.details.as_ref().is_some_and(|details| details.archived);
         
     
         html! {
             <>
                 <Header />
-                <div class="chat-room-container">
+                <div class="chat-room-container" onkeydown={on_key_activity} onpointerdown={on_pointer_activity} onwheel={on_wheel_activity}>
                     <div class="room-info">
-                        <h2>{ format!("Room ID: {}", ctx.props().id) }</h2>
-                        <button class="back-button" onclick={on_back}>{"Leave"}</button>
+                        if let Some(details) = &self.details {
+                            <h2>{ &details.room_name }</h2>
+                            if let Some(topic) = &details.topic {
+                                <span class="room-topic">{ topic }</span>
+                            }
+                        } else {
+                            <h2>{ format!("Room ID: {}", ctx.props().id) }</h2>
+                        }
+                        <span class="room-members">
+                            { format!(
+                                "{} members, {} online, {} away",
+                                self.members.len(),
+                                self.members.iter().filter(|m| m.status == PresenceStatus::Online).count(),
+                                self.members.iter().filter(|m| m.status == PresenceStatus::Away).count(),
+                            ) }
+                        </span>
+                        <span class="room-buttons">
+                            <button class="back-button" onclick={ctx.link().callback(|_: MouseEvent| Msg::ToggleMembers)}>{"Members"}</button>
+                            if own_role >= RoomRole::Admin {
+                                <button class="back-button" onclick={ctx.link().callback(|_: MouseEvent| Msg::ToggleInvites)}>{"Invites"}</button>
+                            }
+                            if own_role == RoomRole::Owner && !self.removed {
+                                <button class="back-button" onclick={ctx.link().callback(|_: MouseEvent| Msg::ToggleSettings)}>{"Settings"}</button>
+                            }
+                            <button class="back-button" onclick={on_back}>{"Leave"}</button>
+                        </span>
                     </div>
+                    if self.connection == ConnectionStatus::Reconnecting {
+                        <div class="connection-status">{"Connection lost, reconnecting..."}</div>
+                    }
+                    <div class="chat-body">
                     <div class="chat-window">
-                        <div class="messages">
+                        <div class="messages" ref={self.messages_ref.clone()} onscroll={on_scroll}>
+                        {
+                            if self.loading_history {
+                                html! { <div class="history-status">{"Loading older messages..."}</div> }
+                            } else if self.next_before.is_none() {
+                                html! { <div class="history-status">{"This is the beginning of the conversation"}</div> }
+                            } else {
+                                html! {}
+                            }
+                        }
                         // Messages will be displayed here
                         {
-                            for self.messages.iter().map(|msg| {
-                                html! {
-                                    <div class="message">
-                                        <span class="username">{ &msg.username }</span>
-                                        <span class="timestamp">{ format!("{} UTC", msg.timestamp.format("%Y-%m-%d %H:%M:%S").to_string()) }</span>
-                                        <span class="content">{ &msg.content }</span>
+                            for self.entries.iter().map(|entry| match entry {
+                                ChatEntry::Message(msg) => self.view_message(ctx, msg, None),
+                                ChatEntry::Outgoing { message, delivery, .. } => self.view_message(ctx, message, Some(*delivery)),
+                                ChatEntry::Notice { text, timestamp } => html! {
+                                    <div class="message notice">
+                                        <span class="timestamp">{ format!("{} UTC", timestamp.format("%Y-%m-%d %H:%M:%S").to_string()) }</span>
+                                        <span class="content">{ text }</span>
                                     </div>
-                                }
+                                },
                             })
                         }
                         </div>
+                        if let Some(text) = typing_text(&self.typing) {
+                            <div class="typing-indicator">{ text }</div>
+                        }
                     </div>
-                    <form class="send-message-box" onsubmit={on_submit}>
-                        <input
-                            type="text"
-                            placeholder="Type your message..."
-                            class="message-input"
-                            value={self.current_message.clone()}
-                            oninput={on_input}
+                    if let Some(thread) = &self.thread {
+                        <ThreadPanel
+                            root={thread.root.clone()}
+                            replies={thread.replies.clone()}
+                            on_reply={ctx.link().callback(Msg::SendReply)}
+                            on_close={ctx.link().callback(|_: ()| Msg::CloseThread)}
+                        />
+                    }
+                    if self.members_open {
+                        <MemberPanel
+                            members={self.roles.clone()}
+                            user_id={self.user_id}
+                            own_role={own_role}
+                            on_set_role={ctx.link().callback(|(user_id, role): (i32, RoomRole)| Msg::SetRole(user_id, role))}
+                            on_transfer={ctx.link().callback(Msg::TransferOwnership)}
+                            on_kick={ctx.link().callback(Msg::Kick)}
+                            on_ban={ctx.link().callback(Msg::Ban)}
+                            on_mute={ctx.link().callback(Msg::Mute)}
+                            on_slow_mode={ctx.link().callback(Msg::SetSlowMode)}
+                            on_close={ctx.link().callback(|_: ()| Msg::ToggleMembers)}
+                        />
+                    }
+                    if self.invites_open && own_role >= RoomRole::Admin {
+                        <InvitePanel
+                            room_id={self.room_id(ctx)}
+                            token={self.token.clone()}
+                            on_close={ctx.link().callback(|_: ()| Msg::ToggleInvites)}
+                        />
+                    }
+                    if let Some(details) = self.details.clone().filter(|_| self.settings_open && own_role == RoomRole::Owner) {
+                        <RoomSettingsPanel
+                            room={details}
+                            token={self.token.clone()}
+                            on_close={ctx.link().callback(|_: ()| Msg::ToggleSettings)}
                         />
-                        <button type="submit" class="send-button">{"Send"}</button>
-                    </form>
+                    }
+                    </div>
+                    if self.removed {
+                        <div class="send-message-box read-only">{"You are no longer a member of this room"}</div>
+                    } else if archived {
+                        <div class="send-message-box read-only">{"This room is archived and can only be read"}</div>
+                    } else if own_role == RoomRole::ReadOnly {
+                        <div class="send-message-box read-only">{"You can read this room but not post in it"}</div>
+                    } else {
+                        if !self.pending_attachments.is_empty() || self.uploading > 0 {
+                            <div class="pending-attachments">
+                                { for self.pending_attachments.iter().map(|attachment| {
+                                    let attachment_id = attachment.attachment_id;
+                                    html! {
+                                        <span class="pending-attachment">
+                                            { format!("{} ({})", attachment.file_name, format_size(attachment.size)) }
+                                            <button type="button" title="Remove" onclick={ctx.link().callback(move |_: MouseEvent| Msg::RemoveAttachment(attachment_id))}>{"×"}</button>
+                                        </span>
+                                    }
+                                }) }
+                                if self.uploading > 0 {
+                                    <span class="pending-attachment">{"Uploading..."}</span>
+                                }
+                            </div>
+                        }
+                        <form class="send-message-box" onsubmit={on_submit}>
+                            if self.editing.is_none() {
+                                <label class="attach-button" title="Attach files">
+                                    {"📎"}
+                                    <input type="file" multiple=true onchange={on_attach} />
+                                </label>
+                            }
+                            <input
+                                type="text"
+                                placeholder="Type your message..."
+                                class="message-input"
+                                value={self.current_message.clone()}
+                                oninput={on_input}
+                            />
+                            if self.editing.is_some() {
+                                <button type="button" class="cancel-button" onclick={ctx.link().callback(|_: MouseEvent| Msg::CancelEdit)}>{"Cancel"}</button>
+                                <button type="submit" class="send-button">{"Save"}</button>
+                            } else {
+                                <button type="submit" class="send-button" disabled={self.uploading > 0}>{"Send"}</button>
+                            }
+                        </form>
+                    }
                 </div>
             </>
         }
     }
 }
+
+impl ChatRoom {
+    fn view_message(&self, ctx: &Context<Self>, message: &ChatMessage, delivery: Option<Delivery>) -> Html {
+        if message.deleted {
+            return html! {
+                <div class="message deleted">
+                    <span class="username">{ &message.username }</span>
+                    <span class="content">{"This message was deleted"}</span>
+                </div>
+            };
+        }
+
+        let (class, status) = match delivery {
+            Some(Delivery::Pending) => ("message pending", Some("Sending...")),
+            Some(Delivery::Sent) => ("message", Some("Sent")),
+            Some(Delivery::Failed) => ("message failed", Some("Failed to send")),
+            None => ("message", None),
+        };
+        // Only stored messages can be changed or replied to, and the server decides who may
+        // change them
+        let stored = message.message_id != 0;
+        let own = stored && message.user_id == self.user_id;
+        let moderated = stored && self.own_role() >= RoomRole::Moderator;
+        let message_id = message.message_id;
+        let author_id = message.user_id;
+        let quick_reactions = QUICK_REACTIONS
+            .into_iter()
+            .filter(|emoji| !message.reactions.iter().any(|reaction| reaction.emoji == *emoji));
+
+        html! {
+            <div class={class}>
+                if message.user_id != self.user_id {
+                    <span class="username direct-link" title="Send a direct message" onclick={ctx.link().callback(move |_: MouseEvent| Msg::OpenDirectChat(author_id))}>
+                        { &message.username }
+                    </span>
+                } else {
+                    <span class="username">{ &message.username }</span>
+                }
+                <span class="timestamp">{ format!("{} UTC", message.timestamp.format("%Y-%m-%d %H:%M:%S").to_string()) }</span>
+                <span class="content">
+                    { &message.content }
+                    if message.edited_at.is_some() {
+                        <span class="edited">{" (edited)"}</span>
+                    }
+                </span>
+                if !message.attachments.is_empty() {
+                    <span class="attachments">
+                        { for message.attachments.iter().map(|attachment| self.view_attachment(attachment)) }
+                    </span>
+                }
+                if stored {
+                    <span class="reactions">
+                        { for message.reactions.iter().map(|reaction| self.view_reaction(ctx, message_id, reaction)) }
+                        { for quick_reactions.map(|emoji| html! {
+                            <button class="reaction quick" onclick={ctx.link().callback(move |_: MouseEvent| Msg::ToggleReaction(message_id, emoji.to_string()))}>
+                                { emoji }
+                            </button>
+                        }) }
+                    </span>
+                    <span class="message-actions">
+                        if message.reply_count > 0 {
+                            <button class="thread-link" onclick={ctx.link().callback(move |_: MouseEvent| Msg::OpenThread(message_id))}>
+                                { format!("{} replies", message.reply_count) }
+                            </button>
+                        }
+                        <button onclick={ctx.link().callback(move |_: MouseEvent| Msg::OpenThread(message_id))}>{"Reply"}</button>
+                        if own {
+                            <button onclick={ctx.link().callback(move |_: MouseEvent| Msg::StartEdit(message_id))}>{"Edit"}</button>
+                        }
+                        if own || moderated {
+                            <button onclick={ctx.link().callback(move |_: MouseEvent| Msg::DeleteMessage(message_id))}>{"Delete"}</button>
+                        }
+                    </span>
+                }
+                if let Some(status) = status {
+                    <span class="delivery">{ status }</span>
+                }
+                if let Some(seen_by) = self.seen_by(message) {
+                    <span class="delivery">{ seen_by }</span>
+                }
+            </div>
+        }
+    }
+
+    /// "Seen by Alice, Bob" under our newest message, for the members who have read it
+    fn seen_by(&self, message: &ChatMessage) -> Option<String> {
+        if message.user_id != self.user_id || message.message_id == 0 {
+            return None;
+        }
+        let newest_own = self.entries.iter().rev().find_map(|entry| match entry {
+            ChatEntry::Message(message) | ChatEntry::Outgoing { message, .. }
+                if message.user_id == self.user_id && message.message_id != 0 => Some(message.message_id),
+            _ => None,
+        });
+        if newest_own != Some(message.message_id) {
+            return None;
+        }
+        let readers: Vec<&str> = self
+            .roles
+            .iter()
+            .filter(|member| member.user_id != self.user_id)
+            .filter(|member| member.last_read_message_id.is_some_and(|read| read >= message.message_id))
+            .map(|member| member.username.as_str())
+            .collect();
+        (!readers.is_empty()).then(|| format!("Seen by {}", readers.join(", ")))
+    }
+
+    /// Tell the server we have read the newest message once the list is scrolled to the
+    /// bottom
+    fn mark_read(&mut self) {
+        let Some(list) = self.messages_ref.cast::<web_sys::Element>() else {
+            return;
+        };
+        if list.scroll_top() + list.client_height() + READ_THRESHOLD < list.scroll_height() {
+            return;
+        }
+        let newest = self.entries.iter().rev().find_map(|entry| match entry {
+            ChatEntry::Message(message) | ChatEntry::Outgoing { message, .. } if message.message_id != 0 => Some(message.message_id),
+            _ => None,
+        });
+        if let Some(message_id) = newest.filter(|message_id| *message_id > self.last_read_sent) {
+            self.last_read_sent = message_id;
+            self.send_event(ClientEvent::MarkRead { message_id });
+        }
+    }
+
+    fn view_reaction(&self, ctx: &Context<Self>, message_id: i32, reaction: &Reaction) -> Html {
+        let class = if reaction.user_ids.contains(&self.user_id) { "reaction own" } else { "reaction" };
+        let emoji = reaction.emoji.clone();
+        html! {
+            <button class={class} onclick={ctx.link().callback(move |_: MouseEvent| Msg::ToggleReaction(message_id, emoji.clone()))}>
+                { format!("{} {}", reaction.emoji, reaction.count) }
+            </button>
+        }
+    }
+
+    /// Images are shown in place, other files as download links
+    fn view_attachment(&self, attachment: &Attachment) -> Html {
+        let url = attachment_url(&self.token, attachment.attachment_id);
+        if attachment.is_image() {
+            // The smallest thumbnail that looks sharp, and the size it is shown at, so the
+            // message keeps its height while the image loads
+            let src = match attachment.thumbnail_for(IMAGE_PREVIEW_SIZE) {
+                Some(thumbnail) => thumbnail_url(&self.token, thumbnail),
+                None => url.clone(),
+            };
+            let (width, height) = match (attachment.width, attachment.height) {
+                (Some(width), Some(height)) => {
+                    let (width, height) = preview_size(width, height);
+                    (Some(width.to_string()), Some(height.to_string()))
+                }
+                _ => (None, None),
+            };
+            html! {
+                <a class="attachment-image" href={url} target="_blank" rel="noopener">
+                    <img {src} {width} {height} alt={attachment.file_name.clone()} loading="lazy" />
+                </a>
+            }
+        } else {
+            html! {
+                <a class="attachment-file" href={url} download={attachment.file_name.clone()}>
+                    { format!("{} ({})", attachment.file_name, format_size(attachment.size)) }
+                </a>
+            }
+        }
+    }
+
+    fn room_id(&self, ctx: &Context<Self>) -> i32 {
+        ctx.props().id.parse().unwrap_or_default()
+    }
+
+    /// The name of a member for notices, their user_id if they are not listed
+    fn member_name(&self, user_id: i32) -> String {
+        self.roles
+            .iter()
+            .find(|member| member.user_id == user_id)
+            .map(|member| member.username.clone())
+            .unwrap_or_else(|| format!("User {}", user_id))
+    }
+
+    /// Announce that a member was kicked or banned. If it was us, stop the socket from
+    /// reconnecting; the server has already closed it.
+    fn member_removed(&mut self, user_id: i32, action: &str, reason: Option<String>) {
+        let who = if user_id == self.user_id { "You were".to_string() } else { format!("{} was", self.member_name(user_id)) };
+        let text = match reason {
+            Some(reason) => format!("{} {} the chat room: {}", who, action, reason),
+            None => format!("{} {} the chat room", who, action),
+        };
+        self.entries.push(ChatEntry::Notice { text, timestamp: Utc::now() });
+        self.roles.retain(|member| member.user_id != user_id);
+
+        if user_id == self.user_id {
+            self.removed = true;
+            self.members_open = false;
+            self.settings_open = false;
+            self.close_socket();
+        }
+    }
+
+    /// Tell the room we are typing, at most every `TYPING_REPEAT_MS`, and that we stopped
+    /// once the input is cleared or has been idle for `TYPING_IDLE`
+    fn typed(&mut self, ctx: &Context<Self>) {
+        if self.current_message.is_empty() {
+            self.stop_typing();
+            return;
+        }
+        let now = Utc::now();
+        let due = self
+            .typing_sent_at
+            .is_none_or(|sent_at| (now - sent_at).num_milliseconds() >= TYPING_REPEAT_MS);
+        if due {
+            self.typing_sent_at = Some(now);
+            self.send_event(ClientEvent::Typing { is_typing: true });
+        }
+
+        self.typing_input = self.typing_input.wrapping_add(1);
+        let input = self.typing_input;
+        let link = ctx.link().clone();
+        spawn_local(async move {
+            sleep(TYPING_IDLE).await;
+            link.send_message(Msg::TypingIdle(input));
+        });
+    }
+
+    fn stop_typing(&mut self) {
+        if self.typing_sent_at.take().is_some() {
+            self.send_event(ClientEvent::Typing { is_typing: false });
+        }
+    }
+
+    /// Stop the socket from reconnecting once the server has closed it for good
+    fn close_socket(&self) {
+        let wss = self.wss.clone();
+        spawn_local(async move {
+            if let Some(wss) = wss.lock().await.as_ref() {
+                wss.close();
+            }
+        });
+    }
+
+    /// Our role in the room, a plain member until the member list has loaded
+    fn own_role(&self) -> RoomRole {
+        self.roles
+            .iter()
+            .find(|member| member.user_id == self.user_id)
+            .map(|member| member.role)
+            .unwrap_or_default()
+    }
+
+    /// The listed message with this `message_id`, including our own acked messages
+    fn find_message_mut(&mut self, message_id: i32) -> Option<&mut ChatMessage> {
+        self.entries.iter_mut().find_map(|entry| match entry {
+            ChatEntry::Message(message) | ChatEntry::Outgoing { message, .. }
+                if message.message_id == message_id => Some(message),
+            _ => None,
+        })
+    }
+
+    /// Every copy of a message on screen: in the room's list and in the open thread
+    fn copies_mut(&mut self, message_id: i32) -> impl Iterator<Item = &mut ChatMessage> + '_ {
+        let listed = self.entries.iter_mut().filter_map(|entry| match entry {
+            ChatEntry::Message(message) | ChatEntry::Outgoing { message, .. } => Some(message),
+            ChatEntry::Notice { .. } => None,
+        });
+        let threaded = self
+            .thread
+            .iter_mut()
+            .flat_map(|thread| thread.root.iter_mut().chain(thread.replies.iter_mut()));
+        listed.chain(threaded).filter(move |message| message.message_id == message_id)
+    }
+
+    /// Send an event over the socket in the background, logging failures
+    fn send_event(&self, event: ClientEvent) {
+        let wss = self.wss.clone();
+        spawn_local(async move {
+            match wss.lock().await.as_mut() {
+                Some(wss) => {
+                    if let Some(err) = wss.send_event(&event).await {
+                        log::error!("Error sending event: {:?}", err);
+                    }
+                }
+                None => log::error!("WebSocket connection not established"),
+            }
+        });
+    }
+
+    /// Update the delivery state of the outgoing message with this nonce, and its
+    /// `message_id` once known. Returns whether anything changed.
+    fn set_delivery(&mut self, nonce: &str, message_id: Option<i32>, state: Delivery) -> bool {
+        for entry in self.entries.iter_mut() {
+            if let ChatEntry::Outgoing { nonce: entry_nonce, message, delivery } = entry {
+                if entry_nonce == nonce {
+                    if let Some(message_id) = message_id {
+                        message.message_id = message_id;
+                    }
+                    *delivery = state;
+                    return true;
+                }
+            }
+        }
+        false
+    }
+}
+
+/// A notice for a change to the room's details, `None` if nothing shown in the room changed
+fn describe_room_change(current: &ChatRoomDetails, updated: &ChatRoomDetails) -> Option<String> {
+    if updated.archived != current.archived {
+        Some(if updated.archived { "The chat room was archived" } else { "The chat room was unarchived" }.to_string())
+    } else if updated.room_name != current.room_name {
+        Some(format!("The chat room was renamed to {}", updated.room_name))
+    } else if updated.topic != current.topic {
+        Some(match &updated.topic {
+            Some(topic) => format!("The topic was set to {}", topic),
+            None => "The topic was cleared".to_string(),
+        })
+    } else {
+        None
+    }
+}
+
+/// An image scaled down to fit `IMAGE_PREVIEW_SIZE`, keeping its aspect ratio
+fn preview_size(width: u32, height: u32) -> (u32, u32) {
+    let longest = width.max(height).max(1);
+    if longest <= IMAGE_PREVIEW_SIZE {
+        return (width, height);
+    }
+    let scale = |side: u32| ((side as u64 * IMAGE_PREVIEW_SIZE as u64) / longest as u64).max(1) as u32;
+    (scale(width), scale(height))
+}
+
+/// "Alice is typing…" for the members composing a message, if any
+fn typing_text(typing: &[(i32, String)]) -> Option<String> {
+    match typing {
+        [] => None,
+        [(_, name)] => Some(format!("{} is typing…", name)),
+        [(_, first), (_, second)] => Some(format!("{} and {} are typing…", first, second)),
+        _ => Some(String::from("Several people are typing…")),
+    }
+}
+
+/// A file size for people, e.g. `2.4 MB`
+fn format_size(bytes: u64) -> String {
+    match bytes {
+        0..1024 => format!("{} B", bytes),
+        1024..1_048_576 => format!("{:.1} KB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MB", bytes as f64 / 1_048_576.0),
+    }
+}
+
+/// Count a reaction broadcast by the server, unless it is already counted
+fn add_reaction(reactions: &mut Vec<Reaction>, emoji: &str, user_id: i32) {
+    match reactions.iter_mut().find(|reaction| reaction.emoji == emoji) {
+        Some(reaction) if reaction.user_ids.contains(&user_id) => {}
+        Some(reaction) => {
+            reaction.count += 1;
+            reaction.user_ids.push(user_id);
+        }
+        None => reactions.push(Reaction { emoji: emoji.to_string(), count: 1, user_ids: vec![user_id] }),
+    }
+}
+
+/// Drop a reaction taken back, and the emoji with it once nobody is left
+fn remove_reaction(reactions: &mut Vec<Reaction>, emoji: &str, user_id: i32) {
+    for reaction in reactions.iter_mut().filter(|reaction| reaction.emoji == emoji) {
+        if let Some(position) = reaction.user_ids.iter().position(|id| *id == user_id) {
+            reaction.user_ids.remove(position);
+            reaction.count -= 1;
+        }
+    }
+    reactions.retain(|reaction| reaction.count > 0);
+}
@@ -0,0 +1,164 @@
+use yew::platform::spawn_local;
+use yew::prelude::*;
+
+use crate::services::chat_room;
+use crate::types::chat_room::{CreateInviteRequest, RoomInvite};
+
+#[derive(Properties, PartialEq)]
+pub struct InvitePanelProps {
+    pub room_id: i32,
+    /// Session token of the current user
+    pub token: String,
+    pub on_close: Callback<()>,
+}
+
+/// Side panel where a room's admin creates, lists and revokes its invites
+#[function_component]
+pub fn InvitePanel(props: &InvitePanelProps) -> Html {
+    let invites = use_state(Vec::<RoomInvite>::new);
+    let error = use_state(|| Option::<String>::None);
+    // Options of the next invite, left empty for the server's defaults
+    let user_id = use_state(|| Option::<i32>::None);
+    let expires_in_hours = use_state(|| Option::<i64>::None);
+    let max_uses = use_state(|| Option::<u32>::None);
+
+    {
+        let invites = invites.clone();
+        let error = error.clone();
+        use_effect_with((props.room_id, props.token.clone()), move |(room_id, token)| {
+            let (room_id, token) = (*room_id, token.clone());
+            spawn_local(async move {
+                match chat_room::list_invites(token, room_id).await {
+                    Ok(response) => invites.set(response.invites),
+                    Err(err) => error.set(Some(err)),
+                }
+            });
+        });
+    }
+
+    // Create an invite for the user in the box, or a link when `for_user` is false
+    let create = |for_user: bool| {
+        let invites = invites.clone();
+        let error = error.clone();
+        let room_id = props.room_id;
+        let token = props.token.clone();
+        let request = CreateInviteRequest {
+            user_id: if for_user { *user_id } else { None },
+            expires_in: expires_in_hours.map(|hours| hours * 3600),
+            max_uses: if for_user { None } else { *max_uses },
+        };
+        Callback::from(move |_: MouseEvent| {
+            if for_user && request.user_id.is_none() {
+                error.set(Some("Enter the ID of the user to invite".to_string()));
+                return;
+            }
+            let invites = invites.clone();
+            let error = error.clone();
+            let token = token.clone();
+            let request = request.clone();
+            spawn_local(async move {
+                match chat_room::create_invite(token, room_id, request).await {
+                    Ok(invite) => {
+                        let mut list = (*invites).clone();
+                        list.push(invite);
+                        invites.set(list);
+                        error.set(None);
+                    }
+                    Err(err) => error.set(Some(err)),
+                }
+            });
+        })
+    };
+
+    let view_invite = |invite: &RoomInvite| {
+        let on_revoke = {
+            let invites = invites.clone();
+            let error = error.clone();
+            let room_id = props.room_id;
+            let token = props.token.clone();
+            let invite_id = invite.invite_id;
+            Callback::from(move |_: MouseEvent| {
+                let invites = invites.clone();
+                let error = error.clone();
+                let token = token.clone();
+                spawn_local(async move {
+                    match chat_room::revoke_invite(token, room_id, invite_id).await {
+                        Ok(_) => invites.set(invites.iter().filter(|invite| invite.invite_id != invite_id).cloned().collect()),
+                        Err(err) => error.set(Some(err)),
+                    }
+                });
+            })
+        };
+        let target = match (&invite.token, invite.user_id) {
+            (Some(token), _) => format!("{}/invite/{}", web_sys::window().unwrap().location().origin().unwrap(), token),
+            (None, Some(user_id)) => format!("User #{}", user_id),
+            (None, None) => String::new(),
+        };
+        let uses = match invite.max_uses {
+            Some(max_uses) => format!("{}/{} uses", invite.uses, max_uses),
+            None => format!("{} uses", invite.uses),
+        };
+        let expiry = match invite.expires_at {
+            Some(expires_at) => format!("expires {} UTC", expires_at.format("%Y-%m-%d %H:%M")),
+            None => String::from("never expires"),
+        };
+        html! {
+            <li class="invite">
+                <span class="invite-target">{ target }</span>
+                <span class="invite-details">{ format!("{}, {}", uses, expiry) }</span>
+                <button class="thread-close" onclick={on_revoke}>{"Revoke"}</button>
+            </li>
+        }
+    };
+
+    let on_close = {
+        let on_close = props.on_close.clone();
+        Callback::from(move |_: MouseEvent| on_close.emit(()))
+    };
+
+    html! {
+        <div class="thread-panel invite-panel">
+            <div class="thread-header">
+                <h3>{"Invites"}</h3>
+                <button class="thread-close" onclick={on_close}>{"Close"}</button>
+            </div>
+            if let Some(error) = (*error).clone() {
+                <div class="history-status">{ error }</div>
+            }
+            <ul class="invites">
+                { for invites.iter().map(view_invite) }
+            </ul>
+            <div class="invite-form">
+                <input
+                    type="number"
+                    min="1"
+                    placeholder="Expires in (hours)"
+                    onchange={let expires_in_hours = expires_in_hours.clone(); move |e: Event| {
+                        let input = e.target_unchecked_into::<web_sys::HtmlInputElement>();
+                        expires_in_hours.set(input.value().parse().ok());
+                    }}
+                />
+                <input
+                    type="number"
+                    min="1"
+                    placeholder="Max uses of a link"
+                    onchange={let max_uses = max_uses.clone(); move |e: Event| {
+                        let input = e.target_unchecked_into::<web_sys::HtmlInputElement>();
+                        max_uses.set(input.value().parse().ok());
+                    }}
+                />
+                <button class="send-button" onclick={create(false)}>{"Create link"}</button>
+                <input
+                    type="number"
+                    min="1"
+                    placeholder="User ID"
+                    onchange={let user_id = user_id.clone(); move |e: Event| {
+                        let input = e.target_unchecked_into::<web_sys::HtmlInputElement>();
+                        user_id.set(input.value().parse().ok());
+                    }}
+                />
+                <button class="send-button" onclick={create(true)}>{"Invite user"}</button>
+            </div>
+        </div>
+    }
+}
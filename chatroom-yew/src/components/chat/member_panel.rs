@@ -0,0 +1,139 @@
+use yew::prelude::*;
+
+use crate::types::chat_room::{RoomMember, RoomRole};
+
+/// Roles an admin or owner can hand out, most privileged first
+const ASSIGNABLE_ROLES: [RoomRole; 4] = [RoomRole::Admin, RoomRole::Moderator, RoomRole::Member, RoomRole::ReadOnly];
+/// Slow mode intervals a moderator can pick, in seconds
+const SLOW_MODE_INTERVALS: [u32; 5] = [0, 10, 30, 60, 300];
+
+#[derive(Properties, PartialEq)]
+pub struct MemberPanelProps {
+    pub members: Vec<RoomMember>,
+    /// The current user
+    pub user_id: i32,
+    pub own_role: RoomRole,
+    pub on_set_role: Callback<(i32, RoomRole)>,
+    pub on_transfer: Callback<i32>,
+    pub on_kick: Callback<i32>,
+    pub on_ban: Callback<i32>,
+    pub on_mute: Callback<i32>,
+    pub on_slow_mode: Callback<u32>,
+    pub on_close: Callback<()>,
+}
+
+/// Side panel listing a room's members and their roles. Moderators can kick, ban and mute
+/// the members below them and set slow mode, admins can change the roles below their own,
+/// and the owner can hand the room over.
+#[function_component]
+pub fn MemberPanel(props: &MemberPanelProps) -> Html {
+    let view_member = |member: &RoomMember| {
+        let user_id = member.user_id;
+        let outranked = user_id != props.user_id && member.role < props.own_role;
+        let manageable = outranked && props.own_role >= RoomRole::Admin;
+        let moderatable = outranked && props.own_role >= RoomRole::Moderator;
+        let on_change = {
+            let on_set_role = props.on_set_role.clone();
+            Callback::from(move |e: Event| {
+                let select = e.target_unchecked_into::<web_sys::HtmlSelectElement>();
+                if let Some(role) = RoomRole::parse(&select.value()) {
+                    on_set_role.emit((user_id, role));
+                }
+            })
+        };
+        let on_transfer = {
+            let on_transfer = props.on_transfer.clone();
+            Callback::from(move |_: MouseEvent| on_transfer.emit(user_id))
+        };
+        let on_kick = {
+            let on_kick = props.on_kick.clone();
+            Callback::from(move |_: MouseEvent| on_kick.emit(user_id))
+        };
+        let on_ban = {
+            let on_ban = props.on_ban.clone();
+            Callback::from(move |_: MouseEvent| on_ban.emit(user_id))
+        };
+        let on_mute = {
+            let on_mute = props.on_mute.clone();
+            Callback::from(move |_: MouseEvent| on_mute.emit(user_id))
+        };
+
+        html! {
+            <li class="member">
+                <span class="member-name">{ &member.username }</span>
+                if manageable {
+                    <select class="member-role" onchange={on_change}>
+                        { for ASSIGNABLE_ROLES.iter().filter(|role| **role < props.own_role).map(|role| html! {
+                            <option value={role.as_str()} selected={*role == member.role}>{ role_label(*role) }</option>
+                        }) }
+                    </select>
+                } else {
+                    <span class="member-role">{ role_label(member.role) }</span>
+                }
+                if props.own_role == RoomRole::Owner && user_id != props.user_id {
+                    <button class="thread-close" onclick={on_transfer}>{"Make owner"}</button>
+                }
+                if moderatable {
+                    <span class="member-actions">
+                        <button class="thread-close" onclick={on_mute}>{"Mute"}</button>
+                        <button class="thread-close" onclick={on_kick}>{"Kick"}</button>
+                        <button class="thread-close" onclick={on_ban}>{"Ban"}</button>
+                    </span>
+                }
+            </li>
+        }
+    };
+
+    let on_close = {
+        let on_close = props.on_close.clone();
+        Callback::from(move |_: MouseEvent| on_close.emit(()))
+    };
+    let on_slow_mode = {
+        let on_slow_mode = props.on_slow_mode.clone();
+        Callback::from(move |e: Event| {
+            let select = e.target_unchecked_into::<web_sys::HtmlSelectElement>();
+            if let Ok(interval) = select.value().parse() {
+                on_slow_mode.emit(interval);
+            }
+        })
+    };
+
+    html! {
+        <div class="thread-panel member-panel">
+            <div class="thread-header">
+                <h3>{"Members"}</h3>
+                <button class="thread-close" onclick={on_close}>{"Close"}</button>
+            </div>
+            if props.own_role >= RoomRole::Moderator {
+                // The room's current interval is not known here, only changes are announced
+                <select class="slow-mode" onchange={on_slow_mode}>
+                    <option value="" selected=true disabled=true>{"Slow mode"}</option>
+                    { for SLOW_MODE_INTERVALS.iter().map(|interval| html! {
+                        <option value={interval.to_string()}>{ slow_mode_label(*interval) }</option>
+                    }) }
+                </select>
+            }
+            <ul class="members">
+                { for props.members.iter().map(view_member) }
+            </ul>
+        </div>
+    }
+}
+
+fn role_label(role: RoomRole) -> &'static str {
+    match role {
+        RoomRole::Owner => "Owner",
+        RoomRole::Admin => "Admin",
+        RoomRole::Moderator => "Moderator",
+        RoomRole::Member => "Member",
+        RoomRole::ReadOnly => "Read-only",
+    }
+}
+
+fn slow_mode_label(interval: u32) -> String {
+    match interval {
+        0 => "Off".to_string(),
+        secs if secs % 60 == 0 => format!("{} min", secs / 60),
+        secs => format!("{} s", secs),
+    }
+}
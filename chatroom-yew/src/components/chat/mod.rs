@@ -1,2 +1,6 @@
 mod chat_room;
-pub use chat_room::ChatRoom;
\ No newline at end of file
+mod invite_panel;
+mod member_panel;
+mod room_settings_panel;
+mod thread_panel;
+pub use chat_room::ChatRoom;
@@ -0,0 +1,136 @@
+use yew::platform::spawn_local;
+use yew::prelude::*;
+
+use crate::services::chat_room;
+use crate::types::chat_room::{ChatRoomDetails, UpdateChatRoomRequest};
+
+#[derive(Properties, PartialEq)]
+pub struct RoomSettingsPanelProps {
+    pub room: ChatRoomDetails,
+    /// Session token of the current user
+    pub token: String,
+    pub on_close: Callback<()>,
+}
+
+/// Side panel where a room's owner renames it, describes it, sets its topic, archives it
+/// or deletes it. The room shows the changes once the server broadcasts them.
+#[function_component]
+pub fn RoomSettingsPanel(props: &RoomSettingsPanelProps) -> Html {
+    let room_name = use_state(|| props.room.room_name.clone());
+    let description = use_state(|| props.room.description.clone().unwrap_or_default());
+    let topic = use_state(|| props.room.topic.clone().unwrap_or_default());
+    let error = use_state(|| Option::<String>::None);
+    // Deleting takes a second click
+    let confirm_delete = use_state(|| false);
+
+    let on_save = {
+        let room = props.room.clone();
+        let token = props.token.clone();
+        let (room_name, description, topic, error) = (room_name.clone(), description.clone(), topic.clone(), error.clone());
+        Callback::from(move |e: SubmitEvent| {
+            e.prevent_default();
+            // Only send what changed, so a rename does not race someone else's topic change
+            let changed = |value: &str, current: Option<&str>| (value != current.unwrap_or_default()).then(|| value.to_string());
+            let request = UpdateChatRoomRequest {
+                room_name: changed(&room_name, Some(room.room_name.as_str())),
+                description: changed(&description, room.description.as_deref()),
+                topic: changed(&topic, room.topic.as_deref()),
+            };
+            if request == UpdateChatRoomRequest::default() {
+                return;
+            }
+            let (room_id, token, error) = (room.room_id, token.clone(), error.clone());
+            spawn_local(async move {
+                match chat_room::update_chat_room(token, room_id, request).await {
+                    Ok(_) => error.set(None),
+                    Err(err) => error.set(Some(err)),
+                }
+            });
+        })
+    };
+
+    let on_archive = {
+        let (room_id, archived) = (props.room.room_id, props.room.archived);
+        let (token, error) = (props.token.clone(), error.clone());
+        Callback::from(move |_: MouseEvent| {
+            let (token, error) = (token.clone(), error.clone());
+            spawn_local(async move {
+                match chat_room::set_archived(token, room_id, !archived).await {
+                    Ok(_) => error.set(None),
+                    Err(err) => error.set(Some(err)),
+                }
+            });
+        })
+    };
+
+    let on_delete = {
+        let room_id = props.room.room_id;
+        let (token, error, confirm_delete) = (props.token.clone(), error.clone(), confirm_delete.clone());
+        Callback::from(move |_: MouseEvent| {
+            if !*confirm_delete {
+                confirm_delete.set(true);
+                return;
+            }
+            let (token, error) = (token.clone(), error.clone());
+            spawn_local(async move {
+                if let Err(err) = chat_room::delete_chat_room(token, room_id).await {
+                    error.set(Some(err));
+                }
+            });
+        })
+    };
+
+    let on_close = {
+        let on_close = props.on_close.clone();
+        Callback::from(move |_: MouseEvent| on_close.emit(()))
+    };
+
+    html! {
+        <div class="thread-panel settings-panel">
+            <div class="thread-header">
+                <h3>{"Room settings"}</h3>
+                <button class="thread-close" onclick={on_close}>{"Close"}</button>
+            </div>
+            if let Some(error) = (*error).clone() {
+                <div class="history-status">{ error }</div>
+            }
+            <form class="settings-form" onsubmit={on_save}>
+                <label for="room-name">{"Name"}</label>
+                <input
+                    id="room-name"
+                    type="text"
+                    value={(*room_name).clone()}
+                    oninput={let room_name = room_name.clone(); move |e: InputEvent| {
+                        room_name.set(e.target_unchecked_into::<web_sys::HtmlInputElement>().value());
+                    }}
+                />
+                <label for="room-description">{"Description"}</label>
+                <textarea
+                    id="room-description"
+                    value={(*description).clone()}
+                    oninput={let description = description.clone(); move |e: InputEvent| {
+                        description.set(e.target_unchecked_into::<web_sys::HtmlTextAreaElement>().value());
+                    }}
+                />
+                <label for="room-topic">{"Topic"}</label>
+                <input
+                    id="room-topic"
+                    type="text"
+                    value={(*topic).clone()}
+                    oninput={let topic = topic.clone(); move |e: InputEvent| {
+                        topic.set(e.target_unchecked_into::<web_sys::HtmlInputElement>().value());
+                    }}
+                />
+                <button type="submit" class="send-button">{"Save"}</button>
+            </form>
+            <div class="settings-actions">
+                <button class="thread-close" onclick={on_archive}>
+                    { if props.room.archived { "Unarchive" } else { "Archive" } }
+                </button>
+                <button class="thread-close danger" onclick={on_delete}>
+                    { if *confirm_delete { "Click again to delete for good" } else { "Delete room" } }
+                </button>
+            </div>
+        </div>
+    }
+}
@@ -0,0 +1,105 @@
+use yew::prelude::*;
+
+use crate::types::chat::ChatMessage;
+
+#[derive(Properties, PartialEq)]
+pub struct ThreadPanelProps {
+    /// The message that started the thread, `None` while it is loading
+    pub root: Option<ChatMessage>,
+    /// Replies in the thread, oldest first
+    pub replies: Vec<ChatMessage>,
+    /// Post a reply with this content
+    pub on_reply: Callback<String>,
+    pub on_close: Callback<()>,
+}
+
+/// Side panel showing one thread of a room and a box to reply in it
+#[function_component]
+pub fn ThreadPanel(props: &ThreadPanelProps) -> Html {
+    let reply = use_state(String::new);
+
+    let on_input = {
+        let reply = reply.clone();
+        Callback::from(move |e: InputEvent| {
+            let input = e.target_unchecked_into::<web_sys::HtmlInputElement>();
+            reply.set(input.value());
+        })
+    };
+
+    let on_submit = {
+        let reply = reply.clone();
+        let on_reply = props.on_reply.clone();
+        Callback::from(move |e: SubmitEvent| {
+            e.prevent_default();
+            if reply.is_empty() {
+                return;
+            }
+            on_reply.emit((*reply).clone());
+            reply.set(String::new());
+        })
+    };
+
+    let on_close = {
+        let on_close = props.on_close.clone();
+        Callback::from(move |_: MouseEvent| on_close.emit(()))
+    };
+
+    let view_message = |message: &ChatMessage| {
+        html! {
+            <div class="message">
+                <span class="username">{ &message.username }</span>
+                <span class="timestamp">{ format!("{} UTC", message.timestamp.format("%Y-%m-%d %H:%M:%S")) }</span>
+                if message.deleted {
+                    <span class="content deleted">{"This message was deleted"}</span>
+                } else {
+                    <span class="content">
+                        { &message.content }
+                        if message.edited_at.is_some() {
+                            <span class="edited">{" (edited)"}</span>
+                        }
+                    </span>
+                }
+                if !message.reactions.is_empty() {
+                    <span class="reactions">
+                        { for message.reactions.iter().map(|reaction| html! {
+                            <span class="reaction">{ format!("{} {}", reaction.emoji, reaction.count) }</span>
+                        }) }
+                    </span>
+                }
+            </div>
+        }
+    };
+
+    html! {
+        <div class="thread-panel">
+            <div class="thread-header">
+                <h3>{"Thread"}</h3>
+                <button class="thread-close" onclick={on_close}>{"Close"}</button>
+            </div>
+            <div class="messages">
+                {
+                    match &props.root {
+                        Some(root) => html! {
+                            <>
+                                <div class="thread-root">{ view_message(root) }</div>
+                                <div class="history-status">{ format!("{} replies", props.replies.len()) }</div>
+                                { for props.replies.iter().map(view_message) }
+                            </>
+                        },
+                        None => html! { <div class="history-status">{"Loading thread..."}</div> },
+                    }
+                }
+            </div>
+            <form class="send-message-box" onsubmit={on_submit}>
+                <input
+                    type="text"
+                    placeholder="Reply in thread..."
+                    class="message-input"
+                    value={(*reply).clone()}
+                    oninput={on_input}
+                />
+                <button type="submit" class="send-button">{"Reply"}</button>
+            </form>
+        </div>
+    }
+}
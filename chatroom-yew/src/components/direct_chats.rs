@@ -0,0 +1,112 @@
+use std::rc::Rc;
+
+use yew::platform::spawn_local;
+use yew::prelude::*;
+use yew_router::prelude::*;
+
+use crate::context::auth::AuthContext;
+use crate::services::chat_room;
+use crate::types::chat_room::DirectChat;
+use crate::Route;
+
+#[derive(Properties, PartialEq)]
+pub struct DirectChatsProps {
+    /// Report a failed request to the page
+    pub on_error: Callback<String>,
+}
+
+/// The user's direct conversations, and a box to start one by user ID
+#[function_component]
+pub fn DirectChats(props: &DirectChatsProps) -> Html {
+    let auth_ctx = use_context::<Rc<AuthContext>>().expect("Could not find AuthContext");
+    let navigator = use_navigator().unwrap();
+    let chats = use_state(Vec::<DirectChat>::new);
+    let user_id = use_state(|| Option::<i32>::None);
+
+    {
+        let chats = chats.clone();
+        let token = auth_ctx.state.token.clone();
+        let on_error = props.on_error.clone();
+        use_effect_with(token, move |token| {
+            if let Some(token) = token.clone() {
+                spawn_local(async move {
+                    match chat_room::list_direct_chats(token).await {
+                        Ok(response) => chats.set(response.chats),
+                        Err(err) => on_error.emit(err),
+                    }
+                });
+            }
+        });
+    }
+
+    let on_open = {
+        let navigator = navigator.clone();
+        let token = auth_ctx.state.token.clone();
+        let on_error = props.on_error.clone();
+        let user_id = user_id.clone();
+        move |_: MouseEvent| {
+            let Some(other) = *user_id else {
+                on_error.emit("Invalid user ID".to_string());
+                return;
+            };
+            let navigator = navigator.clone();
+            let token = token.clone().expect("User is not logged in!");
+            let on_error = on_error.clone();
+            spawn_local(async move {
+                match chat_room::open_direct_chat(token, other).await {
+                    Ok(chat) => navigator.push(&Route::ChatRoom { id: chat.room_id }),
+                    Err(err) => on_error.emit(err),
+                }
+            });
+        }
+    };
+
+    let view_chat = |chat: &DirectChat| {
+        let navigator = navigator.clone();
+        let token = auth_ctx.state.token.clone();
+        let on_error = props.on_error.clone();
+        let room_id = chat.room_id;
+        let on_click = move |_: MouseEvent| {
+            let navigator = navigator.clone();
+            let token = token.clone().expect("User is not logged in!");
+            let on_error = on_error.clone();
+            spawn_local(async move {
+                match chat_room::join_chat_room(token, room_id).await {
+                    Ok(_) => navigator.push(&Route::ChatRoom { id: room_id }),
+                    Err(err) => on_error.emit(err),
+                }
+            });
+        };
+        html! {
+            <li class="direct-chat" onclick={on_click}>{ &chat.username }</li>
+        }
+    };
+
+    html! {
+        <div class="chat-container">
+            <h2 class="chat-title">{"Direct Messages"}</h2>
+            if chats.is_empty() {
+                <p class="direct-chats-empty">{"No conversations yet"}</p>
+            } else {
+                <ul class="direct-chats">
+                    { for chats.iter().map(view_chat) }
+                </ul>
+            }
+            <div class="form-group">
+                <label>{"User ID"}</label>
+                <input
+                    type="number"
+                    min="1"
+                    placeholder="Enter User ID..."
+                    onchange={let user_id = user_id.clone(); move |e: Event| {
+                        let input = e.target_unchecked_into::<web_sys::HtmlInputElement>();
+                        user_id.set(input.value().parse::<i32>().ok());
+                    }}
+                />
+                <button class="chat-submit" onclick={on_open}>
+                    {"Message"}
+                </button>
+            </div>
+        </div>
+    }
+}
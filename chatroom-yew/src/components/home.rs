@@ -7,7 +7,11 @@ use yew::prelude::*;
 use yew_router::prelude::*;
 
 use crate::services::chat_room;
+use crate::types::chat_room::RoomVisibility;
 use crate::Route;
+use crate::components::direct_chats::DirectChats;
+use crate::components::my_rooms::MyRooms;
+use crate::components::room_browser::RoomBrowser;
 use crate::components::layout::Header;
 use crate::context::auth::AuthContext;
 use crate::services::auth;
@@ -48,6 +52,8 @@ pub fn Home() -> Html {
     let room_id_clone = room_id.clone();
     let room_name = use_state(String::new);
     let room_name_clone = room_name.clone();
+    let private_room = use_state(|| false);
+    let private_room_clone = private_room.clone();
         
     // Error handler
     let on_close_error = {
@@ -59,13 +65,13 @@ pub fn Home() -> Html {
     let on_join_chat_room = move |_: MouseEvent| {
         let navigator = navigator_clone.clone();
         let room_id = room_id_clone.clone();
-        let user_id = auth_ctx_clone.state.user_id.clone().expect("User is not logged in!");
+        let token = auth_ctx_clone.state.token.clone().expect("User is not logged in!");
         let error = error_clone.clone();
         spawn_local(async move {
             let room_id = (*room_id).clone();
             let navigator = navigator.clone();
             let error = error.clone();
-            match chat_room::join_chat_room(user_id, room_id).await {
+            match chat_room::join_chat_room(token, room_id).await {
                 Ok(_) => {
                     log::info!("Joined chat room");
                     navigator.push(&Route::ChatRoom { id: room_id });
@@ -85,6 +91,7 @@ pub fn Home() -> Html {
         let auth_ctx = auth_ctx_clone.clone();
         let navigator = navigator_clone.clone();
         let room_name = room_name_clone.clone();
+        let visibility = if *private_room_clone { RoomVisibility::Private } else { RoomVisibility::Public };
         let error = error_clone.clone();
 
         if room_name.is_empty() {
@@ -96,7 +103,7 @@ pub fn Home() -> Html {
             // let auth_ctx = auth_ctx_clone.clone();
             let navigator = navigator.clone();
             let room_name = room_name.clone();
-            match chat_room::create_chat_room(auth_ctx.state.user_id.clone().expect("User is not logged in!"), (*room_name).clone()).await {
+            match chat_room::create_chat_room(auth_ctx.state.token.clone().expect("User is not logged in!"), (*room_name).clone(), visibility).await {
                 Ok(response) => {
                     log::info!("Chat room created: {:?}", response.room_id);
                     window().unwrap().alert_with_message(format!("Chat room created successfully. Room ID: {:?}", response.room_id).as_str()).unwrap();
@@ -123,7 +130,7 @@ pub fn Home() -> Html {
         spawn_local(async move {
             let auth_ctx = auth_ctx_clone.clone();
             let navigator = navigator.clone();
-            match auth::logout(auth_ctx.state.user_id.clone().expect("User is not logged in!")).await {
+            match auth::logout(auth_ctx.state.token.clone().expect("User is not logged in!")).await {
                 Ok(_) => {
                     log::info!("Logout successful");
                     auth_ctx_clone.logout.emit(());
@@ -150,6 +157,9 @@ pub fn Home() -> Html {
                 {if *is_logged_in {
                     html! {
                         <div class="auth-buttons">
+                            <Link<Route> to={Route::Search} classes="btn-primary">
+                                {"Search messages"}
+                            </Link<Route>>
                             <button class="btn-secondary" onclick={on_logout}>
                                 {"Logout"}
                             </button>
@@ -170,6 +180,7 @@ pub fn Home() -> Html {
             </header>
             {if *is_logged_in {
                 html! {
+                <>
                 <div class="chat-row">
                     <div class="chat-container">
                         <h2 class="chat-title">{"Create a Chat Room"}</h2>
@@ -184,6 +195,17 @@ pub fn Home() -> Html {
                                     room_name.set(input.value());
                                 }}
                             />
+                            <label class="checkbox-label">
+                                <input
+                                    type="checkbox"
+                                    checked={*private_room}
+                                    onchange={let private_room = private_room.clone(); move |e: Event| {
+                                        let input = e.target_unchecked_into::<web_sys::HtmlInputElement>();
+                                        private_room.set(input.checked());
+                                    }}
+                                />
+                                {"Private (invite only)"}
+                            </label>
                             <button class="chat-submit" onclick={on_create_chat_room}>
                                 {"Create"}
                             </button>
@@ -216,7 +238,14 @@ pub fn Home() -> Html {
                             </button>
                         </div>
                     </div>
+
+                    <DirectChats on_error={let error = error.clone(); Callback::from(move |err| error.set(Some(err)))} />
+                </div>
+                <div class="chat-row">
+                    <MyRooms on_error={let error = error.clone(); Callback::from(move |err| error.set(Some(err)))} />
+                    <RoomBrowser on_error={let error = error.clone(); Callback::from(move |err| error.set(Some(err)))} />
                 </div>
+                </>
                 }
             } else {
                 html! {
@@ -0,0 +1,56 @@
+use std::rc::Rc;
+
+use yew::platform::spawn_local;
+use yew::prelude::*;
+use yew_router::prelude::*;
+
+use crate::components::layout::Header;
+use crate::context::auth::AuthContext;
+use crate::services::chat_room;
+use crate::Route;
+
+#[derive(Properties, PartialEq)]
+pub struct AcceptInviteProps {
+    pub token: String,
+}
+
+/// Landing page of an invite link: joins the room and moves on to it
+#[function_component]
+pub fn AcceptInvite(props: &AcceptInviteProps) -> Html {
+    let auth_ctx = use_context::<Rc<AuthContext>>().expect("Could not find AuthContext");
+    let navigator = use_navigator().unwrap();
+    let error = use_state(|| Option::<String>::None);
+
+    {
+        let error = error.clone();
+        let token = auth_ctx.state.token.clone();
+        use_effect_with((props.token.clone(), token), move |(invite_token, token)| {
+            if let Some(token) = token.clone() {
+                let invite_token = invite_token.clone();
+                spawn_local(async move {
+                    match chat_room::accept_invite(token, invite_token).await {
+                        Ok(response) => navigator.push(&Route::ChatRoom { id: response.room_id }),
+                        Err(err) => error.set(Some(err)),
+                    }
+                });
+            }
+        });
+    }
+
+    html! {
+        <>
+            <Header />
+            <div class="room-container">
+                {
+                    if !auth_ctx.state.is_authenticated {
+                        html! { <h3>{"Please log in, then open the invite link again"}</h3> }
+                    } else if let Some(error) = (*error).clone() {
+                        html! { <h3>{ format!("Could not accept the invite: {}", error) }</h3> }
+                    } else {
+                        html! { <h3>{"Joining chat room..."}</h3> }
+                    }
+                }
+            </div>
+        </>
+    }
+}
@@ -0,0 +1,198 @@
+use std::rc::Rc;
+
+use chrono::{DateTime, Days, NaiveDate, Utc};
+use yew::platform::spawn_local;
+use yew::prelude::*;
+use yew_router::prelude::*;
+
+use crate::components::layout::Header;
+use crate::context::auth::AuthContext;
+use crate::services::chat_room;
+use crate::types::chat_room::{MessageSearchHit, MessageSearchQuery, MessageSearchResponse, RoomSummary};
+use crate::Route;
+
+/// Results shown per page
+const PAGE_SIZE: u32 = 20;
+
+/// Search screen over the messages of the user's rooms, with filters by room, sender and
+/// date. Clicking a result opens its room.
+#[function_component]
+pub fn MessageSearch() -> Html {
+    let auth_ctx = use_context::<Rc<AuthContext>>().expect("Could not find AuthContext");
+    let navigator = use_navigator().unwrap();
+    let rooms = use_state(Vec::<RoomSummary>::new);
+    // The filters being edited, searched for on submit
+    let draft = use_state(MessageSearchQuery::default);
+    let query = use_state(|| Option::<MessageSearchQuery>::None);
+    let offset = use_state(|| 0u32);
+    let page = use_state(|| Option::<MessageSearchResponse>::None);
+    let error = use_state(|| Option::<String>::None);
+
+    {
+        let rooms = rooms.clone();
+        use_effect_with(auth_ctx.state.token.clone(), move |token| {
+            if let Some(token) = token.clone() {
+                spawn_local(async move {
+                    if let Ok(response) = chat_room::list_user_rooms(token).await {
+                        rooms.set(response.rooms);
+                    }
+                });
+            }
+        });
+    }
+
+    {
+        let page = page.clone();
+        let error = error.clone();
+        let token = auth_ctx.state.token.clone();
+        let query = (*query).clone().map(|query| MessageSearchQuery {
+            offset: Some(*offset),
+            limit: Some(PAGE_SIZE),
+            ..query
+        });
+        use_effect_with((token, query), move |(token, query)| {
+            if let (Some(token), Some(query)) = (token.clone(), query.clone()) {
+                spawn_local(async move {
+                    match chat_room::search_messages(token, query).await {
+                        Ok(response) => {
+                            page.set(Some(response));
+                            error.set(None);
+                        }
+                        Err(err) => error.set(Some(err)),
+                    }
+                });
+            }
+        });
+    }
+
+    // Update one filter of the draft from an input's value
+    let on_filter = |update: fn(&mut MessageSearchQuery, String)| {
+        let draft = draft.clone();
+        move |e: InputEvent| {
+            let input = e.target_unchecked_into::<web_sys::HtmlInputElement>();
+            let mut next = (*draft).clone();
+            update(&mut next, input.value());
+            draft.set(next);
+        }
+    };
+    let on_room = {
+        let draft = draft.clone();
+        move |e: Event| {
+            let select = e.target_unchecked_into::<web_sys::HtmlSelectElement>();
+            draft.set(MessageSearchQuery { room_id: select.value().parse().ok(), ..(*draft).clone() });
+        }
+    };
+    let on_submit = {
+        let draft = draft.clone();
+        let query = query.clone();
+        let offset = offset.clone();
+        move |e: SubmitEvent| {
+            e.prevent_default();
+            query.set(Some((*draft).clone()));
+            offset.set(0);
+        }
+    };
+    let on_previous = {
+        let offset = offset.clone();
+        move |_: MouseEvent| offset.set(offset.saturating_sub(PAGE_SIZE))
+    };
+    let on_next = {
+        let offset = offset.clone();
+        move |_: MouseEvent| offset.set(*offset + PAGE_SIZE)
+    };
+
+    let view_hit = |hit: &MessageSearchHit| {
+        let navigator = navigator.clone();
+        let room_id = hit.room_id;
+        let on_click = move |_: MouseEvent| navigator.push(&Route::ChatRoom { id: room_id });
+        html! {
+            <li class="room-summary search-result" onclick={on_click}>
+                <span class="room-summary-info">
+                    { format!(
+                        "{} · {} · {} UTC",
+                        hit.room_name.as_deref().unwrap_or("Direct message"),
+                        hit.message.username,
+                        hit.message.timestamp.format("%Y-%m-%d %H:%M"),
+                    ) }
+                </span>
+                <span class="search-snippet">
+                    { for hit.snippet.iter().map(|part| if part.highlight {
+                        html! { <mark>{ &part.text }</mark> }
+                    } else {
+                        html! { <>{ &part.text }</> }
+                    }) }
+                </span>
+            </li>
+        }
+    };
+
+    if !auth_ctx.state.is_authenticated {
+        return html! {
+            <>
+                <Header />
+                <div class="room-container">
+                    <h3>{"Please log in to search your messages"}</h3>
+                </div>
+            </>
+        };
+    }
+
+    let total = page.as_ref().map(|page| page.total).unwrap_or_default();
+    html! {
+        <>
+            <Header />
+            <div class="home-container">
+                <div class="chat-container search-container">
+                    <h2 class="chat-title">{"Search Messages"}</h2>
+                    <form class="room-filters search-filters" onsubmit={on_submit}>
+                        <input
+                            type="search"
+                            placeholder="Words to find..."
+                            oninput={on_filter(|query, value| query.q = value)}
+                        />
+                        <select onchange={on_room}>
+                            <option value="" selected=true>{"All rooms"}</option>
+                            { for rooms.iter().map(|room| html! {
+                                <option value={room.room_id.to_string()}>{ &room.room_name }</option>
+                            }) }
+                        </select>
+                        <input
+                            type="number"
+                            min="1"
+                            placeholder="Sender ID"
+                            oninput={on_filter(|query, value| query.sender_id = value.parse().ok())}
+                        />
+                        <label>{"From"}</label>
+                        <input type="date" oninput={on_filter(|query, value| query.from = start_of_day(&value, 0))} />
+                        <label>{"To"}</label>
+                        // The end date is included, so search up to the start of the next day
+                        <input type="date" oninput={on_filter(|query, value| query.to = start_of_day(&value, 1))} />
+                        <button type="submit" class="chat-submit">{"Search"}</button>
+                    </form>
+                    if let Some(error) = (*error).clone() {
+                        <p class="direct-chats-empty">{ error }</p>
+                    } else if let Some(page) = page.as_ref().filter(|page| !page.results.is_empty()) {
+                        <ul class="room-summaries">
+                            { for page.results.iter().map(view_hit) }
+                        </ul>
+                    } else if query.is_some() {
+                        <p class="direct-chats-empty">{"No messages found"}</p>
+                    }
+                    if total > PAGE_SIZE {
+                        <div class="room-pages">
+                            <button class="btn-secondary" onclick={on_previous} disabled={*offset == 0}>{"Previous"}</button>
+                            <span>{ format!("{}–{} of {}", *offset + 1, (*offset + PAGE_SIZE).min(total), total) }</span>
+                            <button class="btn-secondary" onclick={on_next} disabled={*offset + PAGE_SIZE >= total}>{"Next"}</button>
+                        </div>
+                    }
+                </div>
+            </div>
+        </>
+    }
+}
+
+/// Midnight UTC `days` after the date picked in a date input, `None` if it was cleared
+fn start_of_day(value: &str, days: u64) -> Option<DateTime<Utc>> {
+    let date = NaiveDate::parse_from_str(value, "%Y-%m-%d").ok()?.checked_add_days(Days::new(days))?;
+    Some(date.and_hms_opt(0, 0, 0)?.and_utc())
+}
@@ -1,6 +1,11 @@
 pub mod auth;
 pub mod chat;
+pub mod direct_chats;
 pub mod home;
+pub mod invite;
 pub mod layout;
+pub mod message_search;
+pub mod my_rooms;
+pub mod room_browser;
 
 pub use home::Home;
@@ -0,0 +1,76 @@
+use std::rc::Rc;
+
+use yew::platform::spawn_local;
+use yew::prelude::*;
+use yew_router::prelude::*;
+
+use crate::context::auth::AuthContext;
+use crate::services::chat_room;
+use crate::types::chat_room::{RoomSummary, RoomVisibility};
+use crate::Route;
+
+#[derive(Properties, PartialEq)]
+pub struct MyRoomsProps {
+    /// Report a failed request to the page
+    pub on_error: Callback<String>,
+}
+
+/// The rooms the user has joined, most recently active first
+#[function_component]
+pub fn MyRooms(props: &MyRoomsProps) -> Html {
+    let auth_ctx = use_context::<Rc<AuthContext>>().expect("Could not find AuthContext");
+    let navigator = use_navigator().unwrap();
+    let rooms = use_state(Vec::<RoomSummary>::new);
+
+    {
+        let rooms = rooms.clone();
+        let token = auth_ctx.state.token.clone();
+        let on_error = props.on_error.clone();
+        use_effect_with(token, move |token| {
+            if let Some(token) = token.clone() {
+                spawn_local(async move {
+                    match chat_room::list_user_rooms(token).await {
+                        Ok(response) => rooms.set(response.rooms),
+                        Err(err) => on_error.emit(err),
+                    }
+                });
+            }
+        });
+    }
+
+    let view_room = |room: &RoomSummary| {
+        let navigator = navigator.clone();
+        let room_id = room.room_id;
+        let on_click = move |_: MouseEvent| navigator.push(&Route::ChatRoom { id: room_id });
+        html! {
+            <li class="room-summary" onclick={on_click}>
+                <span class="room-summary-name">
+                    { &room.room_name }
+                    if room.visibility == RoomVisibility::Private {
+                        <span class="room-summary-info">{" (private)"}</span>
+                    }
+                    if room.archived {
+                        <span class="room-summary-info">{" (archived)"}</span>
+                    }
+                    if let Some(unread) = room.unread_count.filter(|unread| *unread > 0) {
+                        <span class="unread-count" title="Unread messages">{ unread }</span>
+                    }
+                </span>
+                <span class="room-summary-info">{ format!("{} members", room.member_count) }</span>
+            </li>
+        }
+    };
+
+    html! {
+        <div class="chat-container">
+            <h2 class="chat-title">{"My Chat Rooms"}</h2>
+            if rooms.is_empty() {
+                <p class="direct-chats-empty">{"You have not joined any rooms yet"}</p>
+            } else {
+                <ul class="room-summaries">
+                    { for rooms.iter().map(view_room) }
+                </ul>
+            }
+        </div>
+    }
+}
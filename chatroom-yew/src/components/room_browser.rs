@@ -0,0 +1,147 @@
+use std::rc::Rc;
+
+use yew::platform::spawn_local;
+use yew::prelude::*;
+use yew_router::prelude::*;
+
+use crate::context::auth::AuthContext;
+use crate::services::chat_room;
+use crate::types::chat_room::{RoomListQuery, RoomListResponse, RoomSort, RoomSummary};
+use crate::Route;
+
+/// Rooms shown per page
+const PAGE_SIZE: u32 = 10;
+
+#[derive(Properties, PartialEq)]
+pub struct RoomBrowserProps {
+    /// Report a failed request to the page
+    pub on_error: Callback<String>,
+}
+
+/// Searchable, paged list of the public rooms. Clicking a room joins it.
+#[function_component]
+pub fn RoomBrowser(props: &RoomBrowserProps) -> Html {
+    let auth_ctx = use_context::<Rc<AuthContext>>().expect("Could not find AuthContext");
+    let navigator = use_navigator().unwrap();
+    let search = use_state(String::new);
+    let sort = use_state(RoomSort::default);
+    let offset = use_state(|| 0u32);
+    let page = use_state(|| Option::<RoomListResponse>::None);
+
+    {
+        let page = page.clone();
+        let token = auth_ctx.state.token.clone();
+        let on_error = props.on_error.clone();
+        let query = RoomListQuery {
+            search: Some((*search).clone()).filter(|search| !search.is_empty()),
+            sort: Some(*sort),
+            offset: Some(*offset),
+            limit: Some(PAGE_SIZE),
+        };
+        use_effect_with((token, query), move |(token, query)| {
+            if let Some(token) = token.clone() {
+                let query = query.clone();
+                spawn_local(async move {
+                    match chat_room::list_chat_rooms(token, query).await {
+                        Ok(response) => page.set(Some(response)),
+                        Err(err) => on_error.emit(err),
+                    }
+                });
+            }
+        });
+    }
+
+    let on_search = {
+        let search = search.clone();
+        let offset = offset.clone();
+        move |e: Event| {
+            let input = e.target_unchecked_into::<web_sys::HtmlInputElement>();
+            search.set(input.value().trim().to_string());
+            offset.set(0);
+        }
+    };
+    let on_sort = {
+        let sort = sort.clone();
+        let offset = offset.clone();
+        move |e: Event| {
+            let select = e.target_unchecked_into::<web_sys::HtmlSelectElement>();
+            sort.set(match select.value().as_str() {
+                "members" => RoomSort::Members,
+                "name" => RoomSort::Name,
+                _ => RoomSort::Activity,
+            });
+            offset.set(0);
+        }
+    };
+    let on_previous = {
+        let offset = offset.clone();
+        move |_: MouseEvent| offset.set(offset.saturating_sub(PAGE_SIZE))
+    };
+    let on_next = {
+        let offset = offset.clone();
+        move |_: MouseEvent| offset.set(*offset + PAGE_SIZE)
+    };
+
+    let view_room = |room: &RoomSummary| {
+        let navigator = navigator.clone();
+        let token = auth_ctx.state.token.clone();
+        let on_error = props.on_error.clone();
+        let room_id = room.room_id;
+        let on_click = move |_: MouseEvent| {
+            let navigator = navigator.clone();
+            let token = token.clone().expect("User is not logged in!");
+            let on_error = on_error.clone();
+            spawn_local(async move {
+                match chat_room::join_chat_room(token, room_id).await {
+                    Ok(_) => navigator.push(&Route::ChatRoom { id: room_id }),
+                    Err(err) => on_error.emit(err),
+                }
+            });
+        };
+        html! {
+            <li class="room-summary" onclick={on_click}>
+                <span class="room-summary-name">
+                    { &room.room_name }
+                    if room.archived {
+                        <span class="room-summary-info">{" (archived)"}</span>
+                    }
+                </span>
+                <span class="room-summary-info">
+                    { format!("{} members · active {}", room.member_count, room.last_activity.format("%Y-%m-%d %H:%M")) }
+                </span>
+                if let Some(description) = &room.description {
+                    <span class="room-summary-description">{ description }</span>
+                }
+            </li>
+        }
+    };
+
+    let total = page.as_ref().map(|page| page.total).unwrap_or_default();
+    html! {
+        <div class="chat-container">
+            <h2 class="chat-title">{"Browse Chat Rooms"}</h2>
+            <div class="room-filters">
+                <input type="search" placeholder="Search rooms..." onchange={on_search} />
+                <select onchange={on_sort}>
+                    <option value="activity" selected={*sort == RoomSort::Activity}>{"Recently active"}</option>
+                    <option value="members" selected={*sort == RoomSort::Members}>{"Most members"}</option>
+                    <option value="name" selected={*sort == RoomSort::Name}>{"Name"}</option>
+                </select>
+            </div>
+            if let Some(page) = page.as_ref().filter(|page| !page.rooms.is_empty()) {
+                <ul class="room-summaries">
+                    { for page.rooms.iter().map(view_room) }
+                </ul>
+            } else {
+                <p class="direct-chats-empty">{"No rooms found"}</p>
+            }
+            if total > PAGE_SIZE {
+                <div class="room-pages">
+                    <button class="btn-secondary" onclick={on_previous} disabled={*offset == 0}>{"Previous"}</button>
+                    <span>{ format!("{}–{} of {}", *offset + 1, (*offset + PAGE_SIZE).min(total), total) }</span>
+                    <button class="btn-secondary" onclick={on_next} disabled={*offset + PAGE_SIZE >= total}>{"Next"}</button>
+                </div>
+            }
+        </div>
+    }
+}
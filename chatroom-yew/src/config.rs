@@ -8,4 +8,9 @@ impl Endpoints {
     pub const LOGOUT: &'static str = "/api/user/logout";
     pub const CREATE_CHAT_ROOM: &'static str = "/api/chatrooms";
     pub const JOIN_CHAT_ROOM: &'static str = "/api/chatrooms/join";
+    pub const MESSAGES: &'static str = "/api/messages";
+    pub const DIRECT_CHATS: &'static str = "/api/direct";
+    pub const INVITES: &'static str = "/api/invites";
+    pub const MY_ROOMS: &'static str = "/api/users/me/rooms";
+    pub const ATTACHMENTS: &'static str = "/api/attachments";
 }
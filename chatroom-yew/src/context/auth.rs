@@ -1,11 +1,14 @@
 use std::rc::Rc;
 use yew::prelude::*;
 
+use crate::services::auth::Token;
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct AuthState {
     pub is_authenticated: bool,
     pub user_id: Option<i32>,
     pub username: Option<String>,
+    pub token: Option<String>,
 }
 
 impl Default for AuthState {
@@ -14,6 +17,7 @@ impl Default for AuthState {
             is_authenticated: false,
             user_id: None,
             username: None,
+            token: None,
         }
     }
 }
@@ -21,7 +25,7 @@ impl Default for AuthState {
 #[derive(Clone, PartialEq, Debug)]
 pub struct AuthContext {
     pub state: AuthState,
-    pub login: Callback<(i32, String)>, // Takes user_id
+    pub login: Callback<Token>, // Takes the session issued at login
     pub logout: Callback<()>,
 }
 
@@ -38,11 +42,12 @@ pub fn AuthProvider(props: &AuthProviderProps) -> Html {
     let login = {
         log::debug!("AuthProvider login Callback called");
         let state = state.clone();
-        Callback::from(move |tuple: (i32, String)| {
+        Callback::from(move |token: Token| {
             state.set(AuthState {
                 is_authenticated: true,
-                user_id: Some(tuple.0),
-                username: Some(tuple.1),
+                user_id: Some(token.user_id),
+                username: Some(token.username),
+                token: Some(token.token),
             });
         })
     };
@@ -19,6 +19,10 @@ enum Route {
     SignUp,
     #[at("/chat/:id")]
     ChatRoom { id: i32 },
+    #[at("/invite/:token")]
+    Invite { token: String },
+    #[at("/search")]
+    Search,
 }
 
 #[function_component]
@@ -37,7 +41,10 @@ fn switch(routes: Route) -> Html {
         Route::Home => html! { <components::Home /> },
         Route::Login => html! { <components::auth::Login /> },
         Route::SignUp => html! { <components::auth::SignUp /> },
-        Route::ChatRoom { id } => html! { <components::chat::ChatRoom id={id.to_string()} /> },
+        // Keyed by room, so moving to another room reconnects instead of reusing the socket
+        Route::ChatRoom { id } => html! { <components::chat::ChatRoom key={id} id={id.to_string()} /> },
+        Route::Invite { token } => html! { <components::invite::AcceptInvite {token} /> },
+        Route::Search => html! { <components::message_search::MessageSearch /> },
     }
 }
 
@@ -10,6 +10,7 @@ use crate::{config, types::auth::*};
 pub struct Token {
     pub user_id: i32,
     pub username: String,
+    pub token: String,
 }
 
 pub async fn login(email: String, password: String) -> Result<LoginResponse, String> {
@@ -53,7 +54,7 @@ pub async fn login(email: String, password: String) -> Result<LoginResponse, Str
     }
 }
 
-pub async fn signup(username: String, email: String, password: String) -> Result<SignupResponse, String> {
+pub async fn signup(username: String, email: String, password: String) -> Result<MessageResponse, String> {
     let opts = RequestInit::new();
     opts.set_method("POST");
     opts.set_mode(RequestMode::Cors);
@@ -86,7 +87,7 @@ pub async fn signup(username: String, email: String, password: String) -> Result
         .await
         .map_err(|err| err.as_string().unwrap_or_else(|| "Error decoding json".to_string()))?;
     
-    match from_value::<SignupResponse>(json.clone()) {
+    match from_value::<MessageResponse>(json.clone()) {
         Ok(response) => Ok(response),
         Err(err) => {
             log::error!("Failed to parse signup response: {:?}", err);
@@ -95,16 +96,14 @@ pub async fn signup(username: String, email: String, password: String) -> Result
     }
 }
 
-pub async fn logout(user_id: i32) -> Result<(), String> {
-    let logout_request = LogoutRequest { user_id };
+pub async fn logout(token: String) -> Result<(), String> {
     let opts = RequestInit::new();
     opts.set_method("POST");
     opts.set_mode(RequestMode::Cors);
-    opts.set_body(Some(&JsValue::from_str(&serde_json::to_string(&logout_request).unwrap())).unwrap());
 
     let url = format!("{}{}", config::API_BASE_URL, config::Endpoints::LOGOUT);
     let request = Request::new_with_str_and_init(&url, &opts).unwrap();
-    request.headers().set("Content-Type", "application/json").unwrap();
+    request.headers().set("Authorization", &format!("Bearer {}", token)).unwrap();
 
     let window = web_sys::window().unwrap();
     let resp_value = JsFuture::from(window.fetch_with_request(&request))
@@ -126,15 +125,15 @@ pub async fn logout(user_id: i32) -> Result<(), String> {
     Ok(())
 }
 
-pub fn load_auth_token() -> Option<(i32, String)> {
+pub fn load_auth_token() -> Option<Token> {
     let window = window().unwrap();
     let storage = window.session_storage().unwrap().unwrap();
  
     match storage.get_item("user_token") {
         Ok(Some(token)) => {
             log::debug!("In load_auth_token(): Token: {:?}", token);
-            let token: Token = serde_json::from_str(&token).expect("Failed to parse token");
-            Some((token.user_id, token.username))
+            // Tokens saved before sessions were introduced cannot be used any more
+            serde_json::from_str::<Token>(&token).ok()
         }
         _ => {
             log::debug!("In load_auth_token(): No token found");
@@ -1,23 +1,25 @@
 use wasm_bindgen::{JsCast, JsValue};
 use wasm_bindgen_futures::JsFuture;
 use web_sys::{Request, RequestInit, RequestMode, Response};
+use serde::Serialize;
 use serde_wasm_bindgen::from_value;
 
-use crate::{config, types::chat_room::*};
+use crate::{config, types::{chat::{Attachment, Thumbnail}, chat_room::*}};
 
-pub async fn create_chat_room(user_id: i32, room_name: String) -> Result<CreateChatRoomResponse, String> {
+pub async fn create_chat_room(token: String, room_name: String, visibility: RoomVisibility) -> Result<CreateChatRoomResponse, String> {
     log::debug!("Creating chat room with name: {}", room_name);
 
     let opts = RequestInit::new();
     opts.set_method("POST");
     opts.set_mode(RequestMode::Cors);
 
-    let create_request = CreateChatRoomRequest { user_id, room_name };
+    let create_request = CreateChatRoomRequest { room_name, visibility };
     opts.set_body(Some(&JsValue::from_str(&serde_json::to_string(&create_request).unwrap())).unwrap());
 
     let url = format!("{}{}", config::API_BASE_URL, config::Endpoints::CREATE_CHAT_ROOM);
     let request = Request::new_with_str_and_init(&url, &opts).unwrap();
     request.headers().set("Content-Type", "application/json").unwrap();
+    request.headers().set("Authorization", &format!("Bearer {}", token)).unwrap();
 
     let window = web_sys::window().unwrap();
     let resp_value = JsFuture::from(window.fetch_with_request(&request))
@@ -25,6 +27,10 @@ pub async fn create_chat_room(user_id: i32, room_name: String) -> Result<CreateC
         .map_err(|err| err.as_string().unwrap_or_else(|| "Request failed. Is server started?".to_string()))?;
     let resp = resp_value.dyn_into::<Response>().unwrap();
 
+    if resp.status() == 401 {
+        return Err("Your session has expired, please log in again".to_string());
+    }
+
     let json = JsFuture::from(resp.json().unwrap())
         .await
         .map_err(|err| err.as_string().unwrap_or_else(|| "Error decoding error".to_string()))?;
@@ -44,18 +50,19 @@ pub async fn create_chat_room(user_id: i32, room_name: String) -> Result<CreateC
     }
 }
 
-pub async fn join_chat_room(user_id: i32, room_id: i32) -> Result<JoinChatRoomResponse, String> {
-    log::debug!("Joining chat room with user_id: {}, room_id: {}", user_id, room_id);
+pub async fn join_chat_room(token: String, room_id: i32) -> Result<JoinChatRoomResponse, String> {
+    log::debug!("Joining chat room with room_id: {}", room_id);
     let opts = RequestInit::new();
     opts.set_method("POST");
     opts.set_mode(RequestMode::Cors);
     
-    let join_request = JoinChatRoomRequest { user_id, room_id };
+    let join_request = JoinChatRoomRequest { room_id };
     opts.set_body(Some(&JsValue::from_str(&serde_json::to_string(&join_request).unwrap())).unwrap());
 
     let url = format!("{}{}", config::API_BASE_URL, config::Endpoints::JOIN_CHAT_ROOM);
     let request = Request::new_with_str_and_init(&url, &opts).unwrap();
     request.headers().set("Content-Type", "application/json").unwrap();
+    request.headers().set("Authorization", &format!("Bearer {}", token)).unwrap();
 
     let window = web_sys::window().unwrap();
     let resp_value = JsFuture::from(window.fetch_with_request(&request))
@@ -65,6 +72,9 @@ pub async fn join_chat_room(user_id: i32, room_id: i32) -> Result<JoinChatRoomRe
     let resp: Response = resp_value.dyn_into().unwrap();
 
     match resp.status() {
+        401 => {
+            return Err("Your session has expired, please log in again".to_string());
+        }
         404 => {
             return Err("Room not found".to_string());
         }
@@ -86,3 +96,472 @@ pub async fn join_chat_room(user_id: i32, room_id: i32) -> Result<JoinChatRoomRe
         }
     }
 }
+
+pub async fn fetch_thread(token: String, message_id: i32) -> Result<ThreadResponse, String> {
+    log::debug!("Fetching thread of message {}", message_id);
+    let opts = RequestInit::new();
+    opts.set_method("GET");
+    opts.set_mode(RequestMode::Cors);
+
+    let url = format!("{}{}/{}/thread", config::API_BASE_URL, config::Endpoints::MESSAGES, message_id);
+    let request = Request::new_with_str_and_init(&url, &opts).unwrap();
+    request.headers().set("Authorization", &format!("Bearer {}", token)).unwrap();
+
+    let window = web_sys::window().unwrap();
+    let resp_value = JsFuture::from(window.fetch_with_request(&request))
+        .await
+        .map_err(|err| err.as_string().unwrap_or_else(|| "Request failed. Is server started?".to_string()))?;
+    let resp: Response = resp_value.dyn_into().unwrap();
+
+    match resp.status() {
+        401 => {
+            return Err("Your session has expired, please log in again".to_string());
+        }
+        404 => {
+            return Err("Message not found".to_string());
+        }
+        _ => {}
+    }
+
+    let json = JsFuture::from(resp.json().unwrap())
+        .await
+        .map_err(|err| err.as_string().unwrap_or_else(|| "Error decoding error".to_string()))?;
+    from_value::<ThreadResponse>(json.clone()).map_err(|err| {
+        log::error!("Failed to parse thread response: {:?}", err);
+        format!("Response was: {:?}", json)
+    })
+}
+
+/// Find or start the direct conversation with another user
+pub async fn open_direct_chat(token: String, user_id: i32) -> Result<DirectChat, String> {
+    log::debug!("Opening direct chat with user {}", user_id);
+    let opts = RequestInit::new();
+    opts.set_method("POST");
+    opts.set_mode(RequestMode::Cors);
+
+    let open_request = OpenDirectChatRequest { user_id };
+    opts.set_body(Some(&JsValue::from_str(&serde_json::to_string(&open_request).unwrap())).unwrap());
+
+    let url = format!("{}{}", config::API_BASE_URL, config::Endpoints::DIRECT_CHATS);
+    let request = Request::new_with_str_and_init(&url, &opts).unwrap();
+    request.headers().set("Content-Type", "application/json").unwrap();
+    request.headers().set("Authorization", &format!("Bearer {}", token)).unwrap();
+
+    let window = web_sys::window().unwrap();
+    let resp_value = JsFuture::from(window.fetch_with_request(&request))
+        .await
+        .map_err(|err| err.as_string().unwrap_or_else(|| "Request failed. Is server started?".to_string()))?;
+    let resp: Response = resp_value.dyn_into().unwrap();
+
+    match resp.status() {
+        401 => {
+            return Err("Your session has expired, please log in again".to_string());
+        }
+        404 => {
+            return Err("User not found".to_string());
+        }
+        _ => {}
+    }
+
+    let json = JsFuture::from(resp.json().unwrap())
+        .await
+        .map_err(|err| err.as_string().unwrap_or_else(|| "Error decoding error".to_string()))?;
+    from_value::<DirectChat>(json.clone()).map_err(|err| {
+        log::error!("Failed to parse direct chat response: {:?}", err);
+        format!("Response was: {:?}", json)
+    })
+}
+
+/// The user's direct conversations, newest first
+pub async fn list_direct_chats(token: String) -> Result<DirectChatListResponse, String> {
+    log::debug!("Fetching direct chats");
+    let opts = RequestInit::new();
+    opts.set_method("GET");
+    opts.set_mode(RequestMode::Cors);
+
+    let url = format!("{}{}", config::API_BASE_URL, config::Endpoints::DIRECT_CHATS);
+    let request = Request::new_with_str_and_init(&url, &opts).unwrap();
+    request.headers().set("Authorization", &format!("Bearer {}", token)).unwrap();
+
+    let window = web_sys::window().unwrap();
+    let resp_value = JsFuture::from(window.fetch_with_request(&request))
+        .await
+        .map_err(|err| err.as_string().unwrap_or_else(|| "Request failed. Is server started?".to_string()))?;
+    let resp: Response = resp_value.dyn_into().unwrap();
+
+    if resp.status() == 401 {
+        return Err("Your session has expired, please log in again".to_string());
+    }
+
+    let json = JsFuture::from(resp.json().unwrap())
+        .await
+        .map_err(|err| err.as_string().unwrap_or_else(|| "Error decoding error".to_string()))?;
+    from_value::<DirectChatListResponse>(json.clone()).map_err(|err| {
+        log::error!("Failed to parse direct chat list: {:?}", err);
+        format!("Response was: {:?}", json)
+    })
+}
+
+/// One page of the public rooms matching `query`
+pub async fn list_chat_rooms(token: String, query: RoomListQuery) -> Result<RoomListResponse, String> {
+    log::debug!("Listing chat rooms: {:?}", query);
+    let opts = RequestInit::new();
+    opts.set_method("GET");
+    opts.set_mode(RequestMode::Cors);
+
+    let url = format!("{}{}?{}", config::API_BASE_URL, config::Endpoints::CREATE_CHAT_ROOM, query_string(&query));
+    let request = Request::new_with_str_and_init(&url, &opts).unwrap();
+    request.headers().set("Authorization", &format!("Bearer {}", token)).unwrap();
+
+    let json = send_request(request).await?;
+    from_value::<RoomListResponse>(json.clone()).map_err(|err| {
+        log::error!("Failed to parse room list: {:?}", err);
+        format!("Response was: {:?}", json)
+    })
+}
+
+/// The rooms the user has joined, apart from direct conversations
+pub async fn list_user_rooms(token: String) -> Result<RoomListResponse, String> {
+    log::debug!("Fetching joined rooms");
+    let opts = RequestInit::new();
+    opts.set_method("GET");
+    opts.set_mode(RequestMode::Cors);
+
+    let url = format!("{}{}", config::API_BASE_URL, config::Endpoints::MY_ROOMS);
+    let request = Request::new_with_str_and_init(&url, &opts).unwrap();
+    request.headers().set("Authorization", &format!("Bearer {}", token)).unwrap();
+
+    let json = send_request(request).await?;
+    from_value::<RoomListResponse>(json.clone()).map_err(|err| {
+        log::error!("Failed to parse joined rooms: {:?}", err);
+        format!("Response was: {:?}", json)
+    })
+}
+
+/// One page of the messages matching `query` in the user's rooms, newest first
+pub async fn search_messages(token: String, query: MessageSearchQuery) -> Result<MessageSearchResponse, String> {
+    log::debug!("Searching messages: {:?}", query);
+    let opts = RequestInit::new();
+    opts.set_method("GET");
+    opts.set_mode(RequestMode::Cors);
+
+    let url = format!("{}{}/search?{}", config::API_BASE_URL, config::Endpoints::MESSAGES, query_string(&query));
+    let request = Request::new_with_str_and_init(&url, &opts).unwrap();
+    request.headers().set("Authorization", &format!("Bearer {}", token)).unwrap();
+
+    let json = send_request(request).await?;
+    from_value::<MessageSearchResponse>(json.clone()).map_err(|err| {
+        log::error!("Failed to parse search results: {:?}", err);
+        format!("Response was: {:?}", json)
+    })
+}
+
+/// A room's name, description, topic and state
+pub async fn get_chat_room(token: String, room_id: i32) -> Result<ChatRoomDetails, String> {
+    log::debug!("Fetching details of room {}", room_id);
+    let opts = RequestInit::new();
+    opts.set_method("GET");
+    opts.set_mode(RequestMode::Cors);
+
+    let url = format!("{}{}/{}", config::API_BASE_URL, config::Endpoints::CREATE_CHAT_ROOM, room_id);
+    let request = Request::new_with_str_and_init(&url, &opts).unwrap();
+    request.headers().set("Authorization", &format!("Bearer {}", token)).unwrap();
+
+    let json = send_request(request).await?;
+    from_value::<ChatRoomDetails>(json.clone()).map_err(|err| {
+        log::error!("Failed to parse room details: {:?}", err);
+        format!("Response was: {:?}", json)
+    })
+}
+
+/// Rename a room or change its description or topic, leaving the unset fields as they are
+pub async fn update_chat_room(token: String, room_id: i32, request: UpdateChatRoomRequest) -> Result<ChatRoomDetails, String> {
+    log::debug!("Updating room {}", room_id);
+    let opts = RequestInit::new();
+    opts.set_method("PATCH");
+    opts.set_mode(RequestMode::Cors);
+    opts.set_body(Some(&JsValue::from_str(&serde_json::to_string(&request).unwrap())).unwrap());
+
+    let url = format!("{}{}/{}", config::API_BASE_URL, config::Endpoints::CREATE_CHAT_ROOM, room_id);
+    let request = Request::new_with_str_and_init(&url, &opts).unwrap();
+    request.headers().set("Content-Type", "application/json").unwrap();
+    request.headers().set("Authorization", &format!("Bearer {}", token)).unwrap();
+
+    let json = send_request(request).await?;
+    from_value::<ChatRoomDetails>(json.clone()).map_err(|err| {
+        log::error!("Failed to parse room details: {:?}", err);
+        format!("Response was: {:?}", json)
+    })
+}
+
+/// Make a room read-only, or open it again
+pub async fn set_archived(token: String, room_id: i32, archived: bool) -> Result<ChatRoomDetails, String> {
+    log::debug!("Setting archived of room {} to {}", room_id, archived);
+    let opts = RequestInit::new();
+    opts.set_method(if archived { "POST" } else { "DELETE" });
+    opts.set_mode(RequestMode::Cors);
+
+    let url = format!("{}{}/{}/archive", config::API_BASE_URL, config::Endpoints::CREATE_CHAT_ROOM, room_id);
+    let request = Request::new_with_str_and_init(&url, &opts).unwrap();
+    request.headers().set("Authorization", &format!("Bearer {}", token)).unwrap();
+
+    let json = send_request(request).await?;
+    from_value::<ChatRoomDetails>(json.clone()).map_err(|err| {
+        log::error!("Failed to parse room details: {:?}", err);
+        format!("Response was: {:?}", json)
+    })
+}
+
+/// Delete a room with all of its messages
+pub async fn delete_chat_room(token: String, room_id: i32) -> Result<(), String> {
+    log::debug!("Deleting room {}", room_id);
+    let opts = RequestInit::new();
+    opts.set_method("DELETE");
+    opts.set_mode(RequestMode::Cors);
+
+    let url = format!("{}{}/{}", config::API_BASE_URL, config::Endpoints::CREATE_CHAT_ROOM, room_id);
+    let request = Request::new_with_str_and_init(&url, &opts).unwrap();
+    request.headers().set("Authorization", &format!("Bearer {}", token)).unwrap();
+
+    send_request(request).await.map(|_| ())
+}
+
+/// Invite a user to a room, or create a shareable link when `request.user_id` is unset
+pub async fn create_invite(token: String, room_id: i32, request: CreateInviteRequest) -> Result<RoomInvite, String> {
+    log::debug!("Creating invite to room {}", room_id);
+    let opts = RequestInit::new();
+    opts.set_method("POST");
+    opts.set_mode(RequestMode::Cors);
+    opts.set_body(Some(&JsValue::from_str(&serde_json::to_string(&request).unwrap())).unwrap());
+
+    let url = format!("{}{}/{}/invites", config::API_BASE_URL, config::Endpoints::CREATE_CHAT_ROOM, room_id);
+    let request = Request::new_with_str_and_init(&url, &opts).unwrap();
+    request.headers().set("Content-Type", "application/json").unwrap();
+    request.headers().set("Authorization", &format!("Bearer {}", token)).unwrap();
+
+    let json = send_request(request).await?;
+    from_value::<RoomInvite>(json.clone()).map_err(|err| {
+        log::error!("Failed to parse invite: {:?}", err);
+        format!("Response was: {:?}", json)
+    })
+}
+
+pub async fn list_invites(token: String, room_id: i32) -> Result<InviteListResponse, String> {
+    log::debug!("Fetching invites of room {}", room_id);
+    let opts = RequestInit::new();
+    opts.set_method("GET");
+    opts.set_mode(RequestMode::Cors);
+
+    let url = format!("{}{}/{}/invites", config::API_BASE_URL, config::Endpoints::CREATE_CHAT_ROOM, room_id);
+    let request = Request::new_with_str_and_init(&url, &opts).unwrap();
+    request.headers().set("Authorization", &format!("Bearer {}", token)).unwrap();
+
+    let json = send_request(request).await?;
+    from_value::<InviteListResponse>(json.clone()).map_err(|err| {
+        log::error!("Failed to parse invite list: {:?}", err);
+        format!("Response was: {:?}", json)
+    })
+}
+
+pub async fn revoke_invite(token: String, room_id: i32, invite_id: i32) -> Result<(), String> {
+    log::debug!("Revoking invite {} of room {}", invite_id, room_id);
+    let opts = RequestInit::new();
+    opts.set_method("DELETE");
+    opts.set_mode(RequestMode::Cors);
+
+    let url = format!("{}{}/{}/invites/{}", config::API_BASE_URL, config::Endpoints::CREATE_CHAT_ROOM, room_id, invite_id);
+    let request = Request::new_with_str_and_init(&url, &opts).unwrap();
+    request.headers().set("Authorization", &format!("Bearer {}", token)).unwrap();
+
+    send_request(request).await.map(|_| ())
+}
+
+/// Join the room an invite link points to
+pub async fn accept_invite(token: String, invite_token: String) -> Result<AcceptInviteResponse, String> {
+    log::debug!("Accepting invite");
+    let opts = RequestInit::new();
+    opts.set_method("POST");
+    opts.set_mode(RequestMode::Cors);
+
+    let url = format!("{}{}/{}", config::API_BASE_URL, config::Endpoints::INVITES, invite_token);
+    let request = Request::new_with_str_and_init(&url, &opts).unwrap();
+    request.headers().set("Authorization", &format!("Bearer {}", token)).unwrap();
+
+    let json = send_request(request).await?;
+    from_value::<AcceptInviteResponse>(json.clone()).map_err(|err| {
+        log::error!("Failed to parse accept invite response: {:?}", err);
+        format!("Response was: {:?}", json)
+    })
+}
+
+/// The members of a room with their roles
+pub async fn list_members(token: String, room_id: i32) -> Result<MemberListResponse, String> {
+    log::debug!("Fetching members of room {}", room_id);
+    let opts = RequestInit::new();
+    opts.set_method("GET");
+    opts.set_mode(RequestMode::Cors);
+
+    let url = format!("{}{}/{}/members", config::API_BASE_URL, config::Endpoints::CREATE_CHAT_ROOM, room_id);
+    let request = Request::new_with_str_and_init(&url, &opts).unwrap();
+    request.headers().set("Authorization", &format!("Bearer {}", token)).unwrap();
+
+    let json = send_request(request).await?;
+    from_value::<MemberListResponse>(json.clone()).map_err(|err| {
+        log::error!("Failed to parse member list: {:?}", err);
+        format!("Response was: {:?}", json)
+    })
+}
+
+/// Promote or demote a member of a room
+pub async fn set_member_role(token: String, room_id: i32, user_id: i32, role: RoomRole) -> Result<(), String> {
+    log::debug!("Setting role of user {} in room {} to {}", user_id, room_id, role.as_str());
+    let opts = RequestInit::new();
+    opts.set_method("PUT");
+    opts.set_mode(RequestMode::Cors);
+    opts.set_body(Some(&JsValue::from_str(&serde_json::to_string(&SetRoleRequest { role }).unwrap())).unwrap());
+
+    let url = format!("{}{}/{}/members/{}/role", config::API_BASE_URL, config::Endpoints::CREATE_CHAT_ROOM, room_id, user_id);
+    let request = Request::new_with_str_and_init(&url, &opts).unwrap();
+    request.headers().set("Content-Type", "application/json").unwrap();
+    request.headers().set("Authorization", &format!("Bearer {}", token)).unwrap();
+
+    send_request(request).await.map(|_| ())
+}
+
+/// Hand a room over to another member
+pub async fn transfer_ownership(token: String, room_id: i32, user_id: i32) -> Result<(), String> {
+    log::debug!("Transferring room {} to user {}", room_id, user_id);
+    let opts = RequestInit::new();
+    opts.set_method("POST");
+    opts.set_mode(RequestMode::Cors);
+    opts.set_body(Some(&JsValue::from_str(&serde_json::to_string(&TransferOwnershipRequest { user_id }).unwrap())).unwrap());
+
+    let url = format!("{}{}/{}/transfer", config::API_BASE_URL, config::Endpoints::CREATE_CHAT_ROOM, room_id);
+    let request = Request::new_with_str_and_init(&url, &opts).unwrap();
+    request.headers().set("Content-Type", "application/json").unwrap();
+    request.headers().set("Authorization", &format!("Bearer {}", token)).unwrap();
+
+    send_request(request).await.map(|_| ())
+}
+
+/// Remove a member from a room
+pub async fn kick_member(token: String, room_id: i32, request: ModerationRequest) -> Result<(), String> {
+    log::debug!("Kicking user {} from room {}", request.user_id, room_id);
+    send_moderation_request(token, room_id, "kick", request).await
+}
+
+/// Remove a user from a room and keep them out
+pub async fn ban_user(token: String, room_id: i32, request: ModerationRequest) -> Result<(), String> {
+    log::debug!("Banning user {} from room {}", request.user_id, room_id);
+    send_moderation_request(token, room_id, "bans", request).await
+}
+
+/// Stop a member from posting for `request.duration` seconds
+pub async fn mute_member(token: String, room_id: i32, request: ModerationRequest) -> Result<(), String> {
+    log::debug!("Muting user {} in room {}", request.user_id, room_id);
+    send_moderation_request(token, room_id, "mutes", request).await
+}
+
+/// Limit members to one message every `interval` seconds, or lift the limit with 0
+pub async fn set_slow_mode(token: String, room_id: i32, interval: u32) -> Result<(), String> {
+    log::debug!("Setting slow mode of room {} to {} seconds", room_id, interval);
+    let opts = RequestInit::new();
+    opts.set_method("PUT");
+    opts.set_mode(RequestMode::Cors);
+    opts.set_body(Some(&JsValue::from_str(&serde_json::to_string(&SlowModeRequest { interval }).unwrap())).unwrap());
+
+    let url = format!("{}{}/{}/slow_mode", config::API_BASE_URL, config::Endpoints::CREATE_CHAT_ROOM, room_id);
+    let request = Request::new_with_str_and_init(&url, &opts).unwrap();
+    request.headers().set("Content-Type", "application/json").unwrap();
+    request.headers().set("Authorization", &format!("Bearer {}", token)).unwrap();
+
+    send_request(request).await.map(|_| ())
+}
+
+/// POST a moderation action against one user to `/api/chatrooms/{room_id}/{action}`
+async fn send_moderation_request(token: String, room_id: i32, action: &str, request: ModerationRequest) -> Result<(), String> {
+    let opts = RequestInit::new();
+    opts.set_method("POST");
+    opts.set_mode(RequestMode::Cors);
+    opts.set_body(Some(&JsValue::from_str(&serde_json::to_string(&request).unwrap())).unwrap());
+
+    let url = format!("{}{}/{}/{}", config::API_BASE_URL, config::Endpoints::CREATE_CHAT_ROOM, room_id, action);
+    let request = Request::new_with_str_and_init(&url, &opts).unwrap();
+    request.headers().set("Content-Type", "application/json").unwrap();
+    request.headers().set("Authorization", &format!("Bearer {}", token)).unwrap();
+
+    send_request(request).await.map(|_| ())
+}
+
+/// Upload a file to a room, to be sent with the next message
+pub async fn upload_attachment(token: String, room_id: i32, file: web_sys::File) -> Result<Attachment, String> {
+    log::debug!("Uploading {} to room {}", file.name(), room_id);
+    let form = web_sys::FormData::new().unwrap();
+    form.append_with_blob_and_filename("file", &file, &file.name()).unwrap();
+
+    let opts = RequestInit::new();
+    opts.set_method("POST");
+    opts.set_mode(RequestMode::Cors);
+    // The browser sets the multipart Content-Type with its boundary
+    opts.set_body(&form);
+
+    let url = format!("{}{}/{}/attachments", config::API_BASE_URL, config::Endpoints::CREATE_CHAT_ROOM, room_id);
+    let request = Request::new_with_str_and_init(&url, &opts).unwrap();
+    request.headers().set("Authorization", &format!("Bearer {}", token)).unwrap();
+
+    let json = send_request(request).await?;
+    from_value::<Attachment>(json.clone()).map_err(|err| {
+        log::error!("Failed to parse attachment: {:?}", err);
+        format!("Response was: {:?}", json)
+    })
+}
+
+/// Where an attachment is downloaded from. The token goes in the query string, since
+/// images and download links cannot send headers.
+pub fn attachment_url(token: &str, attachment_id: i32) -> String {
+    format!("{}{}/{}?token={}", config::API_BASE_URL, config::Endpoints::ATTACHMENTS, attachment_id, token)
+}
+
+/// Where a thumbnail of an image attachment is loaded from, with the token as for
+/// `attachment_url`
+pub fn thumbnail_url(token: &str, thumbnail: &Thumbnail) -> String {
+    format!("{}{}?token={}", config::API_BASE_URL, thumbnail.url, token)
+}
+
+/// Encode the fields of a query struct as a URL query string
+fn query_string<T: Serialize>(query: &T) -> String {
+    let params = web_sys::UrlSearchParams::new().unwrap();
+    if let Ok(serde_json::Value::Object(fields)) = serde_json::to_value(query) {
+        for (key, value) in fields {
+            match value {
+                serde_json::Value::String(text) => params.append(&key, &text),
+                value => params.append(&key, &value.to_string()),
+            }
+        }
+    }
+    String::from(params.to_string())
+}
+
+/// Send a request to one of the room listing, room management, search, attachment, invite, member or moderation endpoints and return the JSON body of a
+/// successful response, or the server's error message
+async fn send_request(request: Request) -> Result<JsValue, String> {
+    let window = web_sys::window().unwrap();
+    let resp_value = JsFuture::from(window.fetch_with_request(&request))
+        .await
+        .map_err(|err| err.as_string().unwrap_or_else(|| "Request failed. Is server started?".to_string()))?;
+    let resp: Response = resp_value.dyn_into().unwrap();
+
+    if resp.status() == 401 {
+        return Err("Your session has expired, please log in again".to_string());
+    }
+
+    let json = JsFuture::from(resp.json().unwrap())
+        .await
+        .map_err(|err| err.as_string().unwrap_or_else(|| "Error decoding error".to_string()))?;
+    if !resp.ok() {
+        return Err(from_value::<ErrorResponse>(json.clone())
+            .map(|response| response.error)
+            .unwrap_or_else(|_| format!("Response was: {:?}", json)));
+    }
+    Ok(json)
+}
@@ -1,116 +1,160 @@
-use std::{sync::{atomic::{AtomicBool, Ordering}, Arc}, time::Duration};
+use std::{sync::{atomic::{AtomicBool, AtomicI32, Ordering}, Arc}, time::Duration};
 
-use futures::{lock::Mutex, pin_mut, stream::{SplitSink, SplitStream}, FutureExt, SinkExt, StreamExt};
+use futures::{lock::Mutex, pin_mut, stream::SplitSink, FutureExt, SinkExt, StreamExt};
 use wasm_bindgen_futures::spawn_local;
 use tokio_tungstenite_wasm::{connect, WebSocketStream, Message};
 use yew::{platform::time::sleep, Callback};
 
-use crate::types::chat::ChatMessage;
+use crate::types::chat::{ClientEvent, ServerEvent};
+
+/// Delay before the first reconnect attempt; doubled after every failed attempt
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+/// How often a heartbeat is sent; the server closes connections silent for much longer
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(25);
+
+/// Whether the socket is currently usable
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ConnectionStatus {
+    Connected,
+    /// The connection dropped or could not be opened; another attempt follows after a delay
+    Reconnecting,
+}
 
 pub struct WebSocketService {
     sender: Arc<Mutex<Option<SplitSink<WebSocketStream, Message>>>>,
-    _receiver: Arc<Mutex<Option<SplitStream<WebSocketStream>>>>,
-    user_id: i32,
-    username: String,
     cancel: Arc<AtomicBool>,
+    /// Set when the user did something since the last heartbeat
+    active: Arc<AtomicBool>,
 }
 
 impl WebSocketService {
-    pub fn new(url: &str, user_id: i32, username: String, on_message: Callback<ChatMessage>) -> Self {
+    /// Connect to `url` and keep reconnecting with exponential backoff until the service is
+    /// dropped. Reconnects ask the server to replay every message after the last one seen.
+    pub fn new(url: &str, on_message: Callback<ServerEvent>, on_status: Callback<ConnectionStatus>) -> Self {
         log::debug!("WebSocketService new() called");
 
         let sender = Arc::new(Mutex::new(None));
         let sender_clone = sender.clone();
-        let receiver = Arc::new(Mutex::new(None));
-        let receiver_clone = receiver.clone();
-        let url_clone = url.to_string();
+        let url = url.to_string();
         let cancel = Arc::new(AtomicBool::new(false));
         let cancel_clone = cancel.clone();
+        // Highest message_id received so far, 0 until the first message arrives
+        let last_seen = Arc::new(AtomicI32::new(0));
 
-        // Init WebSocket connection asynchronously because it's tungstenite
         spawn_local(async move {
-            log::debug!("WebSocketService new(): init websocket thread spawned");
-            let struct_sender = sender_clone;
-            let struct_receiver = receiver_clone;
-            let url = url_clone.clone();
-            let wss = match connect(url).await {
-                Ok(ws) => ws,
-                Err(e) => {
-                    cancel_clone.store(true, Ordering::SeqCst);
-                    log::error!("WebSocketService: error connecting to WebSocket: {:?}", e.to_string());
-                    return;
+            log::debug!("WebSocketService: connection thread spawned");
+            let mut backoff = INITIAL_BACKOFF;
+            loop {
+                let url = match last_seen.load(Ordering::SeqCst) {
+                    0 => url.clone(),
+                    after => {
+                        let separator = if url.contains('?') { '&' } else { '?' };
+                        format!("{}{}after={}", url, separator, after)
+                    }
+                };
+
+                match connect(url).await {
+                    Ok(wss) => {
+                        log::debug!("WebSocketService: connected");
+                        backoff = INITIAL_BACKOFF;
+                        let (sender, mut receiver) = wss.split();
+                        *sender_clone.lock().await = Some(sender);
+                        on_status.emit(ConnectionStatus::Connected);
+
+                        // Handle incoming messages until the connection drops or we are cancelled
+                        loop {
+                            let cancel_fut = is_cancelled(cancel_clone.clone()).fuse();
+                            let receiver_fut = receiver.next().fuse();
+                            pin_mut!(cancel_fut, receiver_fut);
+
+                            futures::select! {
+                                _ = cancel_fut => {
+                                    log::debug!("WebSocketService: cancellation signal received, exiting...");
+                                    return;
+                                },
+                                msg = receiver_fut => match msg {
+                                    Some(Ok(Message::Text(msg))) => match serde_json::from_str::<ServerEvent>(&msg) {
+                                        Ok(event) => {
+                                            track_last_seen(&last_seen, &event);
+                                            on_message.emit(event);
+                                        }
+                                        Err(err) => log::error!("WebSocketService: failed to parse event {:?}: {:?}", msg, err),
+                                    },
+                                    Some(Ok(Message::Close(_))) | None => {
+                                        log::debug!("WebSocketService: connection closed by server");
+                                        break;
+                                    }
+                                    Some(Ok(_)) => {}
+                                    Some(Err(e)) => {
+                                        log::error!("WebSocketService: connection error: {:?}", e.to_string());
+                                        break;
+                                    }
+                                },
+                            };
+                        }
+                        *sender_clone.lock().await = None;
+                    }
+                    Err(e) => {
+                        log::error!("WebSocketService: error connecting to WebSocket: {:?}", e.to_string());
+                    }
                 }
-            };
-            let (sender, receiver) = wss.split();
-            
-            log::debug!("init ws thread: acquiring two locks...");
-            *struct_sender.lock().await = Some(sender);
-            *struct_receiver.lock().await = Some(receiver);
-            log::debug!("init ws thread: locks released. Sender and Receiver set.");
+
+                if cancel_clone.load(Ordering::SeqCst) {
+                    break;
+                }
+                on_status.emit(ConnectionStatus::Reconnecting);
+                log::debug!("WebSocketService: reconnecting in {:?}", backoff);
+                sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+                // Closed while waiting, e.g. after being kicked from the room
+                if cancel_clone.load(Ordering::SeqCst) {
+                    break;
+                }
+            }
         });
-        
-        // Handle incoming messages
-        let receiver_clone = receiver.clone();
-        let cancel_clone = cancel.clone();
+
+        // Heartbeats keep the connection open and tell the server whether the user is still there
+        let active = Arc::new(AtomicBool::new(true));
+        let heartbeat_sender = sender.clone();
+        let heartbeat_cancel = cancel.clone();
+        let heartbeat_active = active.clone();
         spawn_local(async move {
-            log::debug!("WebSocketService: listening for messages....");
-            let receiver = receiver_clone;
             loop {
-                if cancel_clone.load(Ordering::SeqCst) {
-                    log::debug!("WebSocketService: cancellation signal received, killing WebSocketService...");
+                sleep(HEARTBEAT_INTERVAL).await;
+                if heartbeat_cancel.load(Ordering::SeqCst) {
                     break;
                 }
-                        
-                let mut receiver_lock = receiver.lock().await;
-                if receiver_lock.is_none() {
-                    drop(receiver_lock);
-                    sleep(Duration::from_millis(100)).await;
-                    continue;
+                if let Some(sender) = &mut *heartbeat_sender.lock().await {
+                    let event = ClientEvent::Heartbeat { active: heartbeat_active.swap(false, Ordering::SeqCst) };
+                    if let Err(e) = sender.send(Message::Text(serde_json::to_string(&event).unwrap())).await {
+                        log::error!("WebSocketService: failed to send heartbeat: {:?}", e.to_string());
+                    }
                 }
-
-                let cancel_fut = is_cancelled(cancel_clone.clone()).fuse();
-                let mut receiver_fut = receiver_lock.as_mut().unwrap().next().fuse();
-
-                pin_mut!(cancel_fut);
-
-                futures::select! {
-                    cancel = cancel_fut => {
-                        if cancel {
-                            log::debug!("WebSocketService: cancellation signal received, exiting...");
-                            return;
-                        }
-                    },
-                    msg = receiver_fut => {
-                        if let Some(msg) = msg {
-                            let msg = msg.unwrap();
-                            let msg = msg.into_text().unwrap();
-                            let msg = serde_json::from_str::<ChatMessage>(&msg.as_str()).unwrap_or_else(|_| {
-                                log::error!("WebSocketService: failed to parse message: {:?}", msg);
-                                ChatMessage::default()
-                            });
-                            on_message.emit(msg);
-                        }
-                    },
-                };
-            };
+            }
         });
 
         log::debug!("WebSocketService new() finished");
-        
-        Self { sender, _receiver: receiver, user_id, username, cancel }
+
+        Self { sender, cancel, active }
     }
 
-    pub async fn send_message(&mut self, message: &String) -> Option<tokio_tungstenite_wasm::Error> {
+    /// Post a message, optionally as a reply and with uploaded attachments; the server acks
+    /// it with the same `nonce`
+    pub async fn send_message(&mut self, message: &str, nonce: &str, reply_to: Option<i32>, attachments: Vec<i32>) -> Option<tokio_tungstenite_wasm::Error> {
         log::debug!("WebSocketService: send_message() called");
-        let msg = ChatMessage {
-            user_id: self.user_id,
-            username: self.username.clone(),
-            content: message.clone(),
-            timestamp: chrono::Utc::now(),
-        };
+        self.send_event(&ClientEvent::Message {
+            content: message.to_string(),
+            nonce: nonce.to_string(),
+            reply_to,
+            attachments,
+        }).await
+    }
+
+    pub async fn send_event(&mut self, event: &ClientEvent) -> Option<tokio_tungstenite_wasm::Error> {
         let mut sender = self.sender.lock().await;
         if let Some(sender) = &mut *sender {
-            match sender.send(Message::Text(serde_json::to_string(&msg).unwrap())).await {
+            match sender.send(Message::Text(serde_json::to_string(event).unwrap())).await {
                 Ok(_) => None,
                 Err(e) => Some(e),
             }
@@ -120,13 +164,27 @@ impl WebSocketService {
         }
     }
 
-    #[allow(dead_code)]
+    /// Note that the user did something in the page, reported with the next heartbeat
+    pub fn mark_active(&self) {
+        self.active.store(true, Ordering::SeqCst);
+    }
+
     pub fn close(&self) {
         log::debug!("WebSocketService: close() called, setting cancellation signal...");
         self.cancel.store(true, Ordering::SeqCst);
     }
 }
 
+/// Remember the newest message delivered, so a reconnect can resume after it
+fn track_last_seen(last_seen: &AtomicI32, event: &ServerEvent) {
+    let newest = match event {
+        ServerEvent::Message(msg) => msg.message_id,
+        ServerEvent::HistoryBatch { messages, .. } => messages.iter().map(|msg| msg.message_id).max().unwrap_or(0),
+        _ => return,
+    };
+    last_seen.fetch_max(newest, Ordering::SeqCst);
+}
+
 // Cancel listener
 async fn is_cancelled(cancel_clone: Arc<AtomicBool>) -> bool {
     loop {
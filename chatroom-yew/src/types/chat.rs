@@ -1,11 +1,4 @@
-use serde::{Deserialize, Serialize};
-use chrono::{DateTime, Utc};
+//! Messages exchanged with the server over the chat room WebSocket, shared with the server
+//! through the `chat-protocol` crate. Every frame is a JSON object tagged by its `type` field.
 
-#[derive(Clone, Debug, Serialize, Deserialize, Default)]
-#[allow(dead_code)]
-pub struct ChatMessage {
-    pub user_id: i32,            
-    pub username: String,
-    pub content: String,
-    pub timestamp: DateTime<Utc>,
-}
+pub use chat_protocol::ws::{Attachment, ChatMessage, ClientEvent, PresenceStatus, Reaction, ServerEvent, Thumbnail, UserStatus};
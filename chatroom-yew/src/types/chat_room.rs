@@ -1,28 +1,8 @@
-use serde::{Deserialize, Serialize};
-
-#[derive(Serialize)]
-pub struct CreateChatRoomRequest {
-    pub user_id: i32,
-    pub room_name: String,
-}
-
-#[derive(Deserialize)]
-#[allow(dead_code)]
-pub struct CreateChatRoomResponse {
-    pub message: String,
-    pub room_id: i32,
-}
-
-#[derive(Serialize)]
-pub struct JoinChatRoomRequest {
-    pub user_id: i32,
-    pub room_id: i32,
-}
-
-#[derive(Deserialize)]
-#[allow(dead_code)]
-pub struct JoinChatRoomResponse {
-    pub message: String,
-    pub room_name: String,
-}
-
+pub use chat_protocol::api::{
+    AcceptInviteResponse, ChatRoomDetails, CreateChatRoomRequest, CreateChatRoomResponse, CreateInviteRequest,
+    DirectChat, DirectChatListResponse, ErrorResponse, InviteListResponse, JoinChatRoomRequest,
+    JoinChatRoomResponse, MemberListResponse, MessageSearchHit, MessageSearchQuery, MessageSearchResponse,
+    ModerationRequest, OpenDirectChatRequest, RoomInvite, RoomMember, RoomListQuery, RoomListResponse, RoomRole,
+    RoomSort, RoomSummary, RoomVisibility, SetRoleRequest, SlowModeRequest, ThreadResponse,
+    TransferOwnershipRequest, UpdateChatRoomRequest,
+};
@@ -0,0 +1,5 @@
+DROP TABLE IF EXISTS Sessions;
+DROP TABLE IF EXISTS UserInChatRoom;
+DROP TABLE IF EXISTS Messages;
+DROP TABLE IF EXISTS ChatRooms;
+DROP TABLE IF EXISTS Users;
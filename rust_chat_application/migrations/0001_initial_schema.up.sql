@@ -0,0 +1,52 @@
+-- Tables created by initialize_database before migrations existed. IF NOT EXISTS lets
+-- existing deployments adopt this migration without losing data.
+
+CREATE TABLE IF NOT EXISTS Users (
+    user_id INT AUTO_INCREMENT PRIMARY KEY,
+    username VARCHAR(50) UNIQUE NOT NULL,
+    email VARCHAR(100) UNIQUE NOT NULL,
+    password_hash VARCHAR(255) NOT NULL,
+    status ENUM('online', 'offline') DEFAULT 'offline',
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS ChatRooms (
+    chatroom_id INT AUTO_INCREMENT PRIMARY KEY,
+    room_name VARCHAR(100) UNIQUE NOT NULL,
+    created_by INT DEFAULT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (created_by) REFERENCES Users(user_id)
+        ON DELETE SET NULL
+);
+
+CREATE TABLE IF NOT EXISTS UserInChatRoom (
+    user_id INT NOT NULL,
+    chatroom_id INT NOT NULL,
+    joined_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (user_id, chatroom_id),
+    FOREIGN KEY (user_id) REFERENCES Users(user_id)
+        ON DELETE CASCADE,
+    FOREIGN KEY (chatroom_id) REFERENCES ChatRooms(chatroom_id)
+        ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS Messages (
+    message_id INT AUTO_INCREMENT PRIMARY KEY,
+    chatroom_id INT NOT NULL,
+    sender_id INT DEFAULT NULL,
+    message_text TEXT NOT NULL,
+    sent_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (chatroom_id) REFERENCES ChatRooms(chatroom_id)
+        ON DELETE CASCADE,
+    FOREIGN KEY (sender_id) REFERENCES Users(user_id)
+        ON DELETE SET NULL
+);
+
+CREATE TABLE IF NOT EXISTS Sessions (
+    token CHAR(64) PRIMARY KEY,
+    user_id INT NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    expires_at DATETIME NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES Users(user_id)
+        ON DELETE CASCADE
+);
@@ -0,0 +1,4 @@
+-- The history index also backs the chatroom_id foreign key, so give the key an index of its
+-- own before dropping it
+CREATE INDEX idx_messages_chatroom ON Messages (chatroom_id);
+DROP INDEX idx_messages_room_history ON Messages;
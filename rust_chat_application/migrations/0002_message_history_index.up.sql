@@ -0,0 +1,2 @@
+-- History is paged backwards from a message_id within one room
+CREATE INDEX idx_messages_room_history ON Messages (chatroom_id, message_id);
@@ -0,0 +1,5 @@
+DROP TABLE IF EXISTS MessageEdits;
+
+ALTER TABLE Messages
+    DROP COLUMN edited_at,
+    DROP COLUMN deleted_at;
@@ -0,0 +1,17 @@
+-- Edited messages keep their earlier versions in MessageEdits. Deleted messages stay
+-- behind as tombstones with an empty message_text.
+ALTER TABLE Messages
+    ADD COLUMN edited_at TIMESTAMP NULL DEFAULT NULL,
+    ADD COLUMN deleted_at TIMESTAMP NULL DEFAULT NULL;
+
+CREATE TABLE MessageEdits (
+    edit_id INT AUTO_INCREMENT PRIMARY KEY,
+    message_id INT NOT NULL,
+    previous_text TEXT NOT NULL,
+    edited_by INT DEFAULT NULL,
+    edited_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (message_id) REFERENCES Messages(message_id)
+        ON DELETE CASCADE,
+    FOREIGN KEY (edited_by) REFERENCES Users(user_id)
+        ON DELETE SET NULL
+);
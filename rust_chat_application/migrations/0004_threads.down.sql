@@ -0,0 +1,9 @@
+ALTER TABLE Messages
+    DROP FOREIGN KEY fk_messages_reply_to,
+    DROP FOREIGN KEY fk_messages_thread_root;
+
+ALTER TABLE Messages
+    DROP INDEX idx_messages_thread,
+    DROP INDEX fk_messages_reply_to,
+    DROP COLUMN reply_to,
+    DROP COLUMN thread_root_id;
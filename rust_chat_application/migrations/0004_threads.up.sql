@@ -0,0 +1,9 @@
+-- A reply points at the message it answers and at the first message of its thread
+ALTER TABLE Messages
+    ADD COLUMN reply_to INT NULL DEFAULT NULL,
+    ADD COLUMN thread_root_id INT NULL DEFAULT NULL,
+    ADD CONSTRAINT fk_messages_reply_to FOREIGN KEY (reply_to) REFERENCES Messages(message_id)
+        ON DELETE SET NULL,
+    ADD CONSTRAINT fk_messages_thread_root FOREIGN KEY (thread_root_id) REFERENCES Messages(message_id)
+        ON DELETE CASCADE,
+    ADD INDEX idx_messages_thread (thread_root_id, message_id);
@@ -0,0 +1,13 @@
+-- Each user can react to a message once per emoji. utf8mb4_bin keeps emoji that only
+-- differ in their variation selector or skin tone apart.
+CREATE TABLE Reactions (
+    message_id INT NOT NULL,
+    user_id INT NOT NULL,
+    emoji VARCHAR(32) CHARACTER SET utf8mb4 COLLATE utf8mb4_bin NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (message_id, user_id, emoji),
+    FOREIGN KEY (message_id) REFERENCES Messages(message_id)
+        ON DELETE CASCADE,
+    FOREIGN KEY (user_id) REFERENCES Users(user_id)
+        ON DELETE CASCADE
+);
@@ -0,0 +1,7 @@
+-- Only direct conversations have no room_name, and they cannot be kept
+DELETE FROM ChatRooms WHERE room_name IS NULL;
+
+DROP TABLE IF EXISTS DirectChats;
+
+ALTER TABLE ChatRooms
+    MODIFY room_name VARCHAR(100) NOT NULL;
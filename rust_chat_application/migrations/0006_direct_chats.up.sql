@@ -0,0 +1,17 @@
+-- A direct conversation is a chat room without a name that only its two participants can
+-- open. user_low is the smaller of the two user IDs, so each pair has a single row.
+ALTER TABLE ChatRooms
+    MODIFY room_name VARCHAR(100) NULL;
+
+CREATE TABLE DirectChats (
+    chatroom_id INT PRIMARY KEY,
+    user_low INT NOT NULL,
+    user_high INT NOT NULL,
+    UNIQUE KEY uq_direct_chats_users (user_low, user_high),
+    FOREIGN KEY (chatroom_id) REFERENCES ChatRooms(chatroom_id)
+        ON DELETE CASCADE,
+    FOREIGN KEY (user_low) REFERENCES Users(user_id)
+        ON DELETE CASCADE,
+    FOREIGN KEY (user_high) REFERENCES Users(user_id)
+        ON DELETE CASCADE
+);
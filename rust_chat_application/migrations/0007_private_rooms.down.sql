@@ -0,0 +1,4 @@
+DROP TABLE IF EXISTS RoomInvites;
+
+ALTER TABLE ChatRooms
+    DROP COLUMN visibility;
@@ -0,0 +1,23 @@
+-- Private rooms can only be joined with an invite. An invite either names one user or
+-- carries a token for a shareable link.
+ALTER TABLE ChatRooms
+    ADD COLUMN visibility ENUM('public', 'private') NOT NULL DEFAULT 'public';
+
+CREATE TABLE RoomInvites (
+    invite_id INT AUTO_INCREMENT PRIMARY KEY,
+    chatroom_id INT NOT NULL,
+    invited_user_id INT DEFAULT NULL,
+    token CHAR(32) UNIQUE DEFAULT NULL,
+    created_by INT DEFAULT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    expires_at DATETIME DEFAULT NULL,
+    max_uses INT DEFAULT NULL,
+    uses INT NOT NULL DEFAULT 0,
+    INDEX idx_room_invites_user (chatroom_id, invited_user_id),
+    FOREIGN KEY (chatroom_id) REFERENCES ChatRooms(chatroom_id)
+        ON DELETE CASCADE,
+    FOREIGN KEY (invited_user_id) REFERENCES Users(user_id)
+        ON DELETE CASCADE,
+    FOREIGN KEY (created_by) REFERENCES Users(user_id)
+        ON DELETE SET NULL
+);
@@ -0,0 +1,2 @@
+ALTER TABLE UserInChatRoom
+    DROP COLUMN role;
@@ -0,0 +1,14 @@
+-- Each member has a role in the room. Rooms start out owned by their creator; direct
+-- conversations have no owner.
+ALTER TABLE UserInChatRoom
+    ADD COLUMN role ENUM('owner', 'admin', 'moderator', 'member', 'read_only') NOT NULL DEFAULT 'member';
+
+INSERT IGNORE INTO UserInChatRoom (user_id, chatroom_id)
+    SELECT created_by, chatroom_id FROM ChatRooms
+    WHERE created_by IS NOT NULL
+    AND chatroom_id NOT IN (SELECT chatroom_id FROM DirectChats);
+
+UPDATE UserInChatRoom u
+    JOIN ChatRooms c ON c.chatroom_id = u.chatroom_id AND c.created_by = u.user_id
+    SET u.role = 'owner'
+    WHERE c.chatroom_id NOT IN (SELECT chatroom_id FROM DirectChats);
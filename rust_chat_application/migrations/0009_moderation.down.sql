@@ -0,0 +1,6 @@
+DROP TABLE IF EXISTS ModerationLog;
+DROP TABLE IF EXISTS RoomMutes;
+DROP TABLE IF EXISTS RoomBans;
+
+ALTER TABLE ChatRooms
+    DROP COLUMN slow_mode_secs;
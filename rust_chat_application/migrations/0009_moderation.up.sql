@@ -0,0 +1,52 @@
+-- Bans and mutes outlive membership, so leaving and rejoining a room does not lift them.
+-- A mute without expires_at lasts until it is lifted.
+ALTER TABLE ChatRooms
+    ADD COLUMN slow_mode_secs INT NOT NULL DEFAULT 0;
+
+CREATE TABLE RoomBans (
+    chatroom_id INT NOT NULL,
+    user_id INT NOT NULL,
+    banned_by INT DEFAULT NULL,
+    reason VARCHAR(255) DEFAULT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (chatroom_id, user_id),
+    FOREIGN KEY (chatroom_id) REFERENCES ChatRooms(chatroom_id)
+        ON DELETE CASCADE,
+    FOREIGN KEY (user_id) REFERENCES Users(user_id)
+        ON DELETE CASCADE,
+    FOREIGN KEY (banned_by) REFERENCES Users(user_id)
+        ON DELETE SET NULL
+);
+
+CREATE TABLE RoomMutes (
+    chatroom_id INT NOT NULL,
+    user_id INT NOT NULL,
+    muted_by INT DEFAULT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    expires_at DATETIME DEFAULT NULL,
+    PRIMARY KEY (chatroom_id, user_id),
+    FOREIGN KEY (chatroom_id) REFERENCES ChatRooms(chatroom_id)
+        ON DELETE CASCADE,
+    FOREIGN KEY (user_id) REFERENCES Users(user_id)
+        ON DELETE CASCADE,
+    FOREIGN KEY (muted_by) REFERENCES Users(user_id)
+        ON DELETE SET NULL
+);
+
+CREATE TABLE ModerationLog (
+    log_id INT AUTO_INCREMENT PRIMARY KEY,
+    chatroom_id INT NOT NULL,
+    action ENUM('kick', 'ban', 'unban', 'mute', 'unmute', 'slow_mode') NOT NULL,
+    moderator_id INT DEFAULT NULL,
+    target_user_id INT DEFAULT NULL,
+    reason VARCHAR(255) DEFAULT NULL,
+    duration INT DEFAULT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    INDEX idx_moderation_log_room (chatroom_id, log_id),
+    FOREIGN KEY (chatroom_id) REFERENCES ChatRooms(chatroom_id)
+        ON DELETE CASCADE,
+    FOREIGN KEY (moderator_id) REFERENCES Users(user_id)
+        ON DELETE SET NULL,
+    FOREIGN KEY (target_user_id) REFERENCES Users(user_id)
+        ON DELETE SET NULL
+);
@@ -0,0 +1,4 @@
+ALTER TABLE ChatRooms
+    DROP COLUMN archived_at,
+    DROP COLUMN topic,
+    DROP COLUMN description;
@@ -0,0 +1,6 @@
+-- Rooms can describe themselves and carry a topic. Archived rooms stay readable, but
+-- nobody can post in them until they are unarchived.
+ALTER TABLE ChatRooms
+    ADD COLUMN description VARCHAR(500) DEFAULT NULL,
+    ADD COLUMN topic VARCHAR(255) DEFAULT NULL,
+    ADD COLUMN archived_at TIMESTAMP NULL DEFAULT NULL;
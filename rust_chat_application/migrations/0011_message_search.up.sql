@@ -0,0 +1,3 @@
+-- Message search matches whole words and word prefixes in boolean mode. The first
+-- FULLTEXT index on a table rebuilds it, so this can take a while on large histories.
+CREATE FULLTEXT INDEX idx_messages_text ON Messages (message_text);
@@ -0,0 +1,20 @@
+-- Uploaded files. The file itself lives on disk under its sha256, so identical uploads
+-- share one copy. message_id is set once the upload is sent in a message.
+CREATE TABLE Attachments (
+    attachment_id INT AUTO_INCREMENT PRIMARY KEY,
+    chatroom_id INT NOT NULL,
+    uploader_id INT DEFAULT NULL,
+    message_id INT DEFAULT NULL,
+    sha256 CHAR(64) NOT NULL,
+    file_name VARCHAR(255) NOT NULL,
+    content_type VARCHAR(100) NOT NULL,
+    size_bytes BIGINT UNSIGNED NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    INDEX idx_attachments_message (message_id),
+    FOREIGN KEY (chatroom_id) REFERENCES ChatRooms(chatroom_id)
+        ON DELETE CASCADE,
+    FOREIGN KEY (uploader_id) REFERENCES Users(user_id)
+        ON DELETE SET NULL,
+    FOREIGN KEY (message_id) REFERENCES Messages(message_id)
+        ON DELETE CASCADE
+);
@@ -0,0 +1,5 @@
+DROP TABLE IF EXISTS AttachmentThumbnails;
+
+ALTER TABLE Attachments
+    DROP COLUMN width,
+    DROP COLUMN height;
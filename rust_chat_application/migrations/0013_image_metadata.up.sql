@@ -0,0 +1,16 @@
+-- Dimensions of image attachments and the thumbnails made of them. A thumbnail is stored
+-- on disk next to its image, named after the size it was made for.
+ALTER TABLE Attachments
+    ADD COLUMN width INT UNSIGNED DEFAULT NULL,
+    ADD COLUMN height INT UNSIGNED DEFAULT NULL;
+
+CREATE TABLE AttachmentThumbnails (
+    attachment_id INT NOT NULL,
+    max_size INT UNSIGNED NOT NULL,
+    width INT UNSIGNED NOT NULL,
+    height INT UNSIGNED NOT NULL,
+    content_type VARCHAR(100) NOT NULL,
+    PRIMARY KEY (attachment_id, max_size),
+    FOREIGN KEY (attachment_id) REFERENCES Attachments(attachment_id)
+        ON DELETE CASCADE
+);
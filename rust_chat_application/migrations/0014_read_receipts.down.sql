@@ -0,0 +1,2 @@
+ALTER TABLE UserInChatRoom
+    DROP COLUMN last_read_message_id;
@@ -0,0 +1,7 @@
+-- How far each member has read a room. Members start out having read everything that was
+-- posted before they joined, or before this migration.
+ALTER TABLE UserInChatRoom
+    ADD COLUMN last_read_message_id INT DEFAULT NULL;
+
+UPDATE UserInChatRoom u
+    SET u.last_read_message_id = (SELECT MAX(m.message_id) FROM Messages m WHERE m.chatroom_id = u.chatroom_id);
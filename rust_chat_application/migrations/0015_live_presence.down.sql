@@ -0,0 +1,2 @@
+ALTER TABLE Users
+    ADD COLUMN status ENUM('online', 'offline') DEFAULT 'offline' AFTER password_hash;
@@ -0,0 +1,4 @@
+-- Presence now comes from open WebSocket connections, so the stored status, which only
+-- changed on login and logout, is dropped.
+ALTER TABLE Users
+    DROP COLUMN status;
@@ -0,0 +1,14 @@
+-- Like bans and mutes, a demotion to read_only outlives membership, so leaving and
+-- rejoining a room does not lift it.
+CREATE TABLE RoomReadOnlyUsers (
+    chatroom_id INT NOT NULL,
+    user_id INT NOT NULL,
+    PRIMARY KEY (chatroom_id, user_id),
+    FOREIGN KEY (chatroom_id) REFERENCES ChatRooms(chatroom_id)
+        ON DELETE CASCADE,
+    FOREIGN KEY (user_id) REFERENCES Users(user_id)
+        ON DELETE CASCADE
+);
+
+INSERT INTO RoomReadOnlyUsers (chatroom_id, user_id)
+    SELECT chatroom_id, user_id FROM UserInChatRoom WHERE role = 'read_only';
@@ -3,11 +3,18 @@
 # Base URL for the API
 BASE_URL="http://127.0.0.1:3000/api/chatrooms"
 
+# Function to log in and print the session token
+login() {
+    local email=$1
+    curl -s -H "Content-Type: application/json" -d "{\"email\":\"${email}\",\"password\":\"11111\"}" http://127.0.0.1:3000/api/user/login \
+        | sed -E 's/.*"token":"([^"]+)".*/\1/'
+}
+
 # Function to create a chat room
 create_chat_room() {
-    local user_id=$1
+    local token=$1
     local room_name=$2
-    curl -H "Content-Type: application/json" -d "{\"user_id\":${user_id},\"room_name\":\"${room_name}\"}" ${BASE_URL}
+    curl -H "Content-Type: application/json" -H "Authorization: Bearer ${token}" -d "{\"room_name\":\"${room_name}\"}" ${BASE_URL}
     echo
 }
 
@@ -18,6 +25,6 @@ curl -H "Content-Type: application/json" -d "{\"username\":\"Carol\",\"email\":\
 curl -H "Content-Type: application/json" -d "{\"username\":\"Yves\",\"email\":\"yves@gmail.com\",\"password\":\"11111\"}" http://127.0.0.1:3000/api/user/signup
 
 # Create 3 Chat Rooms
-create_chat_room 1 "ChatRoom1"
-create_chat_room 2 "ChatRoom2"
-create_chat_room 3 "ChatRoom3"
+create_chat_room "$(login alice@gmail.com)" "ChatRoom1"
+create_chat_room "$(login bob@gmail.com)" "ChatRoom2"
+create_chat_room "$(login carol@gmail.com)" "ChatRoom3"
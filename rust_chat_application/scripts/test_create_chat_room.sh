@@ -3,11 +3,18 @@
 # Base URL for the API
 BASE_URL="http://127.0.0.1:3000/api/chatrooms"
 
+# Function to log in and print the session token
+login() {
+    local email=$1
+    curl -s -H "Content-Type: application/json" -d "{\"email\":\"${email}\",\"password\":\"11111\"}" http://127.0.0.1:3000/api/user/login \
+        | sed -E 's/.*"token":"([^"]+)".*/\1/'
+}
+
 # Function to create a chat room
 create_chat_room() {
-    local user_id=$1
+    local token=$1
     local room_name=$2
-    curl -H "Content-Type: application/json" -d "{\"user_id\":${user_id},\"room_name\":\"${room_name}\"}" ${BASE_URL}
+    curl -H "Content-Type: application/json" -H "Authorization: Bearer ${token}" -d "{\"room_name\":\"${room_name}\"}" ${BASE_URL}
     echo
 }
 
@@ -18,16 +25,19 @@ curl -H "Content-Type: application/json" -d "{\"username\":\"Hello\",\"email\":\
 curl -H "Content-Type: application/json" -d "{\"username\":\"Hey..\",\"email\":\"zzz@gmail.com\",\"password\":\"11111\"}" http://127.0.0.1:3000/api/user/signup
 
 # Create chat rooms with various names
-create_chat_room 1 "ChatRoom1"
-create_chat_room 1 "ChatRoom2"
-create_chat_room 1 "ChatRoom3"
-create_chat_room 1 ""          # Empty name
-create_chat_room 1 "ChatRoom1" # Duplicate name
-create_chat_room 2 "ChatRoom4"
-create_chat_room 2 "ChatRoom5"
-create_chat_room 2 ""          # Empty name
-create_chat_room 2 "ChatRoom2" # Duplicate name
-create_chat_room 3 "ChatRoom6"
-create_chat_room 3 "ChatRoom7"
-create_chat_room 3 ""          # Empty name
-create_chat_room 3 "ChatRoom3" # Duplicate name
+TOKEN1=$(login lll@gmail.com)
+TOKEN2=$(login yyy@gmail.com)
+TOKEN3=$(login zzz@gmail.com)
+create_chat_room $TOKEN1 "ChatRoom1"
+create_chat_room $TOKEN1 "ChatRoom2"
+create_chat_room $TOKEN1 "ChatRoom3"
+create_chat_room $TOKEN1 ""          # Empty name
+create_chat_room $TOKEN1 "ChatRoom1" # Duplicate name
+create_chat_room $TOKEN2 "ChatRoom4"
+create_chat_room $TOKEN2 "ChatRoom5"
+create_chat_room $TOKEN2 ""          # Empty name
+create_chat_room $TOKEN2 "ChatRoom2" # Duplicate name
+create_chat_room $TOKEN3 "ChatRoom6"
+create_chat_room $TOKEN3 "ChatRoom7"
+create_chat_room $TOKEN3 ""          # Empty name
+create_chat_room $TOKEN3 "ChatRoom3" # Duplicate name
@@ -0,0 +1,88 @@
+use crate::config::{Config, StorageBackend};
+use crate::database::{self, migrations};
+
+pub const USAGE: &str = "\
+Usage: rust_chat_application [COMMAND]
+
+Commands:
+  serve            Run the chat server (default)
+  migrate          Apply all pending schema migrations
+  rollback [N]     Revert the last N applied migrations (default 1)
+  status           List migrations and whether they are applied
+  reset --yes      Drop every table and re-apply all migrations";
+
+/// What the binary was asked to do
+pub enum Command {
+    Serve,
+    Migrate,
+    Rollback(usize),
+    Status,
+    Reset,
+}
+
+impl Command {
+    /// Parse the command line arguments, without the program name
+    pub fn parse(args: &[String]) -> Result<Command, String> {
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        match args.as_slice() {
+            [] | ["serve"] => Ok(Command::Serve),
+            ["migrate"] => Ok(Command::Migrate),
+            ["rollback"] => Ok(Command::Rollback(1)),
+            ["rollback", steps] => steps
+                .parse()
+                .map(Command::Rollback)
+                .map_err(|_| format!("Invalid number of steps `{steps}`")),
+            ["status"] => Ok(Command::Status),
+            ["reset", "--yes"] => Ok(Command::Reset),
+            ["reset"] => Err("`reset` drops every table and all data in it; re-run as `reset --yes` to confirm".to_string()),
+            _ => Err(USAGE.to_string()),
+        }
+    }
+}
+
+/// Run one of the schema management commands against the configured database
+pub async fn run_database_command(command: Command, config: &Config) -> Result<(), String> {
+    if config.storage != StorageBackend::Mysql {
+        return Err("Schema migrations only apply to the mysql storage backend".to_string());
+    }
+    let pool = database::connect(config)?;
+
+    match command {
+        Command::Serve => unreachable!("serve is handled by main"),
+        Command::Migrate => {
+            let applied = migrations::migrate(&pool).await?;
+            if applied.is_empty() {
+                println!("Schema is up to date");
+            }
+            for version in applied {
+                println!("Applied migration {version}");
+            }
+        }
+        Command::Rollback(steps) => {
+            let reverted = migrations::rollback(&pool, steps).await?;
+            if reverted.is_empty() {
+                println!("No applied migrations to roll back");
+            }
+            for version in reverted {
+                println!("Rolled back migration {version}");
+            }
+        }
+        Command::Status => {
+            for migration in migrations::status(&pool).await? {
+                let state = match &migration.applied_at {
+                    Some(applied_at) => format!("applied {applied_at}"),
+                    None => String::from("pending"),
+                };
+                println!("{:>4}  {:<40} {}", migration.version, migration.name, state);
+            }
+        }
+        Command::Reset => {
+            database::drop_tables(&pool).await?;
+            println!("Dropped all tables");
+            let applied = migrations::migrate(&pool).await?;
+            println!("Applied {} migrations", applied.len());
+        }
+    }
+
+    pool.disconnect().await.map_err(|e| e.to_string())
+}
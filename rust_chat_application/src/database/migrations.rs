@@ -0,0 +1,144 @@
+use mysql_async::prelude::*;
+use mysql_async::{Conn, Pool};
+
+/// A versioned schema change. Scripts live in `migrations/` as
+/// `<version>_<name>.up.sql` and `<version>_<name>.down.sql`.
+pub struct Migration {
+    pub version: u32,
+    pub name: &'static str,
+    pub up: &'static str,
+    pub down: &'static str,
+}
+
+macro_rules! migration {
+    ($version:expr, $file:literal) => {
+        Migration {
+            version: $version,
+            name: $file,
+            up: include_str!(concat!("../../migrations/", $file, ".up.sql")),
+            down: include_str!(concat!("../../migrations/", $file, ".down.sql")),
+        }
+    };
+}
+
+/// Every migration, in the order they are applied. Append new ones at the end and never
+/// edit one that has been released.
+pub const MIGRATIONS: &[Migration] = &[
+    migration!(1, "0001_initial_schema"),
+    migration!(2, "0002_message_history_index"),
+    migration!(3, "0003_message_edits"),
+    migration!(4, "0004_threads"),
+    migration!(5, "0005_reactions"),
+    migration!(6, "0006_direct_chats"),
+    migration!(7, "0007_private_rooms"),
+    migration!(8, "0008_room_roles"),
+    migration!(9, "0009_moderation"),
+    migration!(10, "0010_room_details"),
+    migration!(11, "0011_message_search"),
+    migration!(12, "0012_attachments"),
+    migration!(13, "0013_image_metadata"),
+    migration!(14, "0014_read_receipts"),
+    migration!(15, "0015_live_presence"),
+    migration!(16, "0016_read_only_users"),
+];
+
+/// The state of one migration, as reported by the `status` command
+pub struct MigrationStatus {
+    pub version: u32,
+    pub name: &'static str,
+    /// When the migration was applied, or `None` if it is pending
+    pub applied_at: Option<String>,
+}
+
+async fn ensure_migrations_table(conn: &mut Conn) -> Result<(), String> {
+    conn.query_drop(
+        r"CREATE TABLE IF NOT EXISTS schema_migrations (
+            version INT PRIMARY KEY,
+            name VARCHAR(255) NOT NULL,
+            applied_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+        )",
+    )
+    .await
+    .map_err(|e| e.to_string())
+}
+
+async fn applied_versions(conn: &mut Conn) -> Result<Vec<u32>, String> {
+    ensure_migrations_table(conn).await?;
+    conn.query("SELECT version FROM schema_migrations ORDER BY version")
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Apply every pending migration in order and return the versions that were applied
+pub async fn migrate(pool: &Pool) -> Result<Vec<u32>, String> {
+    let mut conn = pool.get_conn().await.map_err(|e| e.to_string())?;
+    let applied = applied_versions(&mut conn).await?;
+
+    let mut newly_applied = Vec::new();
+    for migration in MIGRATIONS.iter().filter(|m| !applied.contains(&m.version)) {
+        conn.query_drop(migration.up)
+            .await
+            .map_err(|e| format!("Migration {} failed: {}", migration.name, e))?;
+        conn.exec_drop(
+            r"INSERT INTO schema_migrations (version, name) VALUES (:version, :name)",
+            params! {
+                "version" => migration.version,
+                "name" => migration.name,
+            },
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+        newly_applied.push(migration.version);
+    }
+    Ok(newly_applied)
+}
+
+/// Revert the `steps` most recently applied migrations and return their versions
+pub async fn rollback(pool: &Pool, steps: usize) -> Result<Vec<u32>, String> {
+    let mut conn = pool.get_conn().await.map_err(|e| e.to_string())?;
+    let applied = applied_versions(&mut conn).await?;
+
+    let mut reverted = Vec::new();
+    for version in applied.into_iter().rev().take(steps) {
+        let migration = MIGRATIONS
+            .iter()
+            .find(|m| m.version == version)
+            .ok_or_else(|| format!("Migration {version} is applied but unknown to this build"))?;
+        conn.query_drop(migration.down)
+            .await
+            .map_err(|e| format!("Rollback of {} failed: {}", migration.name, e))?;
+        conn.exec_drop(
+            r"DELETE FROM schema_migrations WHERE version = :version",
+            params! {
+                "version" => version,
+            },
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+        reverted.push(version);
+    }
+    Ok(reverted)
+}
+
+/// List every known migration and whether it has been applied
+pub async fn status(pool: &Pool) -> Result<Vec<MigrationStatus>, String> {
+    let mut conn = pool.get_conn().await.map_err(|e| e.to_string())?;
+    ensure_migrations_table(&mut conn).await?;
+
+    let applied: Vec<(u32, String)> = conn
+        .query("SELECT version, CAST(applied_at AS CHAR) FROM schema_migrations")
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(MIGRATIONS
+        .iter()
+        .map(|migration| MigrationStatus {
+            version: migration.version,
+            name: migration.name,
+            applied_at: applied
+                .iter()
+                .find(|(version, _)| *version == migration.version)
+                .map(|(_, applied_at)| applied_at.clone()),
+        })
+        .collect())
+}
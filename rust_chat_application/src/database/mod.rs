@@ -1,106 +1,55 @@
-use mysql_async::prelude::Queryable; 
-use mysql_async::{Pool, Conn};
+use mysql_async::prelude::Queryable;
+use mysql_async::{Conn, Opts, OptsBuilder, Pool, PoolConstraints, PoolOpts};
 
-pub async fn initialize_database(pool: &Pool) -> Result<(), String> {
-    let mut conn = pool.get_conn().await.map_err(|e| e.to_string())?;
+use crate::config::Config;
 
-    // delete current exist table
-    // drop_tables(&mut conn).await?;
+pub mod migrations;
 
-    // create a new one
-    create_users_table(&mut conn).await?;
-    create_chatrooms_table(&mut conn).await?;
-    create_user_in_chatroom_table(&mut conn).await?;
-    create_messages_table(&mut conn).await?;
+/// Create the connection pool described by the config
+pub fn connect(config: &Config) -> Result<Pool, String> {
+    let constraints = PoolConstraints::new(config.pool_min_connections, config.pool_max_connections)
+        .ok_or_else(|| "pool_min_connections must not exceed pool_max_connections".to_string())?;
+    let opts = Opts::from_url(&config.database_url).map_err(|e| e.to_string())?;
+    let opts = OptsBuilder::from_opts(opts)
+        .pool_opts(PoolOpts::default().with_constraints(constraints));
+    Ok(Pool::new(opts))
+}
 
+/// Bring the schema up to date by applying every pending migration
+pub async fn initialize_database(pool: &Pool) -> Result<(), String> {
+    let applied = migrations::migrate(pool).await?;
+    for version in applied {
+        tracing::info!("Applied migration {version}");
+    }
     Ok(())
 }
-// delete table
-#[allow(dead_code)]
-async fn drop_tables(conn: &mut Conn) -> Result<(), String> {
-    // check the dependency
-    conn.query_drop("DROP TABLE IF EXISTS UserInChatRoom")
-        .await
-        .map_err(|e| e.to_string())?;
-    conn.query_drop("DROP TABLE IF EXISTS Messages")
+
+/// Drop every table in the database, including `schema_migrations`.
+/// Only reachable through the `reset --yes` command.
+pub async fn drop_tables(pool: &Pool) -> Result<(), String> {
+    let mut conn = pool.get_conn().await.map_err(|e| e.to_string())?;
+
+    let tables: Vec<String> = conn
+        .query("SELECT table_name FROM information_schema.tables WHERE table_schema = DATABASE()")
         .await
         .map_err(|e| e.to_string())?;
-    conn.query_drop("DROP TABLE IF EXISTS ChatRooms")
+
+    // Foreign keys would otherwise force us to drop tables in dependency order
+    conn.query_drop("SET FOREIGN_KEY_CHECKS = 0")
         .await
         .map_err(|e| e.to_string())?;
-    conn.query_drop("DROP TABLE IF EXISTS Users")
+    let result = drop_each(&mut conn, &tables).await;
+    conn.query_drop("SET FOREIGN_KEY_CHECKS = 1")
         .await
         .map_err(|e| e.to_string())?;
-    Ok(())
-}
-
-// create Users table
-async fn create_users_table(conn: &mut Conn) -> Result<(), String> {
-    conn.query_drop(
-        r"CREATE TABLE IF NOT EXISTS Users (
-            user_id INT AUTO_INCREMENT PRIMARY KEY,
-            username VARCHAR(50) UNIQUE NOT NULL,
-            email VARCHAR(100) UNIQUE NOT NULL,
-            password_hash VARCHAR(255) NOT NULL,
-            status ENUM('online', 'offline') DEFAULT 'offline',
-            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
-        )"
-    )
-    .await
-    .map_err(|e| e.to_string())
-}
-
-// create ChatRooms table
-async fn create_chatrooms_table(conn: &mut Conn) -> Result<(), String> {
-    conn.query_drop(
-        r"CREATE TABLE IF NOT EXISTS ChatRooms (
-            chatroom_id INT AUTO_INCREMENT PRIMARY KEY,
-            room_name VARCHAR(100) UNIQUE NOT NULL,
-            created_by INT DEFAULT NULL, 
-            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
-            FOREIGN KEY (created_by) REFERENCES Users(user_id)
-                ON DELETE SET NULL
-        )",
-    )
-    .await
-    .map_err(|e| e.to_string())
+    result
 }
 
-
-// create UserInChatRoom table
-async fn create_user_in_chatroom_table(conn: &mut Conn) -> Result<(), String> {
-    conn.query_drop(
-        r"CREATE TABLE IF NOT EXISTS UserInChatRoom (
-            user_id INT NOT NULL,
-            chatroom_id INT NOT NULL,
-            joined_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
-            PRIMARY KEY (user_id, chatroom_id),
-            FOREIGN KEY (user_id) REFERENCES Users(user_id)
-                ON DELETE CASCADE,
-            FOREIGN KEY (chatroom_id) REFERENCES ChatRooms(chatroom_id)
-                ON DELETE CASCADE
-        )"
-    )
-    .await
-    .map_err(|e| e.to_string())
-}
-
-// create Messages table
-async fn create_messages_table(conn: &mut Conn) -> Result<(), String> {
-    conn.query_drop(
-        r"CREATE TABLE IF NOT EXISTS Messages (
-            message_id INT AUTO_INCREMENT PRIMARY KEY,
-            chatroom_id INT NOT NULL,
-            sender_id INT DEFAULT NULL, 
-            message_text TEXT NOT NULL,
-            sent_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
-            FOREIGN KEY (chatroom_id) REFERENCES ChatRooms(chatroom_id)
-                ON DELETE CASCADE,
-            FOREIGN KEY (sender_id) REFERENCES Users(user_id)
-                ON DELETE SET NULL
-        )",
-    )
-    .await
-    .map_err(|e| e.to_string())
+async fn drop_each(conn: &mut Conn, tables: &[String]) -> Result<(), String> {
+    for table in tables {
+        conn.query_drop(format!("DROP TABLE IF EXISTS `{}`", table.replace('`', "``")))
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(())
 }
-
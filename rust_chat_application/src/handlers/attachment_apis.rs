@@ -0,0 +1,163 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Json, Multipart, Path, Request},
+    http::{
+        header::{CONTENT_DISPOSITION, CONTENT_TYPE, X_CONTENT_TYPE_OPTIONS},
+        HeaderValue, StatusCode,
+    },
+    response::IntoResponse,
+    Extension,
+};
+use chat_protocol::api::ErrorResponse;
+use tower_http::services::ServeFile;
+
+use crate::handlers::session::AuthUser;
+use crate::services::attachment_service::{AttachmentService, INLINE_IMAGE_TYPES};
+use crate::AppState;
+
+/// Room for the multipart framing around an upload on top of `max_attachment_bytes`
+pub const MULTIPART_OVERHEAD_BYTES: usize = 64 * 1024;
+
+/// Upload one file, sent as the `file` field of a multipart form, to be attached to a
+/// message in the room
+pub async fn upload_attachment(
+    Extension(state): Extension<Arc<AppState>>,
+    user: AuthUser,
+    Path(chatroom_id): Path<i32>,
+    mut multipart: Multipart,
+) -> impl IntoResponse {
+    loop {
+        let field = match multipart.next_field().await {
+            Ok(Some(field)) => field,
+            Ok(None) => break,
+            Err(e) => return (e.status(), Json(ErrorResponse { error: e.body_text() })).into_response(),
+        };
+        if field.name() != Some("file") {
+            continue;
+        }
+
+        let file_name = field.file_name().unwrap_or_default().to_string();
+        let content_type = field.content_type().unwrap_or("application/octet-stream").to_string();
+        let contents = match field.bytes().await {
+            Ok(contents) => contents,
+            Err(e) => return (e.status(), Json(ErrorResponse { error: e.body_text() })).into_response(),
+        };
+
+        let service = AttachmentService::new(state.store.clone(), state.config.clone());
+        return match service.upload(user.user_id, chatroom_id, &file_name, &content_type, contents.to_vec()).await {
+            Ok(attachment) => (StatusCode::CREATED, Json(attachment)).into_response(),
+            Err(e) => (attachment_error_status(&e), Json(ErrorResponse { error: e })).into_response(),
+        };
+    }
+
+    let error = String::from("Missing file field");
+    (StatusCode::BAD_REQUEST, Json(ErrorResponse { error })).into_response()
+}
+
+/// Serve an attachment to a member of its room. Images are shown inline, anything else is
+/// downloaded, and browsers are told not to guess a different type.
+pub async fn download_attachment(
+    Extension(state): Extension<Arc<AppState>>,
+    user: AuthUser,
+    Path(attachment_id): Path<i32>,
+    request: Request,
+) -> impl IntoResponse {
+    let service = AttachmentService::new(state.store.clone(), state.config.clone());
+    let (path, attachment) = match service.open(user.user_id, attachment_id).await {
+        Ok(file) => file,
+        Err(e) => return (attachment_error_status(&e), Json(ErrorResponse { error: e })).into_response(),
+    };
+
+    // ServeFile handles conditional and range requests
+    let mut response = match ServeFile::new(path).try_call(request).await {
+        Ok(response) => response.map(axum::body::Body::new),
+        Err(e) => {
+            tracing::error!("Could not read attachment {attachment_id}: {e}");
+            let error = String::from("Could not read the attachment");
+            return (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error })).into_response();
+        }
+    };
+    if !response.status().is_success() {
+        return response;
+    }
+
+    let inline = INLINE_IMAGE_TYPES.contains(&attachment.content_type.as_str());
+    let headers = response.headers_mut();
+    if let Ok(content_type) = HeaderValue::from_str(&attachment.content_type) {
+        headers.insert(CONTENT_TYPE, content_type);
+    }
+    headers.insert(X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff"));
+    if let Ok(disposition) = HeaderValue::from_str(&content_disposition(inline, &attachment.file_name)) {
+        headers.insert(CONTENT_DISPOSITION, disposition);
+    }
+    response
+}
+
+/// Serve a thumbnail of an image attachment, made for the size in the path
+pub async fn download_thumbnail(
+    Extension(state): Extension<Arc<AppState>>,
+    user: AuthUser,
+    Path((attachment_id, max_size)): Path<(i32, u32)>,
+    request: Request,
+) -> impl IntoResponse {
+    let service = AttachmentService::new(state.store.clone(), state.config.clone());
+    let (path, content_type) = match service.open_thumbnail(user.user_id, attachment_id, max_size).await {
+        Ok(file) => file,
+        Err(e) => return (attachment_error_status(&e), Json(ErrorResponse { error: e })).into_response(),
+    };
+
+    let mut response = match ServeFile::new(path).try_call(request).await {
+        Ok(response) => response.map(axum::body::Body::new),
+        Err(e) => {
+            tracing::error!("Could not read thumbnail {max_size} of attachment {attachment_id}: {e}");
+            let error = String::from("Could not read the attachment");
+            return (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error })).into_response();
+        }
+    };
+    if !response.status().is_success() {
+        return response;
+    }
+
+    let headers = response.headers_mut();
+    if let Ok(content_type) = HeaderValue::from_str(&content_type) {
+        headers.insert(CONTENT_TYPE, content_type);
+    }
+    headers.insert(X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff"));
+    headers.insert(CONTENT_DISPOSITION, HeaderValue::from_static("inline"));
+    response
+}
+
+/// A `Content-Disposition` value naming the file, with an ASCII fallback for old browsers
+fn content_disposition(inline: bool, file_name: &str) -> String {
+    let fallback: String = file_name
+        .chars()
+        .map(|c| if c.is_ascii_graphic() && c != '"' && c != '\\' || c == ' ' { c } else { '_' })
+        .collect();
+    let encoded: String = file_name
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'.' | b'-' | b'_' => (b as char).to_string(),
+            _ => format!("%{b:02X}"),
+        })
+        .collect();
+    let kind = if inline { "inline" } else { "attachment" };
+    format!("{kind}; filename=\"{fallback}\"; filename*=UTF-8''{encoded}")
+}
+
+/// Map errors from `AttachmentService` and the permission checks to a status code
+fn attachment_error_status(error: &str) -> StatusCode {
+    match error {
+        "Attachment not found" | "Thumbnail not found" | "Chat room not found" => StatusCode::NOT_FOUND,
+        "Not a member of this chat room"
+        | "Banned from this chat room"
+        | "Not allowed to post in this chat room"
+        | "You are muted in this chat room"
+        | "This chat room is archived" => StatusCode::FORBIDDEN,
+        "The attachment is empty" => StatusCode::BAD_REQUEST,
+        error if error.starts_with("The attachment is not a valid") => StatusCode::BAD_REQUEST,
+        error if error.starts_with("Attachments of type") => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+        error if error.starts_with("The attachment is larger") => StatusCode::PAYLOAD_TOO_LARGE,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
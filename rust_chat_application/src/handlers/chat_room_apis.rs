@@ -1,62 +1,379 @@
-use axum::{extract::Json, http::StatusCode, response::IntoResponse};
-use serde::Deserialize;
-use serde_json::json;
-use crate::services::chat_room_service::ChatRoomService;
+use std::sync::Arc;
 
-#[derive(Deserialize)]
-pub struct CreateChatRoomPayload {
-    pub room_name: String,
-    pub user_id: i32
+use axum::{extract::{Json, Path, Query}, http::StatusCode, response::IntoResponse, Extension};
+use chat_protocol::api::{
+    AcceptInviteResponse, ChatRoomDetails, CreateChatRoomRequest, CreateChatRoomResponse, CreateInviteRequest, DirectChat,
+    DirectChatListResponse, ErrorResponse, InviteListResponse, JoinChatRoomRequest, JoinChatRoomResponse,
+    LeaveChatRoomRequest, MemberListResponse, MessageHistoryQuery, MessageHistoryResponse, MessageResponse,
+    OpenDirectChatRequest, RoomListQuery, RoomListResponse, RoomMember, RoomRole, SetRoleRequest,
+    TransferOwnershipRequest, UpdateChatRoomRequest,
+};
+use chat_protocol::ws::ServerEvent;
+use crate::handlers::session::AuthUser;
+use crate::handlers::websocket_handler::{close_channel, send_to_channel};
+use crate::services::chat_room_service::{ChatRoomService, Permission};
+use crate::AppState;
+
+pub async fn create_chat_room(
+    Extension(state): Extension<Arc<AppState>>,
+    user: AuthUser,
+    Json(payload): Json<CreateChatRoomRequest>,
+) -> impl IntoResponse {
+    let service = ChatRoomService::new(state.store.clone());
+    match service.create_chat_room(payload.room_name, user.user_id, payload.visibility).await {
+        Ok(room_id) => (StatusCode::CREATED, Json(CreateChatRoomResponse {
+            message: String::from("Chat room created"),
+            room_id,
+        })).into_response(),
+        Err(e) => match e.as_str() {
+            "Room name cannot be empty" | "Room name is too long" => {
+                (StatusCode::BAD_REQUEST, Json(ErrorResponse { error: e })).into_response()
+            }
+            _ => (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e })).into_response(),
+        },
+    }
 }
 
-#[derive(Deserialize)]
-pub struct JoinChatRoomPayload {
-    pub user_id: i32,
-    pub room_id: i32,
+pub async fn get_chat_room(
+    Extension(state): Extension<Arc<AppState>>,
+    user: AuthUser,
+    Path(room_id): Path<i32>,
+) -> impl IntoResponse {
+    let service = ChatRoomService::new(state.store.clone());
+    match service.get_room_details(user.user_id, room_id).await {
+        Ok(details) => (StatusCode::OK, Json(details)).into_response(),
+        Err(e) => (room_error_status(&e), Json(ErrorResponse { error: e })).into_response(),
+    }
 }
 
-#[derive(Deserialize)]
-pub struct LeaveChatRoomPayload {
-    pub user_id: i32,
-    pub room_id: i32,
+pub async fn update_chat_room(
+    Extension(state): Extension<Arc<AppState>>,
+    user: AuthUser,
+    Path(room_id): Path<i32>,
+    Json(payload): Json<UpdateChatRoomRequest>,
+) -> impl IntoResponse {
+    let service = ChatRoomService::new(state.store.clone());
+    match service.update_chat_room(user.user_id, room_id, payload).await {
+        Ok(details) => room_updated(room_id, state, details).await,
+        Err(e) => (room_error_status(&e), Json(ErrorResponse { error: e })).into_response(),
+    }
 }
 
-pub async fn create_chat_room(
-    Json(payload): Json<CreateChatRoomPayload>,
+pub async fn archive_chat_room(
+    Extension(state): Extension<Arc<AppState>>,
+    user: AuthUser,
+    Path(room_id): Path<i32>,
+) -> impl IntoResponse {
+    let service = ChatRoomService::new(state.store.clone());
+    match service.set_archived(user.user_id, room_id, true).await {
+        Ok(details) => room_updated(room_id, state, details).await,
+        Err(e) => (room_error_status(&e), Json(ErrorResponse { error: e })).into_response(),
+    }
+}
+
+pub async fn unarchive_chat_room(
+    Extension(state): Extension<Arc<AppState>>,
+    user: AuthUser,
+    Path(room_id): Path<i32>,
+) -> impl IntoResponse {
+    let service = ChatRoomService::new(state.store.clone());
+    match service.set_archived(user.user_id, room_id, false).await {
+        Ok(details) => room_updated(room_id, state, details).await,
+        Err(e) => (room_error_status(&e), Json(ErrorResponse { error: e })).into_response(),
+    }
+}
+
+pub async fn delete_chat_room(
+    Extension(state): Extension<Arc<AppState>>,
+    user: AuthUser,
+    Path(room_id): Path<i32>,
+) -> impl IntoResponse {
+    let service = ChatRoomService::new(state.store.clone());
+    match service.delete_chat_room(user.user_id, room_id).await {
+        Ok(_) => {
+            send_to_channel(room_id, state.clone(), ServerEvent::RoomDeleted).await;
+            close_channel(room_id, state.clone()).await;
+            (StatusCode::OK, Json(MessageResponse { message: String::from("Chat room deleted") })).into_response()
+        }
+        Err(e) => (room_error_status(&e), Json(ErrorResponse { error: e })).into_response(),
+    }
+}
+
+/// Tell the room's sockets about its new details and return them to the caller
+async fn room_updated(room_id: i32, state: Arc<AppState>, details: ChatRoomDetails) -> axum::response::Response {
+    send_to_channel(room_id, state, ServerEvent::RoomUpdated(details.clone())).await;
+    (StatusCode::OK, Json(details)).into_response()
+}
+
+/// Map the errors of the room management operations of `ChatRoomService` to a status code
+fn room_error_status(error: &str) -> StatusCode {
+    match error {
+        "Chat room not found" => StatusCode::NOT_FOUND,
+        "Not a member of this chat room" | "Banned from this chat room" | "Only the owner can change the chat room" => {
+            StatusCode::FORBIDDEN
+        }
+        "Room name cannot be empty" | "Room name is too long" | "Description is too long" | "Topic is too long" => {
+            StatusCode::BAD_REQUEST
+        }
+        "Chat room name already exists" => StatusCode::CONFLICT,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+pub async fn list_chat_rooms(
+    Extension(state): Extension<Arc<AppState>>,
+    _user: AuthUser,
+    Query(query): Query<RoomListQuery>,
+) -> impl IntoResponse {
+    let service = ChatRoomService::new(state.store.clone());
+    match service.list_rooms(query).await {
+        Ok((rooms, total)) => (StatusCode::OK, Json(RoomListResponse { rooms, total })).into_response(),
+        Err(e) => match e.as_str() {
+            "Search is too long" => (StatusCode::BAD_REQUEST, Json(ErrorResponse { error: e })).into_response(),
+            _ => (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e })).into_response(),
+        },
+    }
+}
+
+pub async fn list_user_rooms(
+    Extension(state): Extension<Arc<AppState>>,
+    user: AuthUser,
 ) -> impl IntoResponse {
-    let service = ChatRoomService::new();
-    match service.create_chat_room(payload.room_name, payload.user_id).await {
-        Ok(room_id) => (StatusCode::CREATED, Json(json!({
-            "message": "Chat room created",
-            "room_id": room_id
-        }))),
-        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({"error": e}))),
+    let service = ChatRoomService::new(state.store.clone());
+    match service.list_user_rooms(user.user_id).await {
+        Ok(rooms) => (StatusCode::OK, Json(RoomListResponse { total: rooms.len() as u32, rooms })).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e })).into_response(),
     }
 }
 
 pub async fn join_chat_room(
-    Json(payload): Json<JoinChatRoomPayload>,
-) -> impl IntoResponse {
-    let service = ChatRoomService::new();
-    match service.join_chat_room(payload.user_id, payload.room_id).await {
-        Ok(room_name) => (StatusCode::OK, Json(json!({
-            "message": "Joined chat room",
-            "room_name": room_name
-        }))),
+    Extension(state): Extension<Arc<AppState>>,
+    user: AuthUser,
+    Json(payload): Json<JoinChatRoomRequest>,
+) -> impl IntoResponse {
+    let service = ChatRoomService::new(state.store.clone());
+    match service.join_chat_room(user.user_id, payload.room_id).await {
+        Ok(room_name) => (StatusCode::OK, Json(JoinChatRoomResponse {
+            message: String::from("Joined chat room"),
+            room_name,
+        })).into_response(),
         Err(e) => match e.as_str() {
-            "Chat room not found" => (StatusCode::NOT_FOUND, Json(json!({"error": e}))),
-            _ => (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({"error": e}))),
+            "Chat room not found" => (StatusCode::NOT_FOUND, Json(ErrorResponse { error: e })).into_response(),
+            "Banned from this chat room" => (StatusCode::FORBIDDEN, Json(ErrorResponse { error: e })).into_response(),
+            _ => (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e })).into_response(),
         },
     }
 }
 
 
 pub async fn leave_chat_room(
-    Json(payload): Json<LeaveChatRoomPayload>,
+    Extension(state): Extension<Arc<AppState>>,
+    user: AuthUser,
+    Json(payload): Json<LeaveChatRoomRequest>,
 ) -> impl IntoResponse {
-    let service = ChatRoomService::new();
-    match service.leave_chat_room(payload.user_id, payload.room_id).await {
-        Ok(_) => (StatusCode::OK, Json(json!({"message": "Left chat room"}))),
-        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({"error": e}))),
+    let service = ChatRoomService::new(state.store.clone());
+    match service.leave_chat_room(user.user_id, payload.room_id).await {
+        Ok(_) => (StatusCode::OK, Json(MessageResponse { message: String::from("Left chat room") })).into_response(),
+        Err(e) => match e.as_str() {
+            "Transfer the chat room before leaving it" => (StatusCode::CONFLICT, Json(ErrorResponse { error: e })).into_response(),
+            _ => (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e })).into_response(),
+        },
+    }
+}
+
+pub async fn fetch_chat_history(
+    Extension(state): Extension<Arc<AppState>>,
+    user: AuthUser,
+    Path(room_id): Path<i32>,
+    Query(query): Query<MessageHistoryQuery>,
+) -> impl IntoResponse {
+    let service = ChatRoomService::new(state.store.clone());
+    if let Err(e) = service.check_permission(user.user_id, room_id, Permission::View).await {
+        let status = match e.as_str() {
+            "Chat room not found" => StatusCode::NOT_FOUND,
+            "Not a member of this chat room" | "Banned from this chat room" => StatusCode::FORBIDDEN,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        return (status, Json(ErrorResponse { error: e })).into_response();
+    }
+
+    let limit = query.limit.unwrap_or(state.config.history_page_size);
+    match service.fetch_history(room_id, query.before, limit).await {
+        Ok((messages, next_before)) => (StatusCode::OK, Json(MessageHistoryResponse {
+            messages,
+            next_before,
+        })).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e })).into_response(),
+    }
+}
+
+pub async fn open_direct_chat(
+    Extension(state): Extension<Arc<AppState>>,
+    user: AuthUser,
+    Json(payload): Json<OpenDirectChatRequest>,
+) -> impl IntoResponse {
+    let service = ChatRoomService::new(state.store.clone());
+    match service.open_direct_chat(user.user_id, payload.user_id).await {
+        Ok((room_id, username)) => (StatusCode::OK, Json(DirectChat {
+            room_id,
+            user_id: payload.user_id,
+            username,
+        })).into_response(),
+        Err(e) => match e.as_str() {
+            "User not found" => (StatusCode::NOT_FOUND, Json(ErrorResponse { error: e })).into_response(),
+            "Cannot start a direct conversation with yourself" => {
+                (StatusCode::BAD_REQUEST, Json(ErrorResponse { error: e })).into_response()
+            }
+            _ => (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e })).into_response(),
+        },
+    }
+}
+
+pub async fn list_direct_chats(
+    Extension(state): Extension<Arc<AppState>>,
+    user: AuthUser,
+) -> impl IntoResponse {
+    let service = ChatRoomService::new(state.store.clone());
+    match service.list_direct_chats(user.user_id).await {
+        Ok(chats) => (StatusCode::OK, Json(DirectChatListResponse {
+            chats: chats
+                .into_iter()
+                .map(|(room_id, user_id, username)| DirectChat { room_id, user_id, username })
+                .collect(),
+        })).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e })).into_response(),
+    }
+}
+
+pub async fn create_invite(
+    Extension(state): Extension<Arc<AppState>>,
+    user: AuthUser,
+    Path(room_id): Path<i32>,
+    Json(payload): Json<CreateInviteRequest>,
+) -> impl IntoResponse {
+    let service = ChatRoomService::new(state.store.clone());
+    match service.create_invite(user.user_id, room_id, payload).await {
+        Ok(invite) => (StatusCode::CREATED, Json(invite)).into_response(),
+        Err(e) => (invite_error_status(&e), Json(ErrorResponse { error: e })).into_response(),
+    }
+}
+
+pub async fn list_invites(
+    Extension(state): Extension<Arc<AppState>>,
+    user: AuthUser,
+    Path(room_id): Path<i32>,
+) -> impl IntoResponse {
+    let service = ChatRoomService::new(state.store.clone());
+    match service.list_invites(user.user_id, room_id).await {
+        Ok(invites) => (StatusCode::OK, Json(InviteListResponse { invites })).into_response(),
+        Err(e) => (invite_error_status(&e), Json(ErrorResponse { error: e })).into_response(),
+    }
+}
+
+pub async fn revoke_invite(
+    Extension(state): Extension<Arc<AppState>>,
+    user: AuthUser,
+    Path((room_id, invite_id)): Path<(i32, i32)>,
+) -> impl IntoResponse {
+    let service = ChatRoomService::new(state.store.clone());
+    match service.revoke_invite(user.user_id, room_id, invite_id).await {
+        Ok(_) => (StatusCode::OK, Json(MessageResponse { message: String::from("Invite revoked") })).into_response(),
+        Err(e) => (invite_error_status(&e), Json(ErrorResponse { error: e })).into_response(),
+    }
+}
+
+pub async fn accept_invite(
+    Extension(state): Extension<Arc<AppState>>,
+    user: AuthUser,
+    Path(token): Path<String>,
+) -> impl IntoResponse {
+    let service = ChatRoomService::new(state.store.clone());
+    match service.accept_invite(user.user_id, &token).await {
+        Ok((room_id, room_name)) => (StatusCode::OK, Json(AcceptInviteResponse { room_id, room_name })).into_response(),
+        Err(e) => (invite_error_status(&e), Json(ErrorResponse { error: e })).into_response(),
+    }
+}
+
+/// Map the errors of the invite operations of `ChatRoomService` to a status code
+fn invite_error_status(error: &str) -> StatusCode {
+    match error {
+        "Chat room not found" | "User not found" | "Invite not found" | "Invite not found or expired" => {
+            StatusCode::NOT_FOUND
+        }
+        "Not allowed to manage invites" | "Not a member of this chat room" | "Banned from this chat room" => {
+            StatusCode::FORBIDDEN
+        }
+        "Invalid invite" | "Direct conversations have no invites" => StatusCode::BAD_REQUEST,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+pub async fn list_members(
+    Extension(state): Extension<Arc<AppState>>,
+    user: AuthUser,
+    Path(room_id): Path<i32>,
+) -> impl IntoResponse {
+    let service = ChatRoomService::new(state.store.clone());
+    match service.list_members(user.user_id, room_id).await {
+        Ok(members) => (StatusCode::OK, Json(MemberListResponse {
+            members: members
+                .into_iter()
+                .map(|(user_id, username, role, last_read_message_id)| RoomMember {
+                    user_id,
+                    username,
+                    role,
+                    last_read_message_id,
+                })
+                .collect(),
+        })).into_response(),
+        Err(e) => (role_error_status(&e), Json(ErrorResponse { error: e })).into_response(),
+    }
+}
+
+pub async fn set_member_role(
+    Extension(state): Extension<Arc<AppState>>,
+    user: AuthUser,
+    Path((room_id, member_id)): Path<(i32, i32)>,
+    Json(payload): Json<SetRoleRequest>,
+) -> impl IntoResponse {
+    let service = ChatRoomService::new(state.store.clone());
+    match service.set_member_role(user.user_id, room_id, member_id, payload.role).await {
+        Ok(_) => {
+            send_to_channel(room_id, state.clone(), ServerEvent::RoleChanged { user_id: member_id, role: payload.role }).await;
+            (StatusCode::OK, Json(MessageResponse { message: String::from("Role changed") })).into_response()
+        }
+        Err(e) => (role_error_status(&e), Json(ErrorResponse { error: e })).into_response(),
+    }
+}
+
+pub async fn transfer_ownership(
+    Extension(state): Extension<Arc<AppState>>,
+    user: AuthUser,
+    Path(room_id): Path<i32>,
+    Json(payload): Json<TransferOwnershipRequest>,
+) -> impl IntoResponse {
+    let service = ChatRoomService::new(state.store.clone());
+    match service.transfer_ownership(user.user_id, room_id, payload.user_id).await {
+        Ok(_) => {
+            send_to_channel(room_id, state.clone(), ServerEvent::RoleChanged { user_id: user.user_id, role: RoomRole::Admin }).await;
+            send_to_channel(room_id, state.clone(), ServerEvent::RoleChanged { user_id: payload.user_id, role: RoomRole::Owner }).await;
+            (StatusCode::OK, Json(MessageResponse { message: String::from("Chat room transferred") })).into_response()
+        }
+        Err(e) => (role_error_status(&e), Json(ErrorResponse { error: e })).into_response(),
+    }
+}
+
+/// Map the errors of the role operations of `ChatRoomService` to a status code
+fn role_error_status(error: &str) -> StatusCode {
+    match error {
+        "Chat room not found" | "User is not a member of this chat room" => StatusCode::NOT_FOUND,
+        "Not a member of this chat room"
+        | "Banned from this chat room"
+        | "Not allowed to change roles"
+        | "Only the owner can transfer the chat room" => StatusCode::FORBIDDEN,
+        "Transfer the chat room to change its owner" | "Cannot change your own role" | "You already own this chat room" => {
+            StatusCode::BAD_REQUEST
+        }
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
     }
 }
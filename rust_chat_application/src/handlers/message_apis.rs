@@ -0,0 +1,117 @@
+use std::sync::Arc;
+
+use axum::{extract::{Json, Path, Query}, http::StatusCode, response::IntoResponse, Extension};
+use chat_protocol::api::{
+    EditMessageRequest, ErrorResponse, MessageEdit, MessageEditsResponse, MessageResponse, MessageSearchQuery,
+    MessageSearchResponse, ThreadResponse,
+};
+use chat_protocol::ws::ServerEvent;
+use crate::handlers::session::AuthUser;
+use crate::handlers::websocket_handler::send_to_channel;
+use crate::services::chat_room_service::{ChatRoomService, Permission};
+use crate::services::message_service::MessageService;
+use crate::AppState;
+
+pub async fn edit_message(
+    Extension(state): Extension<Arc<AppState>>,
+    user: AuthUser,
+    Path(message_id): Path<i32>,
+    Json(payload): Json<EditMessageRequest>,
+) -> impl IntoResponse {
+    let service = MessageService::new(state.store.clone());
+    match service.edit_message(user.user_id, message_id, payload.content.clone()).await {
+        Ok((chat, edited_at)) => {
+            send_to_channel(chat, state.clone(), ServerEvent::MessageEdited {
+                message_id,
+                content: payload.content,
+                edited_at,
+            }).await;
+            (StatusCode::OK, Json(MessageResponse { message: String::from("Message edited") })).into_response()
+        }
+        Err(e) => (error_status(&e), Json(ErrorResponse { error: e })).into_response(),
+    }
+}
+
+pub async fn delete_message(
+    Extension(state): Extension<Arc<AppState>>,
+    user: AuthUser,
+    Path(message_id): Path<i32>,
+) -> impl IntoResponse {
+    let service = MessageService::new(state.store.clone());
+    match service.delete_message(user.user_id, message_id).await {
+        Ok(chat) => {
+            send_to_channel(chat, state.clone(), ServerEvent::MessageDeleted { message_id }).await;
+            (StatusCode::OK, Json(MessageResponse { message: String::from("Message deleted") })).into_response()
+        }
+        Err(e) => (error_status(&e), Json(ErrorResponse { error: e })).into_response(),
+    }
+}
+
+pub async fn fetch_message_edits(
+    Extension(state): Extension<Arc<AppState>>,
+    user: AuthUser,
+    Path(message_id): Path<i32>,
+) -> impl IntoResponse {
+    let service = MessageService::new(state.store.clone());
+    let (chat, edits) = match service.fetch_message_edits(message_id).await {
+        Ok(result) => result,
+        Err(e) => return (error_status(&e), Json(ErrorResponse { error: e })).into_response(),
+    };
+    if let Err(e) = ChatRoomService::new(state.store.clone()).check_permission(user.user_id, chat, Permission::View).await {
+        return (error_status(&e), Json(ErrorResponse { error: e })).into_response();
+    }
+
+    let edits = edits
+        .into_iter()
+        .map(|(previous_content, edited_by, edited_at)| MessageEdit { previous_content, edited_by, edited_at })
+        .collect();
+    (StatusCode::OK, Json(MessageEditsResponse { edits })).into_response()
+}
+
+pub async fn fetch_thread(
+    Extension(state): Extension<Arc<AppState>>,
+    user: AuthUser,
+    Path(message_id): Path<i32>,
+) -> impl IntoResponse {
+    let service = MessageService::new(state.store.clone());
+    let (chat, root, replies) = match service.fetch_thread(message_id).await {
+        Ok(thread) => thread,
+        Err(e) => return (error_status(&e), Json(ErrorResponse { error: e })).into_response(),
+    };
+    if let Err(e) = ChatRoomService::new(state.store.clone()).check_permission(user.user_id, chat, Permission::View).await {
+        return (error_status(&e), Json(ErrorResponse { error: e })).into_response();
+    }
+
+    (StatusCode::OK, Json(ThreadResponse { root, replies })).into_response()
+}
+
+pub async fn search_messages(
+    Extension(state): Extension<Arc<AppState>>,
+    user: AuthUser,
+    Query(query): Query<MessageSearchQuery>,
+) -> impl IntoResponse {
+    let service = MessageService::new(state.store.clone());
+    match service.search_messages(user.user_id, query).await {
+        Ok((results, total)) => (StatusCode::OK, Json(MessageSearchResponse { results, total })).into_response(),
+        Err(e) => (error_status(&e), Json(ErrorResponse { error: e })).into_response(),
+    }
+}
+
+/// Map the errors of `MessageService` and `ChatRoomService::check_permission` to a status code
+fn error_status(error: &str) -> StatusCode {
+    match error {
+        "Message not found" | "Chat room not found" => StatusCode::NOT_FOUND,
+        "Not allowed to change this message"
+        | "Not a member of this chat room"
+        | "Banned from this chat room"
+        | "Not allowed to post in this chat room"
+        | "You are muted in this chat room"
+        | "This chat room is archived" => StatusCode::FORBIDDEN,
+        "Message has been deleted" => StatusCode::GONE,
+        "Message cannot be empty" | "Search is too long" | "The date range ends before it starts" => {
+            StatusCode::BAD_REQUEST
+        }
+        error if error.starts_with("Search for words of at least") => StatusCode::BAD_REQUEST,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
@@ -1,3 +1,7 @@
+pub mod attachment_apis;
 pub mod chat_room_apis;
+pub mod message_apis;
+pub mod moderation_apis;
+pub mod session;
 pub mod user_auth_apis;
 pub mod websocket_handler;
\ No newline at end of file
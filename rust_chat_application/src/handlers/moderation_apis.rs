@@ -0,0 +1,128 @@
+use std::sync::Arc;
+
+use axum::{extract::{Json, Path}, http::StatusCode, response::IntoResponse, Extension};
+use chat_protocol::api::{ErrorResponse, MessageResponse, ModerationLogResponse, ModerationRequest, SlowModeRequest};
+use chat_protocol::ws::ServerEvent;
+use crate::handlers::session::AuthUser;
+use crate::handlers::websocket_handler::send_to_channel;
+use crate::services::moderation_service::ModerationService;
+use crate::AppState;
+
+pub async fn kick_member(
+    Extension(state): Extension<Arc<AppState>>,
+    user: AuthUser,
+    Path(room_id): Path<i32>,
+    Json(payload): Json<ModerationRequest>,
+) -> impl IntoResponse {
+    let service = ModerationService::new(state.store.clone());
+    match service.kick(user.user_id, room_id, &payload).await {
+        Ok(_) => {
+            send_to_channel(room_id, state.clone(), ServerEvent::Kicked { user_id: payload.user_id, reason: payload.reason }).await;
+            (StatusCode::OK, Json(MessageResponse { message: String::from("User kicked") })).into_response()
+        }
+        Err(e) => (moderation_error_status(&e), Json(ErrorResponse { error: e })).into_response(),
+    }
+}
+
+pub async fn ban_user(
+    Extension(state): Extension<Arc<AppState>>,
+    user: AuthUser,
+    Path(room_id): Path<i32>,
+    Json(payload): Json<ModerationRequest>,
+) -> impl IntoResponse {
+    let service = ModerationService::new(state.store.clone());
+    match service.ban(user.user_id, room_id, &payload).await {
+        Ok(_) => {
+            send_to_channel(room_id, state.clone(), ServerEvent::Banned { user_id: payload.user_id, reason: payload.reason }).await;
+            (StatusCode::OK, Json(MessageResponse { message: String::from("User banned") })).into_response()
+        }
+        Err(e) => (moderation_error_status(&e), Json(ErrorResponse { error: e })).into_response(),
+    }
+}
+
+pub async fn unban_user(
+    Extension(state): Extension<Arc<AppState>>,
+    user: AuthUser,
+    Path((room_id, user_id)): Path<(i32, i32)>,
+) -> impl IntoResponse {
+    let service = ModerationService::new(state.store.clone());
+    match service.unban(user.user_id, room_id, user_id).await {
+        Ok(_) => (StatusCode::OK, Json(MessageResponse { message: String::from("User unbanned") })).into_response(),
+        Err(e) => (moderation_error_status(&e), Json(ErrorResponse { error: e })).into_response(),
+    }
+}
+
+pub async fn mute_member(
+    Extension(state): Extension<Arc<AppState>>,
+    user: AuthUser,
+    Path(room_id): Path<i32>,
+    Json(payload): Json<ModerationRequest>,
+) -> impl IntoResponse {
+    let service = ModerationService::new(state.store.clone());
+    match service.mute(user.user_id, room_id, &payload).await {
+        Ok(until) => {
+            send_to_channel(room_id, state.clone(), ServerEvent::Muted { user_id: payload.user_id, until }).await;
+            (StatusCode::OK, Json(MessageResponse { message: String::from("User muted") })).into_response()
+        }
+        Err(e) => (moderation_error_status(&e), Json(ErrorResponse { error: e })).into_response(),
+    }
+}
+
+pub async fn unmute_member(
+    Extension(state): Extension<Arc<AppState>>,
+    user: AuthUser,
+    Path((room_id, user_id)): Path<(i32, i32)>,
+) -> impl IntoResponse {
+    let service = ModerationService::new(state.store.clone());
+    match service.unmute(user.user_id, room_id, user_id).await {
+        Ok(_) => {
+            send_to_channel(room_id, state.clone(), ServerEvent::Unmuted { user_id }).await;
+            (StatusCode::OK, Json(MessageResponse { message: String::from("User unmuted") })).into_response()
+        }
+        Err(e) => (moderation_error_status(&e), Json(ErrorResponse { error: e })).into_response(),
+    }
+}
+
+pub async fn set_slow_mode(
+    Extension(state): Extension<Arc<AppState>>,
+    user: AuthUser,
+    Path(room_id): Path<i32>,
+    Json(payload): Json<SlowModeRequest>,
+) -> impl IntoResponse {
+    let service = ModerationService::new(state.store.clone());
+    match service.set_slow_mode(user.user_id, room_id, payload.interval).await {
+        Ok(_) => {
+            send_to_channel(room_id, state.clone(), ServerEvent::SlowModeChanged { interval: payload.interval }).await;
+            (StatusCode::OK, Json(MessageResponse { message: String::from("Slow mode changed") })).into_response()
+        }
+        Err(e) => (moderation_error_status(&e), Json(ErrorResponse { error: e })).into_response(),
+    }
+}
+
+pub async fn fetch_moderation_log(
+    Extension(state): Extension<Arc<AppState>>,
+    user: AuthUser,
+    Path(room_id): Path<i32>,
+) -> impl IntoResponse {
+    let service = ModerationService::new(state.store.clone());
+    match service.fetch_log(user.user_id, room_id).await {
+        Ok(entries) => (StatusCode::OK, Json(ModerationLogResponse { entries })).into_response(),
+        Err(e) => (moderation_error_status(&e), Json(ErrorResponse { error: e })).into_response(),
+    }
+}
+
+/// Map the errors of `ModerationService` to a status code
+fn moderation_error_status(error: &str) -> StatusCode {
+    match error {
+        "Chat room not found" | "User not found" | "User is not a member of this chat room" | "User is not banned"
+        | "User is not muted" => StatusCode::NOT_FOUND,
+        "Not a member of this chat room"
+        | "Banned from this chat room"
+        | "Not allowed to moderate this chat room"
+        | "Not allowed to moderate this user" => StatusCode::FORBIDDEN,
+        "Reason is too long" | "Invalid duration" | "Invalid interval" | "Cannot moderate yourself" => {
+            StatusCode::BAD_REQUEST
+        }
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
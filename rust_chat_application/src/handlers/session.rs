@@ -0,0 +1,62 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{FromRequestParts, Json, Query},
+    http::{header::AUTHORIZATION, request::Parts, StatusCode},
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::services::user_auth_service::UserAuthService;
+use crate::AppState;
+
+/// The user behind the session token of the current request.
+///
+/// The token is read from an `Authorization: Bearer <token>` header, or from a `token`
+/// query parameter since browsers cannot set headers on WebSocket upgrades.
+#[derive(Clone, Debug)]
+pub struct AuthUser {
+    pub user_id: i32,
+    pub username: String,
+    pub token: String,
+}
+
+#[derive(Deserialize)]
+struct TokenQuery {
+    token: String,
+}
+
+impl<S> FromRequestParts<S> for AuthUser
+where
+    S: Send + Sync,
+{
+    type Rejection = (StatusCode, Json<Value>);
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let token = bearer_token(parts)
+            .or_else(|| Query::<TokenQuery>::try_from_uri(&parts.uri).ok().map(|q| q.0.token))
+            .ok_or_else(|| unauthorized("Missing session token"))?;
+
+        let state = parts
+            .extensions
+            .get::<Arc<AppState>>()
+            .cloned()
+            .ok_or_else(|| (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({"error": "Missing app state"}))))?;
+
+        let service = UserAuthService::new(state.store.clone());
+        match service.authenticate(&token).await {
+            Ok(Some((user_id, username))) => Ok(AuthUser { user_id, username, token }),
+            Ok(None) => Err(unauthorized("Invalid or expired session token")),
+            Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, Json(json!({"error": e})))),
+        }
+    }
+}
+
+fn bearer_token(parts: &Parts) -> Option<String> {
+    let value = parts.headers.get(AUTHORIZATION)?.to_str().ok()?;
+    value.strip_prefix("Bearer ").map(|token| token.trim().to_string())
+}
+
+fn unauthorized(message: &str) -> (StatusCode, Json<Value>) {
+    (StatusCode::UNAUTHORIZED, Json(json!({"error": message})))
+}
@@ -1,50 +1,27 @@
-use axum::{extract::Json, response::IntoResponse};
-use serde::Deserialize;
-use lazy_static::lazy_static;
-use serde_json::json;
-use tokio::sync::Mutex;
+use std::sync::Arc;
+
+use axum::{extract::Json, http::StatusCode, response::IntoResponse, Extension};
+use chat_protocol::api::{
+    ErrorResponse, FetchStatusRequest, FetchStatusResponse, LoginRequest, LoginResponse,
+    MessageResponse, SignupRequest,
+};
 use chrono::Utc;
+use crate::handlers::session::AuthUser;
+use crate::services::chat_room_service::{ChatRoomService, Permission};
 use crate::services::user_auth_service::UserAuthService;
+use crate::AppState;
 
 
-#[derive(Deserialize)]
-pub struct SignupPayload {
-    pub username: String,
-    pub email: String,
-    pub password: String,
-}
-
-
-#[derive(Deserialize)]
-pub struct LoginPayload {
-    email: String,
-    password: String,
-}
-
-#[derive(Deserialize)]
-pub struct LogoutPayload {
-    user_id: i32,
-}
-
-#[derive(Deserialize)]
-pub struct FetchOnlineStatusQuery {
-    room_id: i32,
-}
-
-lazy_static! {
-    static ref USERSERVICE: Mutex<UserAuthService> = Mutex::new(UserAuthService::new());
-}
-
-
-use axum::http::StatusCode;
-
-pub async fn user_signup(Json(payload): Json<SignupPayload>) -> impl IntoResponse {
-    let service = USERSERVICE.lock().await;
+pub async fn user_signup(
+    Extension(state): Extension<Arc<AppState>>,
+    Json(payload): Json<SignupRequest>,
+) -> impl IntoResponse {
+    let service = UserAuthService::new(state.store.clone());
     match service.user_check_exist(payload.email.clone()).await {
         Err(_e) => (
             StatusCode::BAD_REQUEST,
-            Json(json!({"error": "this email has existed"})),
-        ),
+            Json(ErrorResponse { error: String::from("this email has existed") }),
+        ).into_response(),
         Ok(_) => {
             match service
                 .user_sign_up(
@@ -57,85 +34,98 @@ pub async fn user_signup(Json(payload): Json<SignupPayload>) -> impl IntoRespons
             {
                 Ok(_) => (
                     StatusCode::OK,
-                    Json(json!({"message": "User signed up successfully"})),
-                ),
+                    Json(MessageResponse { message: String::from("User signed up successfully") }),
+                ).into_response(),
                 Err(e) => (
                     StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(json!({"error": e})),
-                ),
+                    Json(ErrorResponse { error: e }),
+                ).into_response(),
             }
         }
     }
 }
 
 
-pub async fn user_login(Json(payload): Json<LoginPayload>) -> impl IntoResponse {
-    let service = USERSERVICE.lock().await;
+pub async fn user_login(
+    Extension(state): Extension<Arc<AppState>>,
+    Json(payload): Json<LoginRequest>,
+) -> impl IntoResponse {
+    let service = UserAuthService::new(state.store.clone());
 
     // call user_query() to fetch username and id
     match service.user_query(payload.email, payload.password).await {
-        Ok(Some((uid, username))) => (
-            StatusCode::OK,
-            Json(json!({
-                "message": "User logged in successfully",
-                "uid": uid,
-                "username": username
-            })),
-        ),
+        Ok(Some((uid, username))) => match service.create_session(uid).await {
+            Ok((token, expires_in)) => (
+                StatusCode::OK,
+                Json(LoginResponse {
+                    message: String::from("User logged in successfully"),
+                    uid,
+                    username,
+                    token,
+                    expires_in,
+                }),
+            ).into_response(),
+            Err(e) => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse { error: e }),
+            ).into_response(),
+        },
         Ok(None) => (
             StatusCode::UNAUTHORIZED,
-            Json(json!({"error": "Invalid credentials"})),
-        ),
+            Json(ErrorResponse { error: String::from("Invalid credentials") }),
+        ).into_response(),
         Err(e) => (
             StatusCode::INTERNAL_SERVER_ERROR,
-            Json(json!({"error": e})),
-        ),
+            Json(ErrorResponse { error: e }),
+        ).into_response(),
     }
 }
 
-pub async fn user_logout(Json(payload): Json<LogoutPayload>) -> impl IntoResponse {
-    let service = USERSERVICE.lock().await;
-    match service.user_logout(payload.user_id).await {
+pub async fn user_logout(
+    Extension(state): Extension<Arc<AppState>>,
+    user: AuthUser,
+) -> impl IntoResponse {
+    let service = UserAuthService::new(state.store.clone());
+    match service.user_logout(&user.token).await {
         Ok(_) => (
             StatusCode::OK,
-            Json(json!({"message": "User logged out successfully"})),
-        ),
+            Json(MessageResponse { message: String::from("User logged out successfully") }),
+        ).into_response(),
         Err(e) => (
             StatusCode::INTERNAL_SERVER_ERROR,
-            Json(json!({"error": e})),
-        ),
+            Json(ErrorResponse { error: e }),
+        ).into_response(),
     }
 }
 
-pub async fn fetch_user_status(Json(params): Json<FetchOnlineStatusQuery>) -> impl IntoResponse {
-    let service = USERSERVICE.lock().await;
+pub async fn fetch_user_status(
+    Extension(state): Extension<Arc<AppState>>,
+    user: AuthUser,
+    Json(params): Json<FetchStatusRequest>,
+) -> impl IntoResponse {
+    // Only members may see who else is in a room
+    let rooms = ChatRoomService::new(state.store.clone());
+    if let Err(e) = rooms.check_permission(user.user_id, params.room_id, Permission::View).await {
+        let status = match e.as_str() {
+            "Chat room not found" => StatusCode::NOT_FOUND,
+            "Not a member of this chat room" | "Banned from this chat room" => StatusCode::FORBIDDEN,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        return (status, Json(ErrorResponse { error: e })).into_response();
+    }
+
+    let service = UserAuthService::new(state.store.clone());
 
     // Fetch the list of users in the specified room
     match service.fetch_user_list(params.room_id).await {
         Ok(user_list) => {
-            // Fetch the status of the users
-            match service.fetch_user_status(user_list).await {
-                Ok(online_users) => {
-                    // Map the online users to a response-friendly format
-                    let online_users_json = online_users.into_iter().map(|(user_id, status)| {
-                        json!({
-                            "user_id": user_id,
-                            "status": status,
-                        })
-                    }).collect::<Vec<_>>();
-
-                    // Return the response with the list of online users
-                    (StatusCode::OK, Json(json!({ "online_users": online_users_json })))
-                },
-                Err(e) => {
-                    // If fetching user statuses fails, return an error
-                    (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({ "error": e })))
-                },
-            }
+            // Their status comes from the connections they have open right now
+            let online_users = state.presence.statuses(&user_list);
+            (StatusCode::OK, Json(FetchStatusResponse { online_users })).into_response()
         },
         Err(e) => {
             // If fetching the user list fails, return an error
-            (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({ "error": e })))
+            (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e })).into_response()
         },
     }
 }
@@ -1,19 +1,39 @@
 use std::sync::Arc;
 use std::net::SocketAddr;
+use std::time::Duration;
 
 use axum::{
-    extract::{ws::{Message, WebSocket, WebSocketUpgrade}, Path, Query}, response::IntoResponse, Extension
+    extract::{ws::{Message, WebSocket, WebSocketUpgrade}, Path, Query}, http::StatusCode, response::IntoResponse, Extension, Json
 };
+use serde::Deserialize;
 use axum_extra::TypedHeader;
-use tokio::sync::broadcast;
-use mysql_async::{Pool, prelude::*, Row};
+use tokio::sync::{broadcast, broadcast::error::RecvError, mpsc};
+use tokio::time::{sleep_until, Instant};
 //allows to extract the IP of connecting user
 use axum::extract::connect_info::ConnectInfo;
 //allows to split the websocket stream into separate TX and RX branches
-use futures::{sink::SinkExt, stream::StreamExt};
-use chrono::{DateTime, Utc}; // Added DateTime and Utc
+use futures::{sink::SinkExt, stream::{SplitSink, StreamExt}};
+use chat_protocol::{api::ErrorResponse, ws::{ClientEvent, PresenceStatus, ServerEvent, UserStatus}};
 
-use crate::{AppState, ChatMessage, WsQuery};
+use crate::handlers::session::AuthUser;
+use crate::services::chat_room_service::{ChatRoomService, Permission};
+use crate::services::message_service::MessageService;
+use crate::services::typing::TypingIndicator;
+use crate::AppState;
+
+/// How long a connection may stay silent before it is taken as gone and closed. Clients send
+/// a heartbeat well within it.
+const HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(90);
+/// How long a connection may go without user activity before it counts as idle
+const AWAY_AFTER: Duration = Duration::from_secs(5 * 60);
+
+/// Query parameters of `/ws/{chat}` besides the session token
+#[derive(Deserialize)]
+pub struct ConnectParams {
+    /// Set when resuming after a dropped connection: the last `message_id` the client saw.
+    /// Every later message is replayed instead of sending the newest page of history.
+    after: Option<i32>,
+}
 
 /// The handler for the HTTP request (this gets called when the HTTP request lands at the start
 /// of websocket negotiation). After this completes, the actual switching from HTTP to
@@ -23,7 +43,8 @@ use crate::{AppState, ChatMessage, WsQuery};
 pub async fn ws_handler(
     ws: WebSocketUpgrade,
     Path(chat): Path<i32>,
-    Query(query): Query<WsQuery>,
+    Query(params): Query<ConnectParams>,
+    user: AuthUser,
     user_agent: Option<TypedHeader<headers::UserAgent>>,
     ConnectInfo(addr): ConnectInfo<SocketAddr>,
     Extension(state): Extension<Arc<AppState>>,
@@ -37,70 +58,159 @@ pub async fn ws_handler(
     };
     tracing::info!("`{user_agent}` at {addr} connected.");
 
-    
+    // Refuse to upgrade for rooms that do not exist or that the user may not see
+    if let Err(e) = ChatRoomService::new(state.store.clone()).check_access(user.user_id, chat).await {
+        let status = match e.as_str() {
+            "Chat room not found" => StatusCode::NOT_FOUND,
+            "Banned from this chat room" => StatusCode::FORBIDDEN,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        return (status, Json(ErrorResponse { error: e })).into_response();
+    }
+
     // finalize the upgrade process by returning upgrade callback.
     // we can customize the callback by sending additional info such as address.
-    ws.on_upgrade(move |socket| handle_socket(socket, addr, chat, state, query.user_id, query.username.clone()))
+    ws.on_upgrade(move |socket| handle_socket(socket, addr, chat, state, user.user_id, user.username, params.after))
+        .into_response()
 }
 
 /// Actual websocket statemachine (one will be spawned per connection)
-async fn handle_socket(socket: WebSocket, who: SocketAddr, chat: i32,state: Arc<AppState>, user_id: i32, username: String) {
+async fn handle_socket(
+    socket: WebSocket,
+    who: SocketAddr,
+    chat: i32,
+    state: Arc<AppState>,
+    user_id: i32,
+    username: String,
+    resume_after: Option<i32>,
+) {
     tracing::info!("Websocket context {who} created");
     let (mut sender, mut receiver) = socket.split();
 
-    // Fetch chat history before setting up the broadcast subscription
-    match fetch_chat_history(&state.pool, chat).await {
-        Ok(history) => {
-            // Send chat history as messages
-            for msg in history {
-                if sender.send(Message::Text(serde_json::to_string(&msg).unwrap())).await.is_err() {
-                    tracing::error!("Failed to send chat history to client {who}");
-                    return;
-                }
-            }
+    // Subscribe before reading history, so nothing posted in between is missed. Messages
+    // in both are sent twice and clients drop the copy by `message_id`.
+    let mut rx = {
+        let mut chat_channels = state.chat_channels.lock().await;
+        chat_channels.entry(chat).or_insert_with(|| broadcast::channel(state.config.broadcast_capacity)).0.subscribe()
+    };
+
+    // Connecting to a public room joins it; `ws_handler` already checked the user may open it
+    if let Err(e) = ChatRoomService::new(state.store.clone()).rejoin_chat_room(user_id, chat).await {
+        tracing::error!("Failed to join chat room: {}", e);
+    }
+
+    let history_sent = match resume_after {
+        Some(after) => replay_messages(&mut sender, &state, chat, after).await,
+        None => {
+            let history = history_page(&state, chat, None, None).await;
+            send_event(&mut sender, &history).await
         }
-        Err(e) => {
-            tracing::error!("Failed to fetch chat history: {}", e);
-            let err_msg = ChatMessage {
-                user_id: -1,
-                username: String::from("Server"),
-                content: String::from("Failed to load chat history"),
-                timestamp: chrono::Utc::now(),
-            };
-            if sender.send(Message::Text(serde_json::to_string(&err_msg).unwrap())).await.is_err() {
+    };
+    if history_sent.is_err() {
+        tracing::error!("Failed to send chat history to client {who}");
+        return;
+    }
+
+    // Count this connection before telling the client who is online, so they see themselves
+    let (connection_id, status) = state.presence.connect(user_id);
+    if let Some(status) = status {
+        broadcast_presence(&state, user_id, status).await;
+    }
+
+    // Tell the client who else is in the room
+    match room_presence(&state, chat).await {
+        Ok(users) => {
+            if send_event(&mut sender, &ServerEvent::Presence { users }).await.is_err() {
+                disconnect_presence(&state, user_id, connection_id).await;
                 return;
             }
         }
+        Err(e) => tracing::error!("Failed to fetch room presence: {}", e),
     }
 
-    let mut rx = {
-        let mut chat_channels = state.chat_channels.lock().await;
-        chat_channels.entry(chat).or_insert_with(|| broadcast::channel(16)).0.subscribe()
-    };
+    // Events meant for this client only (acks and errors) bypass the room channel
+    let (direct_tx, mut direct_rx) = mpsc::unbounded_channel::<ServerEvent>();
 
-    // Spawn a task that will push several messages to the client (does not matter what client does)
+    // Spawn a task that forwards room events and direct replies to the client
     let mut send_task = tokio::spawn(async move {
         let mut cnt = 0;
         loop {
-            let msg = rx.recv().await.unwrap();
+            // Direct replies go first, so a sender sees its ack before its own broadcast message
+            let event = tokio::select! {
+                biased;
+                event = direct_rx.recv() => match event {
+                    Some(event) => event,
+                    None => break,
+                },
+                event = rx.recv() => match event {
+                    Ok(event) => event,
+                    Err(RecvError::Lagged(skipped)) => {
+                        tracing::warn!("Client {who} lagged behind and missed {skipped} events");
+                        continue;
+                    }
+                    Err(RecvError::Closed) => break,
+                },
+            };
             cnt += 1;
-            if sender.send(Message::Text(serde_json::to_string(&msg).unwrap())).await.is_err() {
+            if send_event(&mut sender, &event).await.is_err() {
                 tracing::info!("client {who} abruptly disconnected because we could not send message to it");
                 break;
             }
+            // A kicked or banned user keeps nothing open in the room
+            if let ServerEvent::Kicked { user_id: target, .. } | ServerEvent::Banned { user_id: target, .. } = &event {
+                if *target == user_id {
+                    tracing::info!("Closing websocket of {who} (user_id: {user_id}), removed from the room");
+                    break;
+                }
+            }
+            if let ServerEvent::RoomDeleted = event {
+                tracing::info!("Closing websocket of {who}, the room was deleted");
+                break;
+            }
         }
         cnt
     });
 
-    // This second task will receive messages from client and print them on server console
+    // This second task will receive events from the client and act on them
     let state_clone = state.clone();
+    let sender_name = username.clone();
     let mut recv_task = tokio::spawn(async move {
         tracing::info!("Receive task created for {who} (user_id: {user_id})");
         let state = state_clone;
         let mut cnt = 0;
+        let mut typing = TypingIndicator::default();
+        // When the client last sent anything, and when the user last did something
+        let mut last_frame = Instant::now();
+        let mut last_active = Instant::now();
+        let mut idle = false;
         loop {
-            let msg = receiver.next().await.unwrap();
-            tracing::info!("Received message from {who}");
+            let msg = tokio::select! {
+                msg = receiver.next() => match msg {
+                    Some(msg) => msg,
+                    None => break,
+                },
+                _ = sleep_until(last_frame + HEARTBEAT_TIMEOUT) => {
+                    tracing::info!("Client {who} stopped sending heartbeats");
+                    break;
+                }
+                _ = sleep_until(last_active + AWAY_AFTER), if !idle => {
+                    idle = true;
+                    if let Some(status) = state.presence.set_idle(user_id, connection_id, true) {
+                        broadcast_presence(&state, user_id, status).await;
+                    }
+                    continue;
+                }
+                _ = sleep_until(typing.expires_at().unwrap_or_else(Instant::now)), if typing.expires_at().is_some() => {
+                    if typing.expire(Instant::now()) {
+                        send_to_channel(chat, state.clone(), ServerEvent::Typing {
+                            user_id,
+                            username: sender_name.clone(),
+                            is_typing: false,
+                        }).await;
+                    }
+                    continue;
+                }
+            };
             match msg {
                 Ok(Message::Close(_)) => {
                     tracing::info!("Client {who} sent close message");
@@ -109,40 +219,135 @@ async fn handle_socket(socket: WebSocket, who: SocketAddr, chat: i32,state: Arc<
                 Ok(Message::Text(msg)) => {
                     tracing::info!("Received message from {who}: {msg}");
                     cnt += 1;
-                    // Deserialize the message and send it to the chat channel
-                    let msg: ChatMessage = serde_json::from_str(&msg).unwrap();
-
-                    // Add it to db
-                    let mut conn = state.pool.get_conn().await.unwrap();
-                    match conn.exec_drop(
-                        r"INSERT INTO Messages (chatroom_id, sender_id, message_text, sent_at) VALUES (:chatroom_id, :sender_id, :message_text, :sent_at)",
-                        params! {
-                            "chatroom_id" => chat,
-                            "sender_id" => msg.user_id,
-                            "message_text" => msg.content.clone(),
-                            "sent_at" => msg.timestamp.to_rfc3339(),
-                        },
-                    ).await{
-                        Ok(_) => {
-                            // Send to the channel
-                            send_to_channel(chat, state.clone(), msg).await;
-                            
-                        },
+                    let event = match serde_json::from_str::<ClientEvent>(&msg) {
+                        Ok(event) => event,
                         Err(e) => {
-                            tracing::error!("Could not insert message into db due to {e}");
-                            let err_msg = ChatMessage {
-                                user_id: -1,
-                                username: String::from("Server"),
-                                content: format!("New Message: {msg}. Could not insert message into db due to {e}"),
-                                timestamp: chrono::Utc::now(),
-                                // addr: who,
-                            };
-                            send_to_channel(chat, state.clone(), err_msg).await;
-                            break;
+                            let _ = direct_tx.send(ServerEvent::Error {
+                                message: format!("Invalid event: {e}"),
+                                nonce: None,
+                            });
+                            continue;
+                        }
+                    };
+
+                    // Anything but a heartbeat from an untouched page is the user being there
+                    last_frame = Instant::now();
+                    if !matches!(event, ClientEvent::Heartbeat { active: false }) {
+                        last_active = last_frame;
+                        if idle {
+                            idle = false;
+                            if let Some(status) = state.presence.set_idle(user_id, connection_id, false) {
+                                broadcast_presence(&state, user_id, status).await;
+                            }
                         }
                     }
 
-                    
+                    match event {
+                        ClientEvent::Message { content, nonce, reply_to, attachments } => {
+                            // The sender identity and timestamp always come from the server, never the client
+                            let service = MessageService::new(state.store.clone());
+                            match service.post_message(chat, user_id, sender_name.clone(), content, reply_to, attachments).await {
+                                Ok(msg) => {
+                                    // Ack first, so the sender can match the broadcast to its pending message
+                                    let _ = direct_tx.send(ServerEvent::Ack { nonce, message_id: msg.message_id });
+                                    send_to_channel(chat, state.clone(), ServerEvent::Message(msg)).await;
+                                },
+                                Err(e) => {
+                                    tracing::error!("Could not insert message into db due to {e}");
+                                    let _ = direct_tx.send(ServerEvent::Error {
+                                        message: format!("Could not store message: {e}"),
+                                        nonce: Some(nonce),
+                                    });
+                                }
+                            }
+                        }
+                        ClientEvent::Typing { is_typing } => {
+                            // Read-only members cannot post, so they are never typing
+                            let service = ChatRoomService::new(state.store.clone());
+                            if service.check_permission(user_id, chat, Permission::Post).await.is_err() {
+                                continue;
+                            }
+                            // Typing events are not stored; the room hears about changes
+                            if typing.update(is_typing, Instant::now()) {
+                                send_to_channel(chat, state.clone(), ServerEvent::Typing {
+                                    user_id,
+                                    username: sender_name.clone(),
+                                    is_typing,
+                                }).await;
+                            }
+                        }
+                        ClientEvent::Edit { message_id, content } => {
+                            let service = MessageService::new(state.store.clone());
+                            match service.edit_message(user_id, message_id, content.clone()).await {
+                                Ok((room, edited_at)) => {
+                                    send_to_channel(room, state.clone(), ServerEvent::MessageEdited {
+                                        message_id,
+                                        content,
+                                        edited_at,
+                                    }).await;
+                                }
+                                Err(e) => {
+                                    let _ = direct_tx.send(ServerEvent::Error { message: e, nonce: None });
+                                }
+                            }
+                        }
+                        ClientEvent::Delete { message_id } => {
+                            let service = MessageService::new(state.store.clone());
+                            match service.delete_message(user_id, message_id).await {
+                                Ok(room) => {
+                                    send_to_channel(room, state.clone(), ServerEvent::MessageDeleted { message_id }).await;
+                                }
+                                Err(e) => {
+                                    let _ = direct_tx.send(ServerEvent::Error { message: e, nonce: None });
+                                }
+                            }
+                        }
+                        ClientEvent::AddReaction { message_id, emoji } => {
+                            let service = MessageService::new(state.store.clone());
+                            match service.add_reaction(chat, user_id, message_id, &emoji).await {
+                                Ok(true) => {
+                                    send_to_channel(chat, state.clone(), ServerEvent::ReactionAdded { message_id, user_id, emoji }).await;
+                                }
+                                Ok(false) => {}
+                                Err(e) => {
+                                    let _ = direct_tx.send(ServerEvent::Error { message: e, nonce: None });
+                                }
+                            }
+                        }
+                        ClientEvent::RemoveReaction { message_id, emoji } => {
+                            let service = MessageService::new(state.store.clone());
+                            match service.remove_reaction(chat, user_id, message_id, &emoji).await {
+                                Ok(true) => {
+                                    send_to_channel(chat, state.clone(), ServerEvent::ReactionRemoved { message_id, user_id, emoji }).await;
+                                }
+                                Ok(false) => {}
+                                Err(e) => {
+                                    let _ = direct_tx.send(ServerEvent::Error { message: e, nonce: None });
+                                }
+                            }
+                        }
+                        ClientEvent::MarkRead { message_id } => {
+                            let service = MessageService::new(state.store.clone());
+                            match service.mark_read(chat, user_id, message_id).await {
+                                Ok(true) => {
+                                    send_to_channel(chat, state.clone(), ServerEvent::ReadReceipt { user_id, message_id }).await;
+                                }
+                                Ok(false) => {}
+                                Err(e) => {
+                                    let _ = direct_tx.send(ServerEvent::Error { message: e, nonce: None });
+                                }
+                            }
+                        }
+                        ClientEvent::FetchHistory { before, limit } => {
+                            let service = ChatRoomService::new(state.store.clone());
+                            let event = match service.check_permission(user_id, chat, Permission::View).await {
+                                Ok(_) => history_page(&state, chat, before, limit).await,
+                                Err(e) => ServerEvent::Error { message: e, nonce: None },
+                            };
+                            let _ = direct_tx.send(event);
+                        }
+                        ClientEvent::Heartbeat { .. } => {}
+                    }
                 }
                 Err(e) => {
                     tracing::info!("Client {who} abruptly disconnected due to {e}");
@@ -159,16 +364,11 @@ async fn handle_socket(socket: WebSocket, who: SocketAddr, chat: i32,state: Arc<
     });
 
     // Join chat room
-    {
-        let mut chat_channels = state.chat_channels.lock().await;
-        let tx = &chat_channels.get_mut(&chat).unwrap().0;
-        tx.send(ChatMessage {
-            user_id: -1,
-            username: String::from("Server"),
-            content: format!("User {} (user_id: {}) joined the chat room", username, user_id),
-            timestamp: chrono::Utc::now(),
-        }).unwrap();
-    }
+    send_to_channel(chat, state.clone(), ServerEvent::Join {
+        user_id,
+        username: username.clone(),
+        timestamp: chrono::Utc::now(),
+    }).await;
 
     // If any one of the tasks exit, abort the other.
     tokio::select! {
@@ -189,69 +389,108 @@ async fn handle_socket(socket: WebSocket, who: SocketAddr, chat: i32,state: Arc<
     }
 
     // Leave chat room
-    {
-        let mut chat_channels = state.chat_channels.lock().await;
-        let tx = &chat_channels.get_mut(&chat).unwrap().0;
-        tx.send(ChatMessage {
-            user_id: -1,
-            username: String::from("Server"),
-            content: format!("User {} (user_id: {}) left the chat room", username, user_id),
-            timestamp: chrono::Utc::now(),
-        }).unwrap();
-
-        // Call leave chat room api
-        let mut conn = state.pool.get_conn().await.unwrap();
-        conn.exec_drop(
-            r"DELETE FROM UserInChatRoom WHERE user_id = :user_id AND chatroom_id = :chatroom_id",
-            params! {
-                "user_id" => user_id,
-                "chatroom_id" => chat,
-            },
-        ).await
-        .expect("Could not leave chat room");
-    }
-
+    send_to_channel(chat, state.clone(), ServerEvent::Leave {
+        user_id,
+        username,
+        timestamp: chrono::Utc::now(),
+    }).await;
+    disconnect_presence(&state, user_id, connection_id).await;
 
     tracing::info!("Websocket context {who} destroyed (user_id: {})", user_id);
 }
 
-/// Helper function to send a message to a channel (chat)
-async fn send_to_channel(chat: i32, state: Arc<AppState>, msg: ChatMessage) {
-    let mut chat_channels = state.chat_channels.lock().await;
-    let tx = &chat_channels.get_mut(&chat).unwrap().0;
-    tx.send(msg).unwrap();
+/// Helper function to serialize an event and send it to one client
+async fn send_event(sender: &mut SplitSink<WebSocket, Message>, event: &ServerEvent) -> Result<(), axum::Error> {
+    sender.send(Message::Text(serde_json::to_string(event).unwrap().into())).await
 }
 
-/// Helper function to fetch chat history from the database
-async fn fetch_chat_history(pool: &Pool, chat_id: i32) -> Result<Vec<ChatMessage>, mysql_async::Error> {
-    let mut conn = pool.get_conn().await?;
-    
-    let messages: Vec<ChatMessage> = conn
-        .exec_map(
-            r"SELECT m.sender_id, u.username, m.message_text, 
-              UNIX_TIMESTAMP(m.sent_at) as sent_at 
-              FROM Messages m 
-              LEFT JOIN Users u ON m.sender_id = u.user_id 
-              WHERE m.chatroom_id = :chat_id 
-              ORDER BY m.sent_at ASC 
-              LIMIT 50",
-            params! {
-                "chat_id" => chat_id,
-            },
-            |row: Row| {
-                let timestamp_unix: i64 = row.get("sent_at").unwrap();
-                let timestamp = DateTime::<Utc>::from_timestamp(timestamp_unix, 0).unwrap();
-                
-                ChatMessage {
-                    user_id: row.get("sender_id").unwrap(),
-                    username: row.get::<String, _>("username")
-                               .unwrap_or_else(|| String::from("Deleted User")),
-                    content: row.get("message_text").unwrap(),
-                    timestamp,
-                }
+/// Helper function to send an event to a channel (chat)
+pub(crate) async fn send_to_channel(chat: i32, state: Arc<AppState>, event: ServerEvent) {
+    let chat_channels = state.chat_channels.lock().await;
+    if let Some((tx, _)) = chat_channels.get(&chat) {
+        // Sending only fails when nobody is subscribed, which is fine
+        let _ = tx.send(event);
+    }
+}
+
+/// Helper function to drop a room's channel, which closes it once its subscribers have
+/// received the events already sent
+pub(crate) async fn close_channel(chat: i32, state: Arc<AppState>) {
+    state.chat_channels.lock().await.remove(&chat);
+}
+
+/// Helper function to load a page of history as the event sent to the client
+async fn history_page(state: &AppState, chat: i32, before: Option<i32>, limit: Option<u32>) -> ServerEvent {
+    let service = ChatRoomService::new(state.store.clone());
+    let limit = limit.unwrap_or(state.config.history_page_size);
+    match service.fetch_history(chat, before, limit).await {
+        Ok((messages, next_before)) => ServerEvent::HistoryBatch { messages, next_before },
+        Err(e) => {
+            tracing::error!("Failed to fetch chat history: {}", e);
+            ServerEvent::Error { message: String::from("Failed to load chat history"), nonce: None }
+        }
+    }
+}
+
+/// Helper function to send every message after `after` to one client, a page at a time
+async fn replay_messages(
+    sender: &mut SplitSink<WebSocket, Message>,
+    state: &AppState,
+    chat: i32,
+    mut after: i32,
+) -> Result<(), axum::Error> {
+    let page_size = state.config.history_page_size.max(1);
+    loop {
+        let messages = match state.store.fetch_messages_after(chat, after, page_size).await {
+            Ok(messages) => messages,
+            Err(e) => {
+                tracing::error!("Failed to replay missed messages: {}", e);
+                let error = ServerEvent::Error { message: String::from("Failed to replay missed messages"), nonce: None };
+                return send_event(sender, &error).await;
             }
-        )
-        .await?;
+        };
+
+        let last_page = messages.len() < page_size as usize;
+        for msg in messages {
+            after = msg.message_id;
+            send_event(sender, &ServerEvent::Message(msg)).await?;
+        }
+        if last_page {
+            return Ok(());
+        }
+    }
+}
+
+/// Helper function to look up the status of every member of a room
+async fn room_presence(state: &AppState, chat: i32) -> Result<Vec<UserStatus>, String> {
+    let members = state.store.fetch_user_list(chat).await?;
+    Ok(state.presence.statuses(&members))
+}
+
+/// Helper function to forget a closed connection, telling the user's rooms if that took them offline
+async fn disconnect_presence(state: &Arc<AppState>, user_id: i32, connection_id: u64) {
+    if let Some(status) = state.presence.disconnect(user_id, connection_id) {
+        broadcast_presence(state, user_id, status).await;
+    }
+}
 
-    Ok(messages)
+/// Helper function to send a user's new status to every room and direct conversation they are in
+async fn broadcast_presence(state: &Arc<AppState>, user_id: i32, status: PresenceStatus) {
+    let rooms = match state.store.fetch_user_rooms(user_id).await {
+        Ok(rooms) => rooms.into_iter().map(|room| room.room_id),
+        Err(e) => {
+            tracing::error!("Failed to fetch rooms of user {user_id}: {}", e);
+            return;
+        }
+    };
+    let direct_chats = match state.store.fetch_direct_chats(user_id).await {
+        Ok(chats) => chats.into_iter().map(|(chatroom_id, _, _)| chatroom_id),
+        Err(e) => {
+            tracing::error!("Failed to fetch direct chats of user {user_id}: {}", e);
+            return;
+        }
+    };
+    for chat in rooms.chain(direct_chats) {
+        send_to_channel(chat, state.clone(), ServerEvent::PresenceChanged { user_id, status }).await;
+    }
 }
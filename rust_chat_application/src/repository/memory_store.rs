@@ -0,0 +1,1066 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{Mutex, MutexGuard};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use chat_protocol::{
+    api::{
+        ChatRoomDetails, ModerationAction, ModerationLogEntry, RoomInvite, RoomRole, RoomSort, RoomSummary,
+        RoomVisibility,
+    },
+    ws::{Attachment, ChatMessage},
+};
+
+use crate::repository::store::{
+    add_reaction_to, matches_search_term, search_words, thumbnail_of, ChatRoomStore, ChatStore, MessageSearch,
+    MessageStore, ModerationStore, NewAttachment, StoredAttachment, UserStore,
+};
+
+/// `ChatStore` that keeps everything in process memory, so the server can run without a
+/// database service. Data is lost on restart. It mirrors the constraints of the MySQL
+/// schema, including MySQL's "Duplicate entry" errors that clients look for.
+#[derive(Default)]
+pub struct MemoryStore {
+    data: Mutex<MemoryData>,
+}
+
+#[derive(Default)]
+struct MemoryData {
+    users: Vec<UserRow>,
+    rooms: HashMap<i32, RoomRow>,
+    next_room_id: i32,
+    /// (user_id, chatroom_id) -> role, as in `UserInChatRoom`
+    memberships: HashMap<(i32, i32), RoomRole>,
+    /// (user_id, chatroom_id) -> last_read_message_id of members who have read anything
+    last_read: HashMap<(i32, i32), i32>,
+    /// chatroom_id -> (user_low, user_high), as in `DirectChats`
+    direct_chats: HashMap<i32, (i32, i32)>,
+    invites: Vec<RoomInvite>,
+    next_invite_id: i32,
+    messages: Vec<MessageRow>,
+    next_message_id: i32,
+    message_edits: Vec<MessageEditRow>,
+    /// (message_id, user_id, emoji) in the order the reactions were added, as in `Reactions`
+    reactions: Vec<(i32, i32, String)>,
+    /// As in `Attachments`, in upload order
+    attachments: Vec<StoredAttachment>,
+    next_attachment_id: i32,
+    /// token -> (user_id, expires_at)
+    sessions: HashMap<String, (i32, DateTime<Utc>)>,
+    /// (chatroom_id, user_id) pairs, as in `RoomBans`
+    bans: HashSet<(i32, i32)>,
+    /// (chatroom_id, user_id) -> expires_at, as in `RoomMutes`
+    mutes: HashMap<(i32, i32), Option<DateTime<Utc>>>,
+    /// (chatroom_id, user_id) pairs, as in `RoomReadOnlyUsers`
+    read_only: HashSet<(i32, i32)>,
+    /// (chatroom_id, entry), as in `ModerationLog`
+    moderation_log: Vec<(i32, ModerationLogEntry)>,
+    next_log_id: i32,
+}
+
+struct UserRow {
+    user_id: i32,
+    username: String,
+    email: String,
+    password_hash: String,
+}
+
+struct RoomRow {
+    /// `None` for direct conversations
+    room_name: Option<String>,
+    description: Option<String>,
+    topic: Option<String>,
+    private: bool,
+    archived: bool,
+    slow_mode_secs: u32,
+    created_at: DateTime<Utc>,
+}
+
+struct MessageRow {
+    message_id: i32,
+    chatroom_id: i32,
+    sender_id: i32,
+    message_text: String,
+    sent_at: DateTime<Utc>,
+    edited_at: Option<DateTime<Utc>>,
+    deleted: bool,
+    reply_to: Option<i32>,
+    thread_root_id: Option<i32>,
+}
+
+struct MessageEditRow {
+    message_id: i32,
+    previous_text: String,
+    edited_by: Option<i32>,
+    edited_at: DateTime<Utc>,
+}
+
+impl RoomRow {
+    fn visibility(&self) -> RoomVisibility {
+        if self.private {
+            RoomVisibility::Private
+        } else {
+            RoomVisibility::Public
+        }
+    }
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        MemoryStore::default()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, MemoryData> {
+        self.data.lock().unwrap()
+    }
+}
+
+impl MemoryData {
+    fn user(&self, user_id: i32) -> Option<&UserRow> {
+        self.users.iter().find(|user| user.user_id == user_id)
+    }
+
+    fn user_mut(&mut self, user_id: i32) -> Option<&mut UserRow> {
+        self.users.iter_mut().find(|user| user.user_id == user_id)
+    }
+
+    /// Same as inserting into `UserInChatRoom`: the member has read everything posted so far
+    fn add_member(&mut self, user_id: i32, chatroom_id: i32, role: RoomRole) -> Result<(), String> {
+        if self.memberships.contains_key(&(user_id, chatroom_id)) {
+            return Err(format!("Duplicate entry '{user_id}-{chatroom_id}' for key 'UserInChatRoom.PRIMARY'"));
+        }
+        self.memberships.insert((user_id, chatroom_id), role);
+        let newest = self
+            .messages
+            .iter()
+            .filter(|message| message.chatroom_id == chatroom_id)
+            .map(|message| message.message_id)
+            .max();
+        if let Some(message_id) = newest {
+            self.last_read.insert((user_id, chatroom_id), message_id);
+        }
+        Ok(())
+    }
+
+    /// The listing of a room that is not a direct conversation, with `role` left unset
+    fn room_summary(&self, chatroom_id: i32, room: &RoomRow) -> RoomSummary {
+        let last_message = self
+            .messages
+            .iter()
+            .filter(|message| message.chatroom_id == chatroom_id)
+            .map(|message| message.sent_at)
+            .max();
+
+        RoomSummary {
+            room_id: chatroom_id,
+            room_name: room.room_name.clone().unwrap_or_default(),
+            description: room.description.clone(),
+            visibility: room.visibility(),
+            archived: room.archived,
+            member_count: self.memberships.keys().filter(|(_, room_id)| *room_id == chatroom_id).count() as u32,
+            last_activity: last_message.unwrap_or(room.created_at),
+            role: None,
+            unread_count: None,
+        }
+    }
+
+    fn message_mut(&mut self, message_id: i32) -> Result<&mut MessageRow, String> {
+        self.messages
+            .iter_mut()
+            .find(|message| message.message_id == message_id)
+            .ok_or_else(|| "Message not found".to_string())
+    }
+
+    /// The wire form of a stored message, with the sender's current username
+    fn chat_message(&self, message: &MessageRow) -> ChatMessage {
+        ChatMessage {
+            message_id: message.message_id,
+            user_id: message.sender_id,
+            username: self
+                .user(message.sender_id)
+                .map(|user| user.username.clone())
+                .unwrap_or_else(|| String::from("Deleted User")),
+            content: message.message_text.clone(),
+            timestamp: message.sent_at,
+            edited_at: message.edited_at,
+            deleted: message.deleted,
+            reply_to: message.reply_to,
+            thread_root_id: message.thread_root_id,
+            reply_count: self
+                .messages
+                .iter()
+                .filter(|reply| reply.thread_root_id == Some(message.message_id))
+                .count() as u32,
+            reactions: self
+                .reactions
+                .iter()
+                .filter(|(message_id, _, _)| *message_id == message.message_id)
+                .fold(Vec::new(), |mut reactions, (_, user_id, emoji)| {
+                    add_reaction_to(&mut reactions, emoji.clone(), *user_id);
+                    reactions
+                }),
+            attachments: self
+                .attachments
+                .iter()
+                .filter(|stored| stored.message_id == Some(message.message_id))
+                .map(|stored| stored.attachment.clone())
+                .collect(),
+        }
+    }
+}
+
+/// An invite that has neither expired nor been used up
+fn is_valid_invite(invite: &RoomInvite, now: DateTime<Utc>) -> bool {
+    invite.expires_at.is_none_or(|expires_at| expires_at > now)
+        && invite.max_uses.is_none_or(|max_uses| invite.uses < max_uses)
+}
+
+#[async_trait]
+impl ChatStore for MemoryStore {
+    async fn initialize(&self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ChatRoomStore for MemoryStore {
+    // Ownership is kept in the members' roles, so the creator is not needed here
+    async fn create_chat_room(&self, room_name: &str, _created_by: i32, private: bool) -> Result<i32, String> {
+        let mut data = self.lock();
+        if data.rooms.values().any(|room| room.room_name.as_deref() == Some(room_name)) {
+            return Err(format!("Duplicate entry '{room_name}' for key 'ChatRooms.room_name'"));
+        }
+
+        data.next_room_id += 1;
+        let room_id = data.next_room_id;
+        data.rooms.insert(room_id, RoomRow {
+            room_name: Some(room_name.to_string()),
+            description: None,
+            topic: None,
+            private,
+            archived: false,
+            slow_mode_secs: 0,
+            created_at: Utc::now(),
+        });
+        Ok(room_id)
+    }
+
+    async fn get_room_name(&self, chatroom_id: i32) -> Result<String, String> {
+        self.lock()
+            .rooms
+            .get(&chatroom_id)
+            .map(|room| room.room_name.clone().unwrap_or_default())
+            .ok_or_else(|| "Room not found".to_string())
+    }
+
+    async fn does_room_exist(&self, chatroom_id: i32) -> Result<bool, String> {
+        Ok(self.lock().rooms.contains_key(&chatroom_id))
+    }
+
+    async fn is_room_private(&self, chatroom_id: i32) -> Result<bool, String> {
+        self.lock()
+            .rooms
+            .get(&chatroom_id)
+            .map(|room| room.private)
+            .ok_or_else(|| "Room not found".to_string())
+    }
+
+    async fn fetch_room_details(&self, chatroom_id: i32) -> Result<ChatRoomDetails, String> {
+        let data = self.lock();
+        let room = data.rooms.get(&chatroom_id).ok_or_else(|| "Room not found".to_string())?;
+        Ok(ChatRoomDetails {
+            room_id: chatroom_id,
+            room_name: room.room_name.clone().unwrap_or_default(),
+            description: room.description.clone(),
+            topic: room.topic.clone(),
+            visibility: room.visibility(),
+            archived: room.archived,
+        })
+    }
+
+    async fn update_chat_room(
+        &self,
+        chatroom_id: i32,
+        room_name: &str,
+        description: Option<&str>,
+        topic: Option<&str>,
+    ) -> Result<(), String> {
+        let mut data = self.lock();
+        if data
+            .rooms
+            .iter()
+            .any(|(room_id, room)| *room_id != chatroom_id && room.room_name.as_deref() == Some(room_name))
+        {
+            return Err(format!("Duplicate entry '{room_name}' for key 'ChatRooms.room_name'"));
+        }
+
+        if let Some(room) = data.rooms.get_mut(&chatroom_id) {
+            room.room_name = Some(room_name.to_string());
+            room.description = description.map(str::to_string);
+            room.topic = topic.map(str::to_string);
+        }
+        Ok(())
+    }
+
+    async fn set_room_archived(&self, chatroom_id: i32, archived: bool) -> Result<(), String> {
+        if let Some(room) = self.lock().rooms.get_mut(&chatroom_id) {
+            room.archived = archived;
+        }
+        Ok(())
+    }
+
+    async fn is_room_archived(&self, chatroom_id: i32) -> Result<bool, String> {
+        self.lock()
+            .rooms
+            .get(&chatroom_id)
+            .map(|room| room.archived)
+            .ok_or_else(|| "Room not found".to_string())
+    }
+
+    async fn delete_chat_room(&self, chatroom_id: i32) -> Result<(), String> {
+        let mut data = self.lock();
+        let data = &mut *data;
+        data.rooms.remove(&chatroom_id);
+        data.direct_chats.remove(&chatroom_id);
+        data.memberships.retain(|(_, room_id), _| *room_id != chatroom_id);
+        data.last_read.retain(|(_, room_id), _| *room_id != chatroom_id);
+        data.invites.retain(|invite| invite.room_id != chatroom_id);
+        data.bans.retain(|(room_id, _)| *room_id != chatroom_id);
+        data.mutes.retain(|(room_id, _), _| *room_id != chatroom_id);
+        data.read_only.retain(|(room_id, _)| *room_id != chatroom_id);
+        data.moderation_log.retain(|(room_id, _)| *room_id != chatroom_id);
+        data.attachments.retain(|stored| stored.chatroom_id != chatroom_id);
+
+        let message_ids: HashSet<i32> = data
+            .messages
+            .iter()
+            .filter(|message| message.chatroom_id == chatroom_id)
+            .map(|message| message.message_id)
+            .collect();
+        data.messages.retain(|message| !message_ids.contains(&message.message_id));
+        data.message_edits.retain(|edit| !message_ids.contains(&edit.message_id));
+        data.reactions.retain(|(message_id, _, _)| !message_ids.contains(message_id));
+        Ok(())
+    }
+
+    async fn list_public_rooms(
+        &self,
+        search: Option<&str>,
+        sort: RoomSort,
+        offset: u32,
+        limit: u32,
+    ) -> Result<(Vec<RoomSummary>, u32), String> {
+        let data = self.lock();
+        let search = search.unwrap_or_default().to_lowercase();
+        let mut rooms: Vec<RoomSummary> = data
+            .rooms
+            .iter()
+            .filter(|(chatroom_id, room)| !room.private && !data.direct_chats.contains_key(*chatroom_id))
+            .filter(|(_, room)| room.room_name.as_deref().is_some_and(|name| name.to_lowercase().contains(&search)))
+            .map(|(chatroom_id, room)| data.room_summary(*chatroom_id, room))
+            .collect();
+        match sort {
+            RoomSort::Activity => rooms.sort_by_key(|room| std::cmp::Reverse((room.last_activity, room.room_id))),
+            RoomSort::Members => rooms.sort_by_key(|room| std::cmp::Reverse((room.member_count, room.room_id))),
+            RoomSort::Name => rooms.sort_by(|a, b| (&a.room_name, a.room_id).cmp(&(&b.room_name, b.room_id))),
+        }
+
+        let total = rooms.len() as u32;
+        let page = rooms.into_iter().skip(offset as usize).take(limit as usize).collect();
+        Ok((page, total))
+    }
+
+    async fn fetch_user_rooms(&self, user_id: i32) -> Result<Vec<RoomSummary>, String> {
+        let data = self.lock();
+        let mut rooms: Vec<RoomSummary> = data
+            .memberships
+            .iter()
+            .filter(|((member_id, chatroom_id), _)| *member_id == user_id && !data.direct_chats.contains_key(chatroom_id))
+            .filter_map(|((_, chatroom_id), role)| {
+                let room = data.rooms.get(chatroom_id)?;
+                let last_read = data.last_read.get(&(user_id, *chatroom_id)).copied().unwrap_or_default();
+                let unread_count = data
+                    .messages
+                    .iter()
+                    .filter(|message| message.chatroom_id == *chatroom_id && message.message_id > last_read)
+                    .filter(|message| message.thread_root_id.is_none() && !message.deleted && message.sender_id != user_id)
+                    .count() as u32;
+                Some(RoomSummary {
+                    role: Some(*role),
+                    unread_count: Some(unread_count),
+                    ..data.room_summary(*chatroom_id, room)
+                })
+            })
+            .collect();
+        rooms.sort_by_key(|room| std::cmp::Reverse((room.last_activity, room.room_id)));
+        Ok(rooms)
+    }
+
+    async fn add_user_to_chat_room(&self, user_id: i32, chatroom_id: i32, role: RoomRole) -> Result<(), String> {
+        self.lock().add_member(user_id, chatroom_id, role)
+    }
+
+    async fn remove_user_from_chat_room(&self, user_id: i32, chatroom_id: i32) -> Result<(), String> {
+        let mut data = self.lock();
+        data.memberships.remove(&(user_id, chatroom_id));
+        data.last_read.remove(&(user_id, chatroom_id));
+        Ok(())
+    }
+
+    async fn get_member_role(&self, user_id: i32, chatroom_id: i32) -> Result<Option<RoomRole>, String> {
+        Ok(self.lock().memberships.get(&(user_id, chatroom_id)).copied())
+    }
+
+    async fn set_member_role(&self, user_id: i32, chatroom_id: i32, role: RoomRole) -> Result<bool, String> {
+        let mut data = self.lock();
+        match data.memberships.get_mut(&(user_id, chatroom_id)) {
+            Some(current) => *current = role,
+            None => return Ok(false),
+        }
+        if role == RoomRole::ReadOnly {
+            data.read_only.insert((chatroom_id, user_id));
+        } else {
+            data.read_only.remove(&(chatroom_id, user_id));
+        }
+        Ok(true)
+    }
+
+    async fn is_read_only(&self, chatroom_id: i32, user_id: i32) -> Result<bool, String> {
+        Ok(self.lock().read_only.contains(&(chatroom_id, user_id)))
+    }
+
+    async fn fetch_members(&self, chatroom_id: i32) -> Result<Vec<(i32, String, RoomRole, Option<i32>)>, String> {
+        let data = self.lock();
+        let mut members: Vec<(i32, String, RoomRole, Option<i32>)> = data
+            .memberships
+            .iter()
+            .filter(|((_, room), _)| *room == chatroom_id)
+            .filter_map(|((user_id, _), role)| {
+                let last_read = data.last_read.get(&(*user_id, chatroom_id)).copied();
+                data.user(*user_id).map(|user| (*user_id, user.username.clone(), *role, last_read))
+            })
+            .collect();
+        members.sort_by(|a, b| b.2.cmp(&a.2).then_with(|| a.1.cmp(&b.1)));
+        Ok(members)
+    }
+
+    async fn mark_read(&self, user_id: i32, chatroom_id: i32, message_id: i32) -> Result<bool, String> {
+        let mut data = self.lock();
+        if !data.memberships.contains_key(&(user_id, chatroom_id)) {
+            return Ok(false);
+        }
+        let last_read = data.last_read.entry((user_id, chatroom_id)).or_insert(0);
+        if *last_read >= message_id {
+            return Ok(false);
+        }
+        *last_read = message_id;
+        Ok(true)
+    }
+
+    async fn transfer_ownership(&self, chatroom_id: i32, old_owner: i32, new_owner: i32) -> Result<(), String> {
+        let mut data = self.lock();
+        for (user_id, role) in [(old_owner, RoomRole::Admin), (new_owner, RoomRole::Owner)] {
+            if let Some(current) = data.memberships.get_mut(&(user_id, chatroom_id)) {
+                *current = role;
+            }
+        }
+        data.read_only.remove(&(chatroom_id, new_owner));
+        Ok(())
+    }
+
+    async fn find_direct_chat(&self, user_a: i32, user_b: i32) -> Result<Option<i32>, String> {
+        let users = (user_a.min(user_b), user_a.max(user_b));
+        Ok(self
+            .lock()
+            .direct_chats
+            .iter()
+            .find(|(_, pair)| **pair == users)
+            .map(|(chatroom_id, _)| *chatroom_id))
+    }
+
+    async fn create_direct_chat(&self, user_a: i32, user_b: i32) -> Result<i32, String> {
+        let mut data = self.lock();
+        let users = (user_a.min(user_b), user_a.max(user_b));
+        if data.direct_chats.values().any(|pair| *pair == users) {
+            return Err(format!("Duplicate entry '{}-{}' for key 'DirectChats.uq_direct_chats_users'", users.0, users.1));
+        }
+
+        data.next_room_id += 1;
+        let room_id = data.next_room_id;
+        data.rooms.insert(room_id, RoomRow {
+            room_name: None,
+            description: None,
+            topic: None,
+            private: false,
+            archived: false,
+            slow_mode_secs: 0,
+            created_at: Utc::now(),
+        });
+        data.direct_chats.insert(room_id, users);
+        Ok(room_id)
+    }
+
+    async fn get_direct_chat_users(&self, chatroom_id: i32) -> Result<Option<(i32, i32)>, String> {
+        Ok(self.lock().direct_chats.get(&chatroom_id).copied())
+    }
+
+    async fn fetch_direct_chats(&self, user_id: i32) -> Result<Vec<(i32, i32, String)>, String> {
+        let data = self.lock();
+        let mut chats: Vec<(i32, i32, String)> = data
+            .direct_chats
+            .iter()
+            .filter_map(|(chatroom_id, (user_low, user_high))| {
+                let other = match user_id {
+                    id if id == *user_low => *user_high,
+                    id if id == *user_high => *user_low,
+                    _ => return None,
+                };
+                data.user(other).map(|user| (*chatroom_id, other, user.username.clone()))
+            })
+            .collect();
+        chats.sort_by_key(|(chatroom_id, _, _)| std::cmp::Reverse(*chatroom_id));
+        Ok(chats)
+    }
+
+    async fn create_invite(
+        &self,
+        chatroom_id: i32,
+        created_by: i32,
+        invited_user_id: Option<i32>,
+        token: Option<&str>,
+        expires_in: Option<i64>,
+        max_uses: Option<u32>,
+    ) -> Result<RoomInvite, String> {
+        // Worked out before locking, so a bad value cannot poison the lock
+        let created_at = Utc::now();
+        let expires_at = match expires_in {
+            Some(secs) => Some(
+                Duration::try_seconds(secs)
+                    .and_then(|lifetime| created_at.checked_add_signed(lifetime))
+                    .ok_or_else(|| "Invalid invite".to_string())?,
+            ),
+            None => None,
+        };
+        let mut data = self.lock();
+        data.next_invite_id += 1;
+        let invite = RoomInvite {
+            invite_id: data.next_invite_id,
+            room_id: chatroom_id,
+            user_id: invited_user_id,
+            token: token.map(str::to_string),
+            created_by: Some(created_by),
+            created_at,
+            expires_at,
+            max_uses,
+            uses: 0,
+        };
+        data.invites.push(invite.clone());
+        Ok(invite)
+    }
+
+    async fn fetch_invites(&self, chatroom_id: i32) -> Result<Vec<RoomInvite>, String> {
+        Ok(self
+            .lock()
+            .invites
+            .iter()
+            .filter(|invite| invite.room_id == chatroom_id)
+            .cloned()
+            .collect())
+    }
+
+    async fn delete_invite(&self, chatroom_id: i32, invite_id: i32) -> Result<bool, String> {
+        let mut data = self.lock();
+        let before = data.invites.len();
+        data.invites.retain(|invite| !(invite.room_id == chatroom_id && invite.invite_id == invite_id));
+        Ok(data.invites.len() < before)
+    }
+
+    async fn join_with_user_invite(&self, chatroom_id: i32, user_id: i32, role: RoomRole) -> Result<bool, String> {
+        let mut data = self.lock();
+        let now = Utc::now();
+        let Some(index) = data.invites.iter().position(|invite| {
+            invite.room_id == chatroom_id && invite.user_id == Some(user_id) && is_valid_invite(invite, now)
+        }) else {
+            return Ok(false);
+        };
+        data.add_member(user_id, chatroom_id, role)?;
+        data.invites[index].uses += 1;
+        Ok(true)
+    }
+
+    async fn find_invite_link(&self, token: &str) -> Result<Option<i32>, String> {
+        let now = Utc::now();
+        Ok(self
+            .lock()
+            .invites
+            .iter()
+            .find(|invite| invite.token.as_deref() == Some(token) && is_valid_invite(invite, now))
+            .map(|invite| invite.room_id))
+    }
+
+    async fn join_with_invite_link(&self, token: &str, user_id: i32, role: RoomRole) -> Result<bool, String> {
+        let mut data = self.lock();
+        let now = Utc::now();
+        let Some(index) = data
+            .invites
+            .iter()
+            .position(|invite| invite.token.as_deref() == Some(token) && is_valid_invite(invite, now))
+        else {
+            return Ok(false);
+        };
+        let chatroom_id = data.invites[index].room_id;
+        data.add_member(user_id, chatroom_id, role)?;
+        data.invites[index].uses += 1;
+        Ok(true)
+    }
+}
+
+#[async_trait]
+impl UserStore for MemoryStore {
+    async fn user_sign_up(
+        &self,
+        email: &str,
+        user_name: &str,
+        password_hash: &str,
+        _created_at: i64,
+    ) -> Result<(), String> {
+        let mut data = self.lock();
+        // Same as INSERT IGNORE: silently skip duplicate usernames or emails
+        if data.users.iter().any(|user| user.email == email || user.username == user_name) {
+            return Ok(());
+        }
+
+        let user_id = data.users.len() as i32 + 1;
+        data.users.push(UserRow {
+            user_id,
+            username: user_name.to_string(),
+            email: email.to_string(),
+            password_hash: password_hash.to_string(),
+        });
+        Ok(())
+    }
+
+    async fn user_check_exist(&self, email: String) -> Result<(), String> {
+        match self.lock().users.iter().any(|user| user.email == email) {
+            true => Err("User exist".to_string()),
+            false => Ok(()),
+        }
+    }
+
+    async fn user_query(&self, email: &str) -> Result<Option<(i32, String, String)>, String> {
+        Ok(self
+            .lock()
+            .users
+            .iter()
+            .find(|user| user.email == email)
+            .map(|user| (user.user_id, user.username.clone(), user.password_hash.clone())))
+    }
+
+    async fn get_username(&self, user_id: i32) -> Result<Option<String>, String> {
+        Ok(self.lock().user(user_id).map(|user| user.username.clone()))
+    }
+
+    async fn update_password_hash(&self, user_id: i32, password_hash: &str) -> Result<(), String> {
+        if let Some(user) = self.lock().user_mut(user_id) {
+            user.password_hash = password_hash.to_string();
+        }
+        Ok(())
+    }
+
+    async fn create_session(&self, token: &str, user_id: i32, ttl_secs: i64) -> Result<(), String> {
+        let expires_at = Utc::now() + Duration::seconds(ttl_secs);
+        self.lock().sessions.insert(token.to_string(), (user_id, expires_at));
+        Ok(())
+    }
+
+    async fn find_session(&self, token: &str) -> Result<Option<(i32, String)>, String> {
+        let data = self.lock();
+        Ok(data
+            .sessions
+            .get(token)
+            .filter(|(_, expires_at)| *expires_at > Utc::now())
+            .and_then(|(user_id, _)| data.user(*user_id))
+            .map(|user| (user.user_id, user.username.clone())))
+    }
+
+    async fn delete_session(&self, token: &str) -> Result<(), String> {
+        let now = Utc::now();
+        self.lock()
+            .sessions
+            .retain(|key, (_, expires_at)| key != token && *expires_at > now);
+        Ok(())
+    }
+
+    async fn fetch_user_list(&self, room_id: i32) -> Result<Vec<i32>, String> {
+        Ok(self
+            .lock()
+            .memberships
+            .keys()
+            .filter(|(_, chatroom_id)| *chatroom_id == room_id)
+            .map(|(user_id, _)| *user_id)
+            .collect())
+    }
+}
+
+#[async_trait]
+impl MessageStore for MemoryStore {
+    async fn insert_message(
+        &self,
+        chatroom_id: i32,
+        sender_id: i32,
+        message_text: &str,
+        sent_at: DateTime<Utc>,
+        reply_to: Option<i32>,
+        thread_root_id: Option<i32>,
+    ) -> Result<i32, String> {
+        let mut data = self.lock();
+        data.next_message_id += 1;
+        let message_id = data.next_message_id;
+        data.messages.push(MessageRow {
+            message_id,
+            chatroom_id,
+            sender_id,
+            message_text: message_text.to_string(),
+            sent_at,
+            edited_at: None,
+            deleted: false,
+            reply_to,
+            thread_root_id,
+        });
+        Ok(message_id)
+    }
+
+    async fn fetch_chat_history(
+        &self,
+        chatroom_id: i32,
+        before: Option<i32>,
+        limit: u32,
+    ) -> Result<Vec<ChatMessage>, String> {
+        let data = self.lock();
+        let mut rows: Vec<&MessageRow> = data
+            .messages
+            .iter()
+            .filter(|message| message.chatroom_id == chatroom_id && message.thread_root_id.is_none())
+            .filter(|message| before.is_none_or(|before| message.message_id < before))
+            .collect();
+        rows.sort_by_key(|message| std::cmp::Reverse(message.message_id));
+
+        Ok(rows
+            .into_iter()
+            .take(limit as usize)
+            .map(|message| data.chat_message(message))
+            .collect())
+    }
+
+    async fn fetch_message(&self, message_id: i32) -> Result<Option<(i32, ChatMessage)>, String> {
+        let data = self.lock();
+        Ok(data
+            .messages
+            .iter()
+            .find(|message| message.message_id == message_id)
+            .map(|message| (message.chatroom_id, data.chat_message(message))))
+    }
+
+    async fn fetch_thread_replies(&self, thread_root_id: i32) -> Result<Vec<ChatMessage>, String> {
+        let data = self.lock();
+        let mut rows: Vec<&MessageRow> = data
+            .messages
+            .iter()
+            .filter(|message| message.thread_root_id == Some(thread_root_id))
+            .collect();
+        rows.sort_by_key(|message| message.message_id);
+
+        Ok(rows.into_iter().map(|message| data.chat_message(message)).collect())
+    }
+
+    async fn get_message(&self, message_id: i32) -> Result<Option<(i32, i32, bool)>, String> {
+        Ok(self
+            .lock()
+            .messages
+            .iter()
+            .find(|message| message.message_id == message_id)
+            .map(|message| (message.chatroom_id, message.sender_id, message.deleted)))
+    }
+
+    async fn edit_message(
+        &self,
+        message_id: i32,
+        edited_by: i32,
+        message_text: &str,
+        edited_at: DateTime<Utc>,
+    ) -> Result<(), String> {
+        let mut data = self.lock();
+        let message = data.message_mut(message_id)?;
+        let previous_text = std::mem::replace(&mut message.message_text, message_text.to_string());
+        message.edited_at = Some(edited_at);
+        data.message_edits.push(MessageEditRow {
+            message_id,
+            previous_text,
+            edited_by: Some(edited_by),
+            edited_at,
+        });
+        Ok(())
+    }
+
+    async fn delete_message(
+        &self,
+        message_id: i32,
+        deleted_by: i32,
+        deleted_at: DateTime<Utc>,
+    ) -> Result<(), String> {
+        let mut data = self.lock();
+        let message = data.message_mut(message_id)?;
+        let previous_text = std::mem::take(&mut message.message_text);
+        message.deleted = true;
+        data.message_edits.push(MessageEditRow {
+            message_id,
+            previous_text,
+            edited_by: Some(deleted_by),
+            edited_at: deleted_at,
+        });
+        data.attachments.retain(|stored| stored.message_id != Some(message_id));
+        Ok(())
+    }
+
+    async fn fetch_message_edits(
+        &self,
+        message_id: i32,
+    ) -> Result<Vec<(String, Option<i32>, DateTime<Utc>)>, String> {
+        Ok(self
+            .lock()
+            .message_edits
+            .iter()
+            .filter(|edit| edit.message_id == message_id)
+            .map(|edit| (edit.previous_text.clone(), edit.edited_by, edit.edited_at))
+            .collect())
+    }
+
+    async fn add_reaction(&self, message_id: i32, user_id: i32, emoji: &str) -> Result<bool, String> {
+        let mut data = self.lock();
+        let reaction = (message_id, user_id, emoji.to_string());
+        if data.reactions.contains(&reaction) {
+            return Ok(false);
+        }
+        data.reactions.push(reaction);
+        Ok(true)
+    }
+
+    async fn remove_reaction(&self, message_id: i32, user_id: i32, emoji: &str) -> Result<bool, String> {
+        let mut data = self.lock();
+        let before = data.reactions.len();
+        data.reactions.retain(|reaction| *reaction != (message_id, user_id, emoji.to_string()));
+        Ok(data.reactions.len() < before)
+    }
+
+    async fn fetch_messages_after(
+        &self,
+        chatroom_id: i32,
+        after: i32,
+        limit: u32,
+    ) -> Result<Vec<ChatMessage>, String> {
+        let data = self.lock();
+        let mut rows: Vec<&MessageRow> = data
+            .messages
+            .iter()
+            .filter(|message| message.chatroom_id == chatroom_id && message.message_id > after)
+            .collect();
+        rows.sort_by_key(|message| message.message_id);
+
+        Ok(rows
+            .into_iter()
+            .take(limit as usize)
+            .map(|message| data.chat_message(message))
+            .collect())
+    }
+
+    async fn last_message_at(&self, chatroom_id: i32, user_id: i32) -> Result<Option<DateTime<Utc>>, String> {
+        Ok(self
+            .lock()
+            .messages
+            .iter()
+            .rev()
+            .find(|message| message.chatroom_id == chatroom_id && message.sender_id == user_id)
+            .map(|message| message.sent_at))
+    }
+
+    // Scans every message instead of keeping an index, matching terms the way the MySQL
+    // FULLTEXT search does in boolean mode
+    async fn search_messages(
+        &self,
+        user_id: i32,
+        search: &MessageSearch<'_>,
+        offset: u32,
+        limit: u32,
+    ) -> Result<(Vec<(i32, ChatMessage)>, u32), String> {
+        let data = self.lock();
+        let mut rows: Vec<&MessageRow> = data
+            .messages
+            .iter()
+            .filter(|message| !message.deleted && data.memberships.contains_key(&(user_id, message.chatroom_id)))
+            .filter(|message| search.chatroom_id.is_none_or(|chatroom_id| message.chatroom_id == chatroom_id))
+            .filter(|message| search.sender_id.is_none_or(|sender_id| message.sender_id == sender_id))
+            .filter(|message| search.from.is_none_or(|from| message.sent_at >= from))
+            .filter(|message| search.to.is_none_or(|to| message.sent_at < to))
+            .filter(|message| {
+                search.terms.iter().all(|term| {
+                    search_words(&message.message_text).any(|(_, word)| matches_search_term(word, std::slice::from_ref(term)))
+                })
+            })
+            .collect();
+        rows.sort_by_key(|message| std::cmp::Reverse(message.message_id));
+
+        let total = rows.len() as u32;
+        let page = rows
+            .into_iter()
+            .skip(offset as usize)
+            .take(limit as usize)
+            .map(|message| (message.chatroom_id, data.chat_message(message)))
+            .collect();
+        Ok((page, total))
+    }
+
+    async fn insert_attachment(&self, attachment: &NewAttachment<'_>) -> Result<i32, String> {
+        let mut data = self.lock();
+        data.next_attachment_id += 1;
+        let attachment_id = data.next_attachment_id;
+        let mut thumbnails = attachment.thumbnails.to_vec();
+        thumbnails.sort_by_key(|thumbnail| thumbnail.max_size);
+        data.attachments.push(StoredAttachment {
+            chatroom_id: attachment.chatroom_id,
+            uploader_id: Some(attachment.uploader_id),
+            message_id: None,
+            sha256: attachment.sha256.to_string(),
+            attachment: Attachment {
+                attachment_id,
+                file_name: attachment.file_name.to_string(),
+                content_type: attachment.content_type.to_string(),
+                size: attachment.size,
+                width: attachment.dimensions.map(|(width, _)| width),
+                height: attachment.dimensions.map(|(_, height)| height),
+                thumbnails: thumbnails.iter().map(|thumbnail| thumbnail_of(attachment_id, thumbnail)).collect(),
+            },
+            thumbnails,
+        });
+        Ok(attachment_id)
+    }
+
+    async fn fetch_attachment(&self, attachment_id: i32) -> Result<Option<StoredAttachment>, String> {
+        Ok(self
+            .lock()
+            .attachments
+            .iter()
+            .find(|stored| stored.attachment.attachment_id == attachment_id)
+            .cloned())
+    }
+
+    async fn link_attachments(&self, message_id: i32, attachment_ids: &[i32]) -> Result<(), String> {
+        for stored in self.lock().attachments.iter_mut() {
+            if stored.message_id.is_none() && attachment_ids.contains(&stored.attachment.attachment_id) {
+                stored.message_id = Some(message_id);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A mute that has not expired
+fn is_active_mute(expires_at: Option<DateTime<Utc>>, now: DateTime<Utc>) -> bool {
+    expires_at.is_none_or(|expires_at| expires_at > now)
+}
+
+#[async_trait]
+impl ModerationStore for MemoryStore {
+    async fn ban_user(&self, chatroom_id: i32, user_id: i32, _banned_by: i32, _reason: Option<&str>) -> Result<(), String> {
+        self.lock().bans.insert((chatroom_id, user_id));
+        Ok(())
+    }
+
+    async fn unban_user(&self, chatroom_id: i32, user_id: i32) -> Result<bool, String> {
+        Ok(self.lock().bans.remove(&(chatroom_id, user_id)))
+    }
+
+    async fn is_banned(&self, chatroom_id: i32, user_id: i32) -> Result<bool, String> {
+        Ok(self.lock().bans.contains(&(chatroom_id, user_id)))
+    }
+
+    async fn mute_user(&self, chatroom_id: i32, user_id: i32, _muted_by: i32, duration: Option<i64>) -> Result<(), String> {
+        // Worked out before locking, so a bad value cannot poison the lock
+        let expires_at = match duration {
+            Some(secs) => Some(
+                Duration::try_seconds(secs)
+                    .and_then(|duration| Utc::now().checked_add_signed(duration))
+                    .ok_or_else(|| "Invalid duration".to_string())?,
+            ),
+            None => None,
+        };
+        self.lock().mutes.insert((chatroom_id, user_id), expires_at);
+        Ok(())
+    }
+
+    async fn unmute_user(&self, chatroom_id: i32, user_id: i32) -> Result<bool, String> {
+        Ok(self
+            .lock()
+            .mutes
+            .remove(&(chatroom_id, user_id))
+            .is_some_and(|expires_at| is_active_mute(expires_at, Utc::now())))
+    }
+
+    async fn is_muted(&self, chatroom_id: i32, user_id: i32) -> Result<bool, String> {
+        Ok(self
+            .lock()
+            .mutes
+            .get(&(chatroom_id, user_id))
+            .is_some_and(|expires_at| is_active_mute(*expires_at, Utc::now())))
+    }
+
+    async fn get_slow_mode(&self, chatroom_id: i32) -> Result<u32, String> {
+        self.lock()
+            .rooms
+            .get(&chatroom_id)
+            .map(|room| room.slow_mode_secs)
+            .ok_or_else(|| "Room not found".to_string())
+    }
+
+    async fn set_slow_mode(&self, chatroom_id: i32, interval: u32) -> Result<(), String> {
+        if let Some(room) = self.lock().rooms.get_mut(&chatroom_id) {
+            room.slow_mode_secs = interval;
+        }
+        Ok(())
+    }
+
+    async fn log_moderation(
+        &self,
+        chatroom_id: i32,
+        action: ModerationAction,
+        moderator_id: i32,
+        user_id: Option<i32>,
+        reason: Option<&str>,
+        duration: Option<i64>,
+    ) -> Result<(), String> {
+        let mut data = self.lock();
+        data.next_log_id += 1;
+        let log_id = data.next_log_id;
+        data.moderation_log.push((chatroom_id, ModerationLogEntry {
+            log_id,
+            action,
+            moderator_id: Some(moderator_id),
+            user_id,
+            reason: reason.map(str::to_string),
+            duration,
+            created_at: Utc::now(),
+        }));
+        Ok(())
+    }
+
+    async fn fetch_moderation_log(&self, chatroom_id: i32, limit: u32) -> Result<Vec<ModerationLogEntry>, String> {
+        Ok(self
+            .lock()
+            .moderation_log
+            .iter()
+            .rev()
+            .filter(|(room, _)| *room == chatroom_id)
+            .take(limit as usize)
+            .map(|(_, entry)| entry.clone())
+            .collect())
+    }
+}
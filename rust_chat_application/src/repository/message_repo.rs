@@ -0,0 +1,592 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use mysql_async::{prelude::*, Conn, Row, TxOpts};
+use chat_protocol::ws::{Attachment, ChatMessage};
+
+use crate::repository::mysql_store::MySqlStore;
+use crate::repository::store::{
+    add_reaction_to, thumbnail_of, MessageSearch, MessageStore, NewAttachment, StoredAttachment, StoredThumbnail,
+};
+
+/// Columns read by `chat_message_from_row`, plus the room of each message
+const SELECT_MESSAGES: &str = r"SELECT m.message_id, m.chatroom_id, m.sender_id, u.username, m.message_text,
+    UNIX_TIMESTAMP(m.sent_at) as sent_at, UNIX_TIMESTAMP(m.edited_at) as edited_at,
+    m.deleted_at IS NOT NULL as deleted, m.reply_to, m.thread_root_id,
+    (SELECT COUNT(*) FROM Messages r WHERE r.thread_root_id = m.message_id) as reply_count
+    FROM Messages m
+    LEFT JOIN Users u ON m.sender_id = u.user_id";
+
+/// Joins and conditions of a message search, shared by the page and its count. Uses the
+/// FULLTEXT index on `message_text`.
+const SEARCH_MESSAGES: &str = r"JOIN UserInChatRoom uc ON uc.chatroom_id = m.chatroom_id AND uc.user_id = :user_id
+    WHERE MATCH (m.message_text) AGAINST (:terms IN BOOLEAN MODE)
+    AND m.deleted_at IS NULL
+    AND (:chatroom_id IS NULL OR m.chatroom_id = :chatroom_id)
+    AND (:sender_id IS NULL OR m.sender_id = :sender_id)
+    AND (:from IS NULL OR m.sent_at >= FROM_UNIXTIME(:from))
+    AND (:to IS NULL OR m.sent_at < FROM_UNIXTIME(:to))";
+
+#[async_trait]
+impl MessageStore for MySqlStore {
+    async fn insert_message(
+        &self,
+        chatroom_id: i32,
+        sender_id: i32,
+        message_text: &str,
+        sent_at: DateTime<Utc>,
+        reply_to: Option<i32>,
+        thread_root_id: Option<i32>,
+    ) -> Result<i32, String> {
+        let mut conn = self.pool.get_conn().await.map_err(|e| e.to_string())?;
+
+        conn.exec_drop(
+            r"INSERT INTO Messages (chatroom_id, sender_id, message_text, sent_at, reply_to, thread_root_id)
+              VALUES (:chatroom_id, :sender_id, :message_text, :sent_at, :reply_to, :thread_root_id)",
+            params! {
+                "chatroom_id" => chatroom_id,
+                "sender_id" => sender_id,
+                "message_text" => message_text,
+                "sent_at" => sent_at.to_rfc3339(),
+                "reply_to" => reply_to,
+                "thread_root_id" => thread_root_id,
+            },
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+
+        conn.last_insert_id()
+            .map(|id| id as i32)
+            .ok_or_else(|| "Failed to get message ID".to_string())
+    }
+
+    async fn fetch_chat_history(
+        &self,
+        chatroom_id: i32,
+        before: Option<i32>,
+        limit: u32,
+    ) -> Result<Vec<ChatMessage>, String> {
+        let mut conn = self.pool.get_conn().await.map_err(|e| e.to_string())?;
+
+        let messages = conn.exec_map(
+            format!(
+                "{SELECT_MESSAGES}
+                WHERE m.chatroom_id = :chat_id
+                AND m.thread_root_id IS NULL
+                AND (:before IS NULL OR m.message_id < :before)
+                ORDER BY m.message_id DESC
+                LIMIT :limit"
+            ),
+            params! {
+                "chat_id" => chatroom_id,
+                "before" => before,
+                "limit" => limit,
+            },
+            chat_message_from_row,
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+
+        with_details(&mut conn, messages).await
+    }
+
+    async fn fetch_message(&self, message_id: i32) -> Result<Option<(i32, ChatMessage)>, String> {
+        let mut conn = self.pool.get_conn().await.map_err(|e| e.to_string())?;
+
+        let row: Option<Row> = conn
+            .exec_first(
+                format!("{SELECT_MESSAGES} WHERE m.message_id = :message_id"),
+                params! {
+                    "message_id" => message_id,
+                },
+            )
+            .await
+            .map_err(|e| e.to_string())?;
+
+        let Some(row) = row else {
+            return Ok(None);
+        };
+        let chatroom_id = row.get("chatroom_id").unwrap();
+        let message = with_details(&mut conn, vec![chat_message_from_row(row)]).await?.remove(0);
+        Ok(Some((chatroom_id, message)))
+    }
+
+    async fn fetch_thread_replies(&self, thread_root_id: i32) -> Result<Vec<ChatMessage>, String> {
+        let mut conn = self.pool.get_conn().await.map_err(|e| e.to_string())?;
+
+        let messages = conn.exec_map(
+            format!("{SELECT_MESSAGES} WHERE m.thread_root_id = :thread_root_id ORDER BY m.message_id ASC"),
+            params! {
+                "thread_root_id" => thread_root_id,
+            },
+            chat_message_from_row,
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+
+        with_details(&mut conn, messages).await
+    }
+
+    async fn get_message(&self, message_id: i32) -> Result<Option<(i32, i32, bool)>, String> {
+        let mut conn = self.pool.get_conn().await.map_err(|e| e.to_string())?;
+
+        conn.exec_first(
+            r"SELECT chatroom_id, sender_id, deleted_at IS NOT NULL FROM Messages WHERE message_id = :message_id",
+            params! {
+                "message_id" => message_id,
+            },
+        )
+        .await
+        .map_err(|e| e.to_string())
+    }
+
+    async fn edit_message(
+        &self,
+        message_id: i32,
+        edited_by: i32,
+        message_text: &str,
+        edited_at: DateTime<Utc>,
+    ) -> Result<(), String> {
+        let mut tx = self.pool.start_transaction(TxOpts::default()).await.map_err(|e| e.to_string())?;
+
+        tx.exec_drop(
+            r"INSERT INTO MessageEdits (message_id, previous_text, edited_by, edited_at)
+              SELECT message_id, message_text, :edited_by, :edited_at FROM Messages WHERE message_id = :message_id",
+            params! {
+                "message_id" => message_id,
+                "edited_by" => edited_by,
+                "edited_at" => edited_at.to_rfc3339(),
+            },
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+        tx.exec_drop(
+            r"UPDATE Messages SET message_text = :message_text, edited_at = :edited_at WHERE message_id = :message_id",
+            params! {
+                "message_id" => message_id,
+                "message_text" => message_text,
+                "edited_at" => edited_at.to_rfc3339(),
+            },
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+
+        tx.commit().await.map_err(|e| e.to_string())
+    }
+
+    async fn delete_message(
+        &self,
+        message_id: i32,
+        deleted_by: i32,
+        deleted_at: DateTime<Utc>,
+    ) -> Result<(), String> {
+        let mut tx = self.pool.start_transaction(TxOpts::default()).await.map_err(|e| e.to_string())?;
+
+        tx.exec_drop(
+            r"INSERT INTO MessageEdits (message_id, previous_text, edited_by, edited_at)
+              SELECT message_id, message_text, :deleted_by, :deleted_at FROM Messages WHERE message_id = :message_id",
+            params! {
+                "message_id" => message_id,
+                "deleted_by" => deleted_by,
+                "deleted_at" => deleted_at.to_rfc3339(),
+            },
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+        tx.exec_drop(
+            r"UPDATE Messages SET message_text = '', deleted_at = :deleted_at WHERE message_id = :message_id",
+            params! {
+                "message_id" => message_id,
+                "deleted_at" => deleted_at.to_rfc3339(),
+            },
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+        tx.exec_drop(
+            r"DELETE FROM Attachments WHERE message_id = :message_id",
+            params! {
+                "message_id" => message_id,
+            },
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+
+        tx.commit().await.map_err(|e| e.to_string())
+    }
+
+    async fn fetch_message_edits(
+        &self,
+        message_id: i32,
+    ) -> Result<Vec<(String, Option<i32>, DateTime<Utc>)>, String> {
+        let mut conn = self.pool.get_conn().await.map_err(|e| e.to_string())?;
+
+        let edits: Vec<(String, Option<i32>, i64)> = conn
+            .exec(
+                r"SELECT previous_text, edited_by, UNIX_TIMESTAMP(edited_at)
+                  FROM MessageEdits WHERE message_id = :message_id ORDER BY edit_id",
+                params! {
+                    "message_id" => message_id,
+                },
+            )
+            .await
+            .map_err(|e| e.to_string())?;
+
+        Ok(edits
+            .into_iter()
+            .map(|(previous_text, edited_by, edited_at)| {
+                (previous_text, edited_by, DateTime::<Utc>::from_timestamp(edited_at, 0).unwrap())
+            })
+            .collect())
+    }
+
+    async fn add_reaction(&self, message_id: i32, user_id: i32, emoji: &str) -> Result<bool, String> {
+        let mut conn = self.pool.get_conn().await.map_err(|e| e.to_string())?;
+
+        conn.exec_drop(
+            r"INSERT IGNORE INTO Reactions (message_id, user_id, emoji) VALUES (:message_id, :user_id, :emoji)",
+            params! {
+                "message_id" => message_id,
+                "user_id" => user_id,
+                "emoji" => emoji,
+            },
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+
+        Ok(conn.affected_rows() > 0)
+    }
+
+    async fn remove_reaction(&self, message_id: i32, user_id: i32, emoji: &str) -> Result<bool, String> {
+        let mut conn = self.pool.get_conn().await.map_err(|e| e.to_string())?;
+
+        conn.exec_drop(
+            r"DELETE FROM Reactions WHERE message_id = :message_id AND user_id = :user_id AND emoji = :emoji",
+            params! {
+                "message_id" => message_id,
+                "user_id" => user_id,
+                "emoji" => emoji,
+            },
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+
+        Ok(conn.affected_rows() > 0)
+    }
+
+    async fn fetch_messages_after(
+        &self,
+        chatroom_id: i32,
+        after: i32,
+        limit: u32,
+    ) -> Result<Vec<ChatMessage>, String> {
+        let mut conn = self.pool.get_conn().await.map_err(|e| e.to_string())?;
+
+        let messages = conn.exec_map(
+            format!(
+                "{SELECT_MESSAGES}
+                WHERE m.chatroom_id = :chat_id
+                AND m.message_id > :after
+                ORDER BY m.message_id ASC
+                LIMIT :limit"
+            ),
+            params! {
+                "chat_id" => chatroom_id,
+                "after" => after,
+                "limit" => limit,
+            },
+            chat_message_from_row,
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+
+        with_details(&mut conn, messages).await
+    }
+
+    async fn last_message_at(&self, chatroom_id: i32, user_id: i32) -> Result<Option<DateTime<Utc>>, String> {
+        let mut conn = self.pool.get_conn().await.map_err(|e| e.to_string())?;
+
+        let sent_at: Option<i64> = conn
+            .exec_first(
+                r"SELECT UNIX_TIMESTAMP(sent_at) FROM Messages
+                  WHERE chatroom_id = :chatroom_id AND sender_id = :user_id
+                  ORDER BY message_id DESC LIMIT 1",
+                params! {
+                    "chatroom_id" => chatroom_id,
+                    "user_id" => user_id,
+                },
+            )
+            .await
+            .map_err(|e| e.to_string())?;
+
+        Ok(sent_at.and_then(|sent_at| DateTime::<Utc>::from_timestamp(sent_at, 0)))
+    }
+
+    async fn search_messages(
+        &self,
+        user_id: i32,
+        search: &MessageSearch<'_>,
+        offset: u32,
+        limit: u32,
+    ) -> Result<(Vec<(i32, ChatMessage)>, u32), String> {
+        let mut conn = self.pool.get_conn().await.map_err(|e| e.to_string())?;
+
+        // Every term is required and matches as a prefix. Terms only hold word characters,
+        // so they cannot smuggle in other boolean operators.
+        let terms = search.terms.iter().map(|term| format!("+{term}*")).collect::<Vec<_>>().join(" ");
+        let from = search.from.map(|from| from.timestamp());
+        let to = search.to.map(|to| to.timestamp());
+
+        let total: Option<u32> = conn
+            .exec_first(
+                format!("SELECT COUNT(*) FROM Messages m {SEARCH_MESSAGES}"),
+                params! {
+                    "user_id" => user_id,
+                    "terms" => &terms,
+                    "chatroom_id" => search.chatroom_id,
+                    "sender_id" => search.sender_id,
+                    "from" => from,
+                    "to" => to,
+                },
+            )
+            .await
+            .map_err(|e| e.to_string())?;
+
+        let rows: Vec<Row> = conn
+            .exec(
+                format!("{SELECT_MESSAGES} {SEARCH_MESSAGES} ORDER BY m.message_id DESC LIMIT :limit OFFSET :offset"),
+                params! {
+                    "user_id" => user_id,
+                    "terms" => &terms,
+                    "chatroom_id" => search.chatroom_id,
+                    "sender_id" => search.sender_id,
+                    "from" => from,
+                    "to" => to,
+                    "limit" => limit,
+                    "offset" => offset,
+                },
+            )
+            .await
+            .map_err(|e| e.to_string())?;
+
+        let chatroom_ids: Vec<i32> = rows.iter().map(|row| row.get("chatroom_id").unwrap()).collect();
+        let messages = with_details(&mut conn, rows.into_iter().map(chat_message_from_row).collect()).await?;
+        Ok((chatroom_ids.into_iter().zip(messages).collect(), total.unwrap_or_default()))
+    }
+
+    async fn insert_attachment(&self, attachment: &NewAttachment<'_>) -> Result<i32, String> {
+        let mut tx = self.pool.start_transaction(TxOpts::default()).await.map_err(|e| e.to_string())?;
+
+        tx.exec_drop(
+            r"INSERT INTO Attachments (chatroom_id, uploader_id, sha256, file_name, content_type, size_bytes, width, height)
+              VALUES (:chatroom_id, :uploader_id, :sha256, :file_name, :content_type, :size_bytes, :width, :height)",
+            params! {
+                "chatroom_id" => attachment.chatroom_id,
+                "uploader_id" => attachment.uploader_id,
+                "sha256" => attachment.sha256,
+                "file_name" => attachment.file_name,
+                "content_type" => attachment.content_type,
+                "size_bytes" => attachment.size,
+                "width" => attachment.dimensions.map(|(width, _)| width),
+                "height" => attachment.dimensions.map(|(_, height)| height),
+            },
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+        let attachment_id = tx
+            .last_insert_id()
+            .map(|id| id as i32)
+            .ok_or_else(|| "Failed to get attachment ID".to_string())?;
+
+        tx.exec_batch(
+            r"INSERT INTO AttachmentThumbnails (attachment_id, max_size, width, height, content_type)
+              VALUES (:attachment_id, :max_size, :width, :height, :content_type)",
+            attachment.thumbnails.iter().map(|thumbnail| {
+                params! {
+                    "attachment_id" => attachment_id,
+                    "max_size" => thumbnail.max_size,
+                    "width" => thumbnail.width,
+                    "height" => thumbnail.height,
+                    "content_type" => &thumbnail.content_type,
+                }
+            }),
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+
+        tx.commit().await.map_err(|e| e.to_string())?;
+        Ok(attachment_id)
+    }
+
+    async fn fetch_attachment(&self, attachment_id: i32) -> Result<Option<StoredAttachment>, String> {
+        let mut conn = self.pool.get_conn().await.map_err(|e| e.to_string())?;
+
+        let row: Option<Row> = conn
+            .exec_first(
+                r"SELECT attachment_id, chatroom_id, uploader_id, message_id, sha256, file_name, content_type, size_bytes,
+                  width, height
+                  FROM Attachments WHERE attachment_id = :attachment_id",
+                params! {
+                    "attachment_id" => attachment_id,
+                },
+            )
+            .await
+            .map_err(|e| e.to_string())?;
+
+        let Some(row) = row else {
+            return Ok(None);
+        };
+        let thumbnails: Vec<StoredThumbnail> = fetch_thumbnails(&mut conn, vec![attachment_id])
+            .await?
+            .into_iter()
+            .map(|(_, thumbnail)| thumbnail)
+            .collect();
+        let mut attachment = attachment_from_row(&row);
+        attachment.thumbnails = thumbnails.iter().map(|thumbnail| thumbnail_of(attachment_id, thumbnail)).collect();
+
+        Ok(Some(StoredAttachment {
+            chatroom_id: row.get("chatroom_id").unwrap(),
+            uploader_id: row.get::<Option<i32>, _>("uploader_id").flatten(),
+            message_id: row.get::<Option<i32>, _>("message_id").flatten(),
+            sha256: row.get("sha256").unwrap(),
+            thumbnails,
+            attachment,
+        }))
+    }
+
+    async fn link_attachments(&self, message_id: i32, attachment_ids: &[i32]) -> Result<(), String> {
+        if attachment_ids.is_empty() {
+            return Ok(());
+        }
+        let mut conn = self.pool.get_conn().await.map_err(|e| e.to_string())?;
+
+        let placeholders = vec!["?"; attachment_ids.len()].join(", ");
+        let mut params = vec![mysql_async::Value::from(message_id)];
+        params.extend(attachment_ids.iter().map(|id| mysql_async::Value::from(*id)));
+        conn.exec_drop(
+            format!("UPDATE Attachments SET message_id = ? WHERE message_id IS NULL AND attachment_id IN ({placeholders})"),
+            params,
+        )
+        .await
+        .map_err(|e| e.to_string())
+    }
+}
+
+/// Fill in the reactions and attachments of `messages` with one query each for all of them
+async fn with_details(conn: &mut Conn, mut messages: Vec<ChatMessage>) -> Result<Vec<ChatMessage>, String> {
+    if messages.is_empty() {
+        return Ok(messages);
+    }
+
+    let placeholders = vec!["?"; messages.len()].join(", ");
+    let message_ids: Vec<i32> = messages.iter().map(|message| message.message_id).collect();
+    let reactions: Vec<(i32, String, i32)> = conn
+        .exec(
+            format!(
+                "SELECT message_id, emoji, user_id FROM Reactions
+                WHERE message_id IN ({placeholders})
+                ORDER BY created_at, user_id"
+            ),
+            message_ids.clone(),
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+
+    for (message_id, emoji, user_id) in reactions {
+        if let Some(message) = messages.iter_mut().find(|message| message.message_id == message_id) {
+            add_reaction_to(&mut message.reactions, emoji, user_id);
+        }
+    }
+
+    let attachments: Vec<Row> = conn
+        .exec(
+            format!(
+                "SELECT message_id, attachment_id, file_name, content_type, size_bytes, width, height FROM Attachments
+                WHERE message_id IN ({placeholders})
+                ORDER BY attachment_id"
+            ),
+            message_ids,
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let attachment_ids: Vec<i32> = attachments.iter().map(|row| row.get("attachment_id").unwrap()).collect();
+    let thumbnails = fetch_thumbnails(conn, attachment_ids).await?;
+
+    for row in attachments {
+        let message_id: i32 = row.get("message_id").unwrap();
+        if let Some(message) = messages.iter_mut().find(|message| message.message_id == message_id) {
+            let mut attachment = attachment_from_row(&row);
+            attachment.thumbnails = thumbnails
+                .iter()
+                .filter(|(attachment_id, _)| *attachment_id == attachment.attachment_id)
+                .map(|(attachment_id, thumbnail)| thumbnail_of(*attachment_id, thumbnail))
+                .collect();
+            message.attachments.push(attachment);
+        }
+    }
+    Ok(messages)
+}
+
+/// The thumbnails of the attachments, smallest first, with the attachment each belongs to
+async fn fetch_thumbnails(conn: &mut Conn, attachment_ids: Vec<i32>) -> Result<Vec<(i32, StoredThumbnail)>, String> {
+    if attachment_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let placeholders = vec!["?"; attachment_ids.len()].join(", ");
+    let rows: Vec<(i32, u32, u32, u32, String)> = conn
+        .exec(
+            format!(
+                "SELECT attachment_id, max_size, width, height, content_type FROM AttachmentThumbnails
+                WHERE attachment_id IN ({placeholders})
+                ORDER BY attachment_id, max_size"
+            ),
+            attachment_ids,
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(rows
+        .into_iter()
+        .map(|(attachment_id, max_size, width, height, content_type)| {
+            (attachment_id, StoredThumbnail { max_size, width, height, content_type })
+        })
+        .collect())
+}
+
+/// Map a row of `Attachments`
+fn attachment_from_row(row: &Row) -> Attachment {
+    Attachment {
+        attachment_id: row.get("attachment_id").unwrap(),
+        file_name: row.get("file_name").unwrap(),
+        content_type: row.get("content_type").unwrap(),
+        size: row.get("size_bytes").unwrap(),
+        width: row.get::<Option<u32>, _>("width").flatten(),
+        height: row.get::<Option<u32>, _>("height").flatten(),
+        thumbnails: Vec::new(),
+    }
+}
+
+/// Map a row selected with `SELECT_MESSAGES`
+fn chat_message_from_row(row: Row) -> ChatMessage {
+    let timestamp_unix: i64 = row.get("sent_at").unwrap();
+    let timestamp = DateTime::<Utc>::from_timestamp(timestamp_unix, 0).unwrap();
+
+    ChatMessage {
+        message_id: row.get("message_id").unwrap(),
+        user_id: row.get("sender_id").unwrap(),
+        username: row.get::<String, _>("username")
+                   .unwrap_or_else(|| String::from("Deleted User")),
+        content: row.get("message_text").unwrap(),
+        timestamp,
+        edited_at: row
+            .get::<Option<i64>, _>("edited_at")
+            .flatten()
+            .and_then(|edited_at| DateTime::<Utc>::from_timestamp(edited_at, 0)),
+        deleted: row.get("deleted").unwrap(),
+        reply_to: row.get::<Option<i32>, _>("reply_to").flatten(),
+        thread_root_id: row.get::<Option<i32>, _>("thread_root_id").flatten(),
+        reply_count: row.get("reply_count").unwrap(),
+        reactions: Vec::new(),
+        attachments: Vec::new(),
+    }
+}
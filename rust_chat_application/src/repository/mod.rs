@@ -1,5 +1,7 @@
 pub mod chat_room_repo;
+pub mod memory_store;
+pub mod message_repo;
+pub mod moderation_repo;
+pub mod mysql_store;
+pub mod store;
 pub mod user_repo;
-
-
-
@@ -0,0 +1,209 @@
+use async_trait::async_trait;
+use chat_protocol::api::{ModerationAction, ModerationLogEntry};
+use chrono::{DateTime, Utc};
+use mysql_async::{prelude::*, Row};
+
+use crate::repository::mysql_store::MySqlStore;
+use crate::repository::store::ModerationStore;
+
+/// Mutes that have not expired
+const ACTIVE_MUTE: &str = "(expires_at IS NULL OR expires_at > UTC_TIMESTAMP())";
+
+#[async_trait]
+impl ModerationStore for MySqlStore {
+    async fn ban_user(&self, chatroom_id: i32, user_id: i32, banned_by: i32, reason: Option<&str>) -> Result<(), String> {
+        let mut conn = self.pool.get_conn().await.map_err(|e| e.to_string())?;
+
+        conn.exec_drop(
+            r"REPLACE INTO RoomBans (chatroom_id, user_id, banned_by, reason)
+              VALUES (:chatroom_id, :user_id, :banned_by, :reason)",
+            params! {
+                "chatroom_id" => chatroom_id,
+                "user_id" => user_id,
+                "banned_by" => banned_by,
+                "reason" => reason,
+            },
+        )
+        .await
+        .map_err(|e| e.to_string())
+    }
+
+    async fn unban_user(&self, chatroom_id: i32, user_id: i32) -> Result<bool, String> {
+        let mut conn = self.pool.get_conn().await.map_err(|e| e.to_string())?;
+
+        conn.exec_drop(
+            r"DELETE FROM RoomBans WHERE chatroom_id = :chatroom_id AND user_id = :user_id",
+            params! {
+                "chatroom_id" => chatroom_id,
+                "user_id" => user_id,
+            },
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+
+        Ok(conn.affected_rows() > 0)
+    }
+
+    async fn is_banned(&self, chatroom_id: i32, user_id: i32) -> Result<bool, String> {
+        let mut conn = self.pool.get_conn().await.map_err(|e| e.to_string())?;
+
+        let banned: Option<i32> = conn
+            .exec_first(
+                r"SELECT 1 FROM RoomBans WHERE chatroom_id = :chatroom_id AND user_id = :user_id",
+                params! {
+                    "chatroom_id" => chatroom_id,
+                    "user_id" => user_id,
+                },
+            )
+            .await
+            .map_err(|e| e.to_string())?;
+
+        Ok(banned.is_some())
+    }
+
+    async fn mute_user(&self, chatroom_id: i32, user_id: i32, muted_by: i32, duration: Option<i64>) -> Result<(), String> {
+        let mut conn = self.pool.get_conn().await.map_err(|e| e.to_string())?;
+
+        conn.exec_drop(
+            r"REPLACE INTO RoomMutes (chatroom_id, user_id, muted_by, expires_at)
+              VALUES (:chatroom_id, :user_id, :muted_by, DATE_ADD(UTC_TIMESTAMP(), INTERVAL :duration SECOND))",
+            params! {
+                "chatroom_id" => chatroom_id,
+                "user_id" => user_id,
+                "muted_by" => muted_by,
+                "duration" => duration,
+            },
+        )
+        .await
+        .map_err(|e| e.to_string())
+    }
+
+    async fn unmute_user(&self, chatroom_id: i32, user_id: i32) -> Result<bool, String> {
+        let was_muted = self.is_muted(chatroom_id, user_id).await?;
+        let mut conn = self.pool.get_conn().await.map_err(|e| e.to_string())?;
+
+        conn.exec_drop(
+            r"DELETE FROM RoomMutes WHERE chatroom_id = :chatroom_id AND user_id = :user_id",
+            params! {
+                "chatroom_id" => chatroom_id,
+                "user_id" => user_id,
+            },
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+
+        Ok(was_muted)
+    }
+
+    async fn is_muted(&self, chatroom_id: i32, user_id: i32) -> Result<bool, String> {
+        let mut conn = self.pool.get_conn().await.map_err(|e| e.to_string())?;
+
+        let muted: Option<i32> = conn
+            .exec_first(
+                format!("SELECT 1 FROM RoomMutes WHERE chatroom_id = :chatroom_id AND user_id = :user_id AND {ACTIVE_MUTE}"),
+                params! {
+                    "chatroom_id" => chatroom_id,
+                    "user_id" => user_id,
+                },
+            )
+            .await
+            .map_err(|e| e.to_string())?;
+
+        Ok(muted.is_some())
+    }
+
+    async fn get_slow_mode(&self, chatroom_id: i32) -> Result<u32, String> {
+        let mut conn = self.pool.get_conn().await.map_err(|e| e.to_string())?;
+
+        let interval: Option<u32> = conn
+            .exec_first(
+                "SELECT slow_mode_secs FROM ChatRooms WHERE chatroom_id = :chatroom_id",
+                params! {
+                    "chatroom_id" => chatroom_id,
+                },
+            )
+            .await
+            .map_err(|e| e.to_string())?;
+
+        interval.ok_or_else(|| "Room not found".to_string())
+    }
+
+    async fn set_slow_mode(&self, chatroom_id: i32, interval: u32) -> Result<(), String> {
+        let mut conn = self.pool.get_conn().await.map_err(|e| e.to_string())?;
+
+        conn.exec_drop(
+            "UPDATE ChatRooms SET slow_mode_secs = :interval WHERE chatroom_id = :chatroom_id",
+            params! {
+                "chatroom_id" => chatroom_id,
+                "interval" => interval,
+            },
+        )
+        .await
+        .map_err(|e| e.to_string())
+    }
+
+    async fn log_moderation(
+        &self,
+        chatroom_id: i32,
+        action: ModerationAction,
+        moderator_id: i32,
+        user_id: Option<i32>,
+        reason: Option<&str>,
+        duration: Option<i64>,
+    ) -> Result<(), String> {
+        let mut conn = self.pool.get_conn().await.map_err(|e| e.to_string())?;
+
+        conn.exec_drop(
+            r"INSERT INTO ModerationLog (chatroom_id, action, moderator_id, target_user_id, reason, duration)
+              VALUES (:chatroom_id, :action, :moderator_id, :target_user_id, :reason, :duration)",
+            params! {
+                "chatroom_id" => chatroom_id,
+                "action" => action.as_str(),
+                "moderator_id" => moderator_id,
+                "target_user_id" => user_id,
+                "reason" => reason,
+                "duration" => duration,
+            },
+        )
+        .await
+        .map_err(|e| e.to_string())
+    }
+
+    async fn fetch_moderation_log(&self, chatroom_id: i32, limit: u32) -> Result<Vec<ModerationLogEntry>, String> {
+        let mut conn = self.pool.get_conn().await.map_err(|e| e.to_string())?;
+
+        let rows: Vec<Row> = conn
+            .exec(
+                r"SELECT log_id, action, moderator_id, target_user_id, reason, duration,
+                  UNIX_TIMESTAMP(created_at) as created_at
+                  FROM ModerationLog
+                  WHERE chatroom_id = :chatroom_id
+                  ORDER BY log_id DESC
+                  LIMIT :limit",
+                params! {
+                    "chatroom_id" => chatroom_id,
+                    "limit" => limit,
+                },
+            )
+            .await
+            .map_err(|e| e.to_string())?;
+
+        rows.into_iter().map(moderation_log_entry_from_row).collect()
+    }
+}
+
+/// Map a row of `ModerationLog`
+fn moderation_log_entry_from_row(row: Row) -> Result<ModerationLogEntry, String> {
+    let action: String = row.get("action").unwrap();
+    let created_at: i64 = row.get("created_at").unwrap();
+
+    Ok(ModerationLogEntry {
+        log_id: row.get("log_id").unwrap(),
+        action: ModerationAction::parse(&action).ok_or_else(|| format!("Unknown moderation action {action}"))?,
+        moderator_id: row.get::<Option<i32>, _>("moderator_id").flatten(),
+        user_id: row.get::<Option<i32>, _>("target_user_id").flatten(),
+        reason: row.get::<Option<String>, _>("reason").flatten(),
+        duration: row.get::<Option<i64>, _>("duration").flatten(),
+        created_at: DateTime::<Utc>::from_timestamp(created_at, 0).unwrap(),
+    })
+}
@@ -0,0 +1,24 @@
+use async_trait::async_trait;
+use mysql_async::Pool;
+
+use crate::database::initialize_database;
+use crate::repository::store::ChatStore;
+
+/// `ChatStore` backed by MySQL. The queries for each area live in
+/// `chat_room_repo`, `user_repo`, `message_repo` and `moderation_repo`.
+pub struct MySqlStore {
+    pub(super) pool: Pool,
+}
+
+impl MySqlStore {
+    pub fn new(pool: Pool) -> Self {
+        MySqlStore { pool }
+    }
+}
+
+#[async_trait]
+impl ChatStore for MySqlStore {
+    async fn initialize(&self) -> Result<(), String> {
+        initialize_database(&self.pool).await
+    }
+}
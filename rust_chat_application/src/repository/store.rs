@@ -0,0 +1,334 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use chat_protocol::{
+    api::{ChatRoomDetails, ModerationAction, ModerationLogEntry, RoomInvite, RoomRole, RoomSort, RoomSummary},
+    ws::{Attachment, ChatMessage, Reaction, Thumbnail},
+};
+
+use crate::config::{Config, StorageBackend};
+use crate::database;
+use crate::repository::{memory_store::MemoryStore, mysql_store::MySqlStore};
+
+/// A storage backend shared by all services, e.g. `MySqlStore` or `MemoryStore`
+pub type SharedStore = Arc<dyn ChatStore>;
+
+/// Open the storage backend selected in the config
+pub fn open_store(config: &Config) -> Result<SharedStore, String> {
+    match config.storage {
+        StorageBackend::Mysql => Ok(Arc::new(MySqlStore::new(database::connect(config)?))),
+        StorageBackend::Memory => Ok(Arc::new(MemoryStore::new())),
+    }
+}
+
+/// Everything the server persists. Each area lives in its own trait so that backends can
+/// implement them in separate modules.
+#[async_trait]
+pub trait ChatStore: ChatRoomStore + UserStore + MessageStore + ModerationStore {
+    /// Prepare the backend for use, e.g. create missing tables
+    async fn initialize(&self) -> Result<(), String>;
+}
+
+#[async_trait]
+pub trait ChatRoomStore: Send + Sync {
+    async fn create_chat_room(&self, room_name: &str, created_by: i32, private: bool) -> Result<i32, String>;
+    async fn get_room_name(&self, chatroom_id: i32) -> Result<String, String>;
+    async fn does_room_exist(&self, chatroom_id: i32) -> Result<bool, String>;
+    /// Whether the room can only be joined with an invite
+    async fn is_room_private(&self, chatroom_id: i32) -> Result<bool, String>;
+    async fn fetch_room_details(&self, chatroom_id: i32) -> Result<ChatRoomDetails, String>;
+    /// Replace the room's name, description and topic. Fails with a "Duplicate entry"
+    /// error if another room has the name.
+    async fn update_chat_room(
+        &self,
+        chatroom_id: i32,
+        room_name: &str,
+        description: Option<&str>,
+        topic: Option<&str>,
+    ) -> Result<(), String>;
+    async fn set_room_archived(&self, chatroom_id: i32, archived: bool) -> Result<(), String>;
+    /// Whether nobody may post in the room
+    async fn is_room_archived(&self, chatroom_id: i32) -> Result<bool, String>;
+    /// Delete the room with its messages, members, invites, bans and moderation log
+    async fn delete_chat_room(&self, chatroom_id: i32) -> Result<(), String>;
+    /// One page of the public rooms whose name contains `search`, ignoring case, and the
+    /// number of matching rooms. Direct conversations are never listed.
+    async fn list_public_rooms(
+        &self,
+        search: Option<&str>,
+        sort: RoomSort,
+        offset: u32,
+        limit: u32,
+    ) -> Result<(Vec<RoomSummary>, u32), String>;
+    /// Every room the user has joined apart from direct conversations, with their role and
+    /// unread count, most recently active first. Unread are the top-level messages from others
+    /// that are not deleted, after the last one the user read.
+    async fn fetch_user_rooms(&self, user_id: i32) -> Result<Vec<RoomSummary>, String>;
+    /// Add a member who has read everything posted so far
+    async fn add_user_to_chat_room(&self, user_id: i32, chatroom_id: i32, role: RoomRole) -> Result<(), String>;
+    async fn remove_user_from_chat_room(&self, user_id: i32, chatroom_id: i32) -> Result<(), String>;
+    /// The user's role in the room, `None` if they are not a member
+    async fn get_member_role(&self, user_id: i32, chatroom_id: i32) -> Result<Option<RoomRole>, String>;
+    /// Returns false if the user is not a member of the room. A user made `read_only` stays
+    /// read-only if they leave and join again, until they are given another role.
+    async fn set_member_role(&self, user_id: i32, chatroom_id: i32, role: RoomRole) -> Result<bool, String>;
+    /// Whether the user was made `read_only` in the room, member or not
+    async fn is_read_only(&self, chatroom_id: i32, user_id: i32) -> Result<bool, String>;
+    /// Every member of a room as (user_id, username, role, last_read_message_id), most
+    /// privileged first
+    async fn fetch_members(&self, chatroom_id: i32) -> Result<Vec<(i32, String, RoomRole, Option<i32>)>, String>;
+    /// Move the member's read position forward to `message_id`. Returns false if they are
+    /// not a member or have already read that far.
+    async fn mark_read(&self, user_id: i32, chatroom_id: i32, message_id: i32) -> Result<bool, String>;
+    /// Make `new_owner` the owner of the room and `old_owner` an admin, both or neither
+    async fn transfer_ownership(&self, chatroom_id: i32, old_owner: i32, new_owner: i32) -> Result<(), String>;
+    /// The direct conversation between two users, in either order
+    async fn find_direct_chat(&self, user_a: i32, user_b: i32) -> Result<Option<i32>, String>;
+    /// Create the direct conversation between two users. Fails with a "Duplicate entry"
+    /// error if it already exists.
+    async fn create_direct_chat(&self, user_a: i32, user_b: i32) -> Result<i32, String>;
+    /// Both participants of a direct conversation, `None` if the room is not one
+    async fn get_direct_chat_users(&self, chatroom_id: i32) -> Result<Option<(i32, i32)>, String>;
+    /// Every direct conversation of a user as (chatroom_id, other user_id, other username),
+    /// newest first
+    async fn fetch_direct_chats(&self, user_id: i32) -> Result<Vec<(i32, i32, String)>, String>;
+    /// Store an invite for `invited_user_id`, or a link invite with `token`, and return it
+    async fn create_invite(
+        &self,
+        chatroom_id: i32,
+        created_by: i32,
+        invited_user_id: Option<i32>,
+        token: Option<&str>,
+        expires_in: Option<i64>,
+        max_uses: Option<u32>,
+    ) -> Result<RoomInvite, String>;
+    /// Every invite of a room, including expired and used up ones, oldest first
+    async fn fetch_invites(&self, chatroom_id: i32) -> Result<Vec<RoomInvite>, String>;
+    /// Returns false if the room has no such invite
+    async fn delete_invite(&self, chatroom_id: i32, invite_id: i32) -> Result<bool, String>;
+    /// Use up one of the user's valid invites to a room and add them with `role`, in one
+    /// transaction. Returns false if there is no such invite.
+    async fn join_with_user_invite(&self, chatroom_id: i32, user_id: i32, role: RoomRole) -> Result<bool, String>;
+    /// The chatroom_id of a valid link invite, without using it
+    async fn find_invite_link(&self, token: &str) -> Result<Option<i32>, String>;
+    /// Count one use of a valid link invite and add the user to its room with `role`, in
+    /// one transaction. Returns false if the invite is no longer valid.
+    async fn join_with_invite_link(&self, token: &str, user_id: i32, role: RoomRole) -> Result<bool, String>;
+}
+
+#[async_trait]
+pub trait UserStore: Send + Sync {
+    async fn user_sign_up(
+        &self,
+        email: &str,
+        user_name: &str,
+        password_hash: &str,
+        created_at: i64,
+    ) -> Result<(), String>;
+    /// Fails with "User exist" if the email is already registered
+    async fn user_check_exist(&self, email: String) -> Result<(), String>;
+    /// Look up (user_id, username, password_hash) by email
+    async fn user_query(&self, email: &str) -> Result<Option<(i32, String, String)>, String>;
+    async fn get_username(&self, user_id: i32) -> Result<Option<String>, String>;
+    async fn update_password_hash(&self, user_id: i32, password_hash: &str) -> Result<(), String>;
+    async fn create_session(&self, token: &str, user_id: i32, ttl_secs: i64) -> Result<(), String>;
+    /// Resolve an unexpired session token to (user_id, username)
+    async fn find_session(&self, token: &str) -> Result<Option<(i32, String)>, String>;
+    async fn delete_session(&self, token: &str) -> Result<(), String>;
+    async fn fetch_user_list(&self, room_id: i32) -> Result<Vec<i32>, String>;
+}
+
+#[async_trait]
+pub trait MessageStore: Send + Sync {
+    /// Persist a message, optionally as a reply in a thread, and return its `message_id`
+    async fn insert_message(
+        &self,
+        chatroom_id: i32,
+        sender_id: i32,
+        message_text: &str,
+        sent_at: DateTime<Utc>,
+        reply_to: Option<i32>,
+        thread_root_id: Option<i32>,
+    ) -> Result<i32, String>;
+    /// Up to `limit` messages of a room with a `message_id` below `before`, or the newest
+    /// ones when `before` is `None`, newest first. Thread replies are left out. Every
+    /// `ChatMessage` returned by this trait carries its reactions and attachments.
+    async fn fetch_chat_history(
+        &self,
+        chatroom_id: i32,
+        before: Option<i32>,
+        limit: u32,
+    ) -> Result<Vec<ChatMessage>, String>;
+    /// Look up a message together with its chatroom_id
+    async fn fetch_message(&self, message_id: i32) -> Result<Option<(i32, ChatMessage)>, String>;
+    /// Every reply in a thread, oldest first
+    async fn fetch_thread_replies(&self, thread_root_id: i32) -> Result<Vec<ChatMessage>, String>;
+    /// Look up (chatroom_id, sender_id, deleted) of a message
+    async fn get_message(&self, message_id: i32) -> Result<Option<(i32, i32, bool)>, String>;
+    /// Replace the text of a message, keeping the previous one in `MessageEdits`
+    async fn edit_message(
+        &self,
+        message_id: i32,
+        edited_by: i32,
+        message_text: &str,
+        edited_at: DateTime<Utc>,
+    ) -> Result<(), String>;
+    /// Blank out a message and mark it deleted, keeping its text in `MessageEdits`. Its
+    /// attachments are dropped.
+    async fn delete_message(
+        &self,
+        message_id: i32,
+        deleted_by: i32,
+        deleted_at: DateTime<Utc>,
+    ) -> Result<(), String>;
+    /// Earlier versions of a message as (previous_text, edited_by, edited_at), oldest first
+    async fn fetch_message_edits(
+        &self,
+        message_id: i32,
+    ) -> Result<Vec<(String, Option<i32>, DateTime<Utc>)>, String>;
+    /// Record a reaction. Returns false if the user had already reacted with this emoji.
+    async fn add_reaction(&self, message_id: i32, user_id: i32, emoji: &str) -> Result<bool, String>;
+    /// Remove a reaction. Returns false if there was no such reaction.
+    async fn remove_reaction(&self, message_id: i32, user_id: i32, emoji: &str) -> Result<bool, String>;
+    /// Up to `limit` messages of a room with a `message_id` above `after`, oldest first
+    async fn fetch_messages_after(
+        &self,
+        chatroom_id: i32,
+        after: i32,
+        limit: u32,
+    ) -> Result<Vec<ChatMessage>, String>;
+    /// When the user last posted to the room, if ever
+    async fn last_message_at(&self, chatroom_id: i32, user_id: i32) -> Result<Option<DateTime<Utc>>, String>;
+    /// One page of the messages matching `search` as (chatroom_id, message), newest first,
+    /// and the number of matching messages. Only rooms the user belongs to are searched and
+    /// deleted messages are left out.
+    async fn search_messages(
+        &self,
+        user_id: i32,
+        search: &MessageSearch<'_>,
+        offset: u32,
+        limit: u32,
+    ) -> Result<(Vec<(i32, ChatMessage)>, u32), String>;
+    /// Record an uploaded file that is not part of a message yet, with its thumbnails, and
+    /// return its attachment_id
+    async fn insert_attachment(&self, attachment: &NewAttachment<'_>) -> Result<i32, String>;
+    async fn fetch_attachment(&self, attachment_id: i32) -> Result<Option<StoredAttachment>, String>;
+    /// Make the attachments that are not part of a message yet part of this one
+    async fn link_attachments(&self, message_id: i32, attachment_ids: &[i32]) -> Result<(), String>;
+}
+
+/// What `MessageStore::insert_attachment` records
+pub struct NewAttachment<'a> {
+    pub chatroom_id: i32,
+    pub uploader_id: i32,
+    pub sha256: &'a str,
+    pub file_name: &'a str,
+    pub content_type: &'a str,
+    pub size: u64,
+    /// Width and height of an image
+    pub dimensions: Option<(u32, u32)>,
+    pub thumbnails: &'a [StoredThumbnail],
+}
+
+/// A thumbnail of an image attachment, stored next to it on disk
+#[derive(Clone)]
+pub struct StoredThumbnail {
+    /// The configured size it was made for, which names it
+    pub max_size: u32,
+    pub width: u32,
+    pub height: u32,
+    pub content_type: String,
+}
+
+/// An uploaded file as kept by `MessageStore`
+#[derive(Clone)]
+pub struct StoredAttachment {
+    pub chatroom_id: i32,
+    /// `None` once the uploader's account is gone
+    pub uploader_id: Option<i32>,
+    /// `None` until the file is sent in a message
+    pub message_id: Option<i32>,
+    /// Hex digest of the contents, which names the file on disk
+    pub sha256: String,
+    /// Smallest first
+    pub thumbnails: Vec<StoredThumbnail>,
+    pub attachment: Attachment,
+}
+
+/// What `MessageStore::search_messages` looks for
+pub struct MessageSearch<'a> {
+    /// Lowercase words that must each start a word of the message
+    pub terms: &'a [String],
+    pub chatroom_id: Option<i32>,
+    pub sender_id: Option<i32>,
+    /// Sent at or after this time
+    pub from: Option<DateTime<Utc>>,
+    /// Sent before this time
+    pub to: Option<DateTime<Utc>>,
+}
+
+#[async_trait]
+pub trait ModerationStore: Send + Sync {
+    /// Keep a user out of a room, replacing an earlier ban
+    async fn ban_user(&self, chatroom_id: i32, user_id: i32, banned_by: i32, reason: Option<&str>) -> Result<(), String>;
+    /// Returns false if the user was not banned
+    async fn unban_user(&self, chatroom_id: i32, user_id: i32) -> Result<bool, String>;
+    async fn is_banned(&self, chatroom_id: i32, user_id: i32) -> Result<bool, String>;
+    /// Keep a user from posting for `duration` seconds, or until unmuted if `None`,
+    /// replacing an earlier mute
+    async fn mute_user(&self, chatroom_id: i32, user_id: i32, muted_by: i32, duration: Option<i64>) -> Result<(), String>;
+    /// Returns false if the user was not muted, or the mute had already expired
+    async fn unmute_user(&self, chatroom_id: i32, user_id: i32) -> Result<bool, String>;
+    /// Whether the user has a mute that has not expired
+    async fn is_muted(&self, chatroom_id: i32, user_id: i32) -> Result<bool, String>;
+    /// Seconds a member has to wait between two messages, 0 when slow mode is off
+    async fn get_slow_mode(&self, chatroom_id: i32) -> Result<u32, String>;
+    async fn set_slow_mode(&self, chatroom_id: i32, interval: u32) -> Result<(), String>;
+    /// Record an action in the room's moderation log
+    async fn log_moderation(
+        &self,
+        chatroom_id: i32,
+        action: ModerationAction,
+        moderator_id: i32,
+        user_id: Option<i32>,
+        reason: Option<&str>,
+        duration: Option<i64>,
+    ) -> Result<(), String>;
+    /// Up to `limit` entries of the room's moderation log, newest first
+    async fn fetch_moderation_log(&self, chatroom_id: i32, limit: u32) -> Result<Vec<ModerationLogEntry>, String>;
+}
+
+/// Count one user's reaction into the per-emoji totals of a message
+pub(crate) fn add_reaction_to(reactions: &mut Vec<Reaction>, emoji: String, user_id: i32) {
+    match reactions.iter_mut().find(|reaction| reaction.emoji == emoji) {
+        Some(reaction) => {
+            reaction.count += 1;
+            reaction.user_ids.push(user_id);
+        }
+        None => reactions.push(Reaction { emoji, count: 1, user_ids: vec![user_id] }),
+    }
+}
+
+/// The wire form of a thumbnail, pointing at `GET /api/attachments/{id}/thumbnails/{max_size}`
+pub(crate) fn thumbnail_of(attachment_id: i32, thumbnail: &StoredThumbnail) -> Thumbnail {
+    Thumbnail {
+        url: format!("/api/attachments/{attachment_id}/thumbnails/{}", thumbnail.max_size),
+        width: thumbnail.width,
+        height: thumbnail.height,
+    }
+}
+
+/// The words of a text with their byte offsets, split the way MySQL's full-text parser
+/// does: runs of letters, digits and underscores
+pub(crate) fn search_words(text: &str) -> impl Iterator<Item = (usize, &str)> {
+    text.split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .filter(|word| !word.is_empty())
+        .map(move |word| (word.as_ptr() as usize - text.as_ptr() as usize, word))
+}
+
+/// Whether a word starts with one of the search terms, ignoring case
+pub(crate) fn matches_search_term(word: &str, terms: &[String]) -> bool {
+    let word = word.to_lowercase();
+    terms.iter().any(|term| word.starts_with(term.as_str()))
+}
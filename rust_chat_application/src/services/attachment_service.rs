@@ -0,0 +1,282 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use chat_protocol::ws::Attachment;
+use rand::{distributions::Alphanumeric, Rng};
+use sha2::{Digest, Sha256};
+
+use crate::config::Config;
+use crate::repository::store::{thumbnail_of, NewAttachment, SharedStore, StoredThumbnail};
+use crate::services::chat_room_service::{ChatRoomService, Permission};
+use crate::services::media::{self, ProcessedImage};
+
+/// Longest file name kept, in characters, as the `file_name` column allows
+const MAX_FILE_NAME_CHARS: usize = 255;
+/// Image types browsers are allowed to show in place. Uploads of these types must really
+/// be such an image, see `is_image_of_type`.
+pub const INLINE_IMAGE_TYPES: [&str; 4] = ["image/png", "image/jpeg", "image/gif", "image/webp"];
+
+pub struct AttachmentService {
+    repository: SharedStore,
+    config: Arc<Config>,
+}
+
+impl AttachmentService {
+    pub fn new(repository: SharedStore, config: Arc<Config>) -> Self {
+        AttachmentService { repository, config }
+    }
+
+    /// Store a file uploaded to a room, to be sent in a message by the same user. Files are
+    /// named after the sha256 of their contents, so identical uploads share one copy. Images
+    /// are stored without their metadata, next to thumbnails of them.
+    pub async fn upload(
+        &self,
+        user_id: i32,
+        chatroom_id: i32,
+        file_name: &str,
+        content_type: &str,
+        contents: Vec<u8>,
+    ) -> Result<Attachment, String> {
+        self.rooms().check_permission(user_id, chatroom_id, Permission::Post).await?;
+
+        // Parameters such as the charset of text files are dropped
+        let content_type = content_type.split(';').next().unwrap_or_default().trim().to_ascii_lowercase();
+        if !self.config.attachment_types.contains(&content_type) {
+            return Err(format!("Attachments of type {content_type} are not allowed"));
+        }
+        if contents.is_empty() {
+            return Err("The attachment is empty".to_string());
+        }
+        if contents.len() as u64 > self.config.max_attachment_bytes {
+            return Err(format!("The attachment is larger than {} bytes", self.config.max_attachment_bytes));
+        }
+        let (contents, dimensions, thumbnail_files) = if INLINE_IMAGE_TYPES.contains(&content_type.as_str()) {
+            if !is_image_of_type(&content_type, &contents) {
+                return Err(format!("The attachment is not a valid {content_type} file"));
+            }
+            let ProcessedImage { contents, width, height, thumbnails } =
+                self.process_image(&content_type, contents).await?;
+            (contents, Some((width, height)), thumbnails)
+        } else {
+            (contents, None, Vec::new())
+        };
+        let sha256 = format!("{:x}", Sha256::digest(&contents));
+        self.store_file(&self.file_path(&sha256), &contents).await?;
+        let mut thumbnails = Vec::new();
+        for thumbnail in thumbnail_files {
+            self.store_file(&self.thumbnail_path(&sha256, thumbnail.max_size), &thumbnail.contents).await?;
+            thumbnails.push(StoredThumbnail {
+                max_size: thumbnail.max_size,
+                width: thumbnail.width,
+                height: thumbnail.height,
+                content_type: thumbnail.content_type.to_string(),
+            });
+        }
+
+        let file_name = clean_file_name(file_name);
+        let size = contents.len() as u64;
+        let attachment_id = self
+            .repository
+            .insert_attachment(&NewAttachment {
+                chatroom_id,
+                uploader_id: user_id,
+                sha256: &sha256,
+                file_name: &file_name,
+                content_type: &content_type,
+                size,
+                dimensions,
+                thumbnails: &thumbnails,
+            })
+            .await?;
+        Ok(Attachment {
+            attachment_id,
+            file_name,
+            content_type,
+            size,
+            width: dimensions.map(|(width, _)| width),
+            height: dimensions.map(|(_, height)| height),
+            thumbnails: thumbnails.iter().map(|thumbnail| thumbnail_of(attachment_id, thumbnail)).collect(),
+        })
+    }
+
+    /// Where an attachment is stored on disk, for members of its room. Until it is sent in a
+    /// message only the uploader can see it.
+    pub async fn open(&self, user_id: i32, attachment_id: i32) -> Result<(PathBuf, Attachment), String> {
+        let stored = self
+            .repository
+            .fetch_attachment(attachment_id)
+            .await?
+            .filter(|stored| stored.message_id.is_some() || stored.uploader_id == Some(user_id))
+            .ok_or_else(|| "Attachment not found".to_string())?;
+        self.rooms().check_permission(user_id, stored.chatroom_id, Permission::View).await?;
+        Ok((self.file_path(&stored.sha256), stored.attachment))
+    }
+
+    /// Where the thumbnail of an image made for `max_size` is stored on disk, and its content
+    /// type, for those who may open the image
+    pub async fn open_thumbnail(
+        &self,
+        user_id: i32,
+        attachment_id: i32,
+        max_size: u32,
+    ) -> Result<(PathBuf, String), String> {
+        let stored = self
+            .repository
+            .fetch_attachment(attachment_id)
+            .await?
+            .filter(|stored| stored.message_id.is_some() || stored.uploader_id == Some(user_id))
+            .ok_or_else(|| "Attachment not found".to_string())?;
+        self.rooms().check_permission(user_id, stored.chatroom_id, Permission::View).await?;
+        let thumbnail = stored
+            .thumbnails
+            .into_iter()
+            .find(|thumbnail| thumbnail.max_size == max_size)
+            .ok_or_else(|| "Thumbnail not found".to_string())?;
+        Ok((self.thumbnail_path(&stored.sha256, max_size), thumbnail.content_type))
+    }
+
+    /// Decode the image on a blocking thread, as it can take a while for large images
+    async fn process_image(&self, content_type: &str, contents: Vec<u8>) -> Result<ProcessedImage, String> {
+        let content_type = content_type.to_string();
+        let thumbnail_sizes = self.config.thumbnail_sizes.clone();
+        tokio::task::spawn_blocking(move || media::process_image(&content_type, &contents, &thumbnail_sizes))
+            .await
+            .map_err(|e| format!("Failed to process image: {e}"))?
+    }
+
+    /// `<attachment_dir>/<first two hex digits>/<sha256>`, so no directory grows too large
+    fn file_path(&self, sha256: &str) -> PathBuf {
+        self.config.attachment_dir.join(&sha256[..2]).join(sha256)
+    }
+
+    /// `<file path>-<max_size>`, next to the image
+    fn thumbnail_path(&self, sha256: &str, max_size: u32) -> PathBuf {
+        self.config.attachment_dir.join(&sha256[..2]).join(format!("{sha256}-{max_size}"))
+    }
+
+    async fn store_file(&self, path: &Path, contents: &[u8]) -> Result<(), String> {
+        if tokio::fs::try_exists(path).await.unwrap_or(false) {
+            return Ok(());
+        }
+        let dir = path.parent().unwrap();
+        tokio::fs::create_dir_all(dir)
+            .await
+            .map_err(|e| format!("Failed to store attachment: {e}"))?;
+
+        // Write under a name of its own first, so nobody reads a half-written file
+        let suffix: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(8)
+            .map(char::from)
+            .collect();
+        let name = path.file_name().unwrap().to_string_lossy();
+        let partial = dir.join(format!("{name}.{suffix}.part"));
+        tokio::fs::write(&partial, contents)
+            .await
+            .map_err(|e| format!("Failed to store attachment: {e}"))?;
+        tokio::fs::rename(&partial, path)
+            .await
+            .map_err(|e| format!("Failed to store attachment: {e}"))
+    }
+
+    /// Permission checks go through the chat room service
+    fn rooms(&self) -> ChatRoomService {
+        ChatRoomService::new(self.repository.clone())
+    }
+}
+
+/// Whether `contents` starts with the signature of the image type, so that a file cannot
+/// be shown inline under a type it does not have
+fn is_image_of_type(content_type: &str, contents: &[u8]) -> bool {
+    match content_type {
+        "image/png" => contents.starts_with(b"\x89PNG\r\n\x1a\n"),
+        "image/jpeg" => contents.starts_with(b"\xff\xd8\xff"),
+        "image/gif" => contents.starts_with(b"GIF87a") || contents.starts_with(b"GIF89a"),
+        "image/webp" => contents.len() >= 12 && contents.starts_with(b"RIFF") && &contents[8..12] == b"WEBP",
+        _ => false,
+    }
+}
+
+/// The name of an uploaded file without any directories or control characters
+fn clean_file_name(file_name: &str) -> String {
+    let name: String = file_name
+        .rsplit(['/', '\\'])
+        .next()
+        .unwrap_or_default()
+        .chars()
+        .filter(|c| !c.is_control())
+        .take(MAX_FILE_NAME_CHARS)
+        .collect();
+    match name.trim() {
+        "" | "." | ".." => String::from("file"),
+        name => name.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chat_protocol::api::RoomVisibility;
+    use crate::repository::store::SharedStore;
+    use crate::services::message_service::MessageService;
+    use crate::services::test_support::{memory_store, room, sign_up};
+
+    /// A service storing files in a directory of its own under the system's temp directory
+    fn attachment_service(store: &SharedStore) -> AttachmentService {
+        let suffix: String = rand::thread_rng().sample_iter(&Alphanumeric).take(8).map(char::from).collect();
+        let config = Config {
+            attachment_dir: std::env::temp_dir().join(format!("chat-attachments-{suffix}")),
+            max_attachment_bytes: 1024,
+            ..Config::default()
+        };
+        AttachmentService::new(store.clone(), Arc::new(config))
+    }
+
+    #[tokio::test]
+    async fn uploads_stay_with_the_uploader_until_sent() {
+        let store = memory_store();
+        let alice = sign_up(&store, "alice").await;
+        let bob = sign_up(&store, "bob").await;
+        let mallory = sign_up(&store, "mallory").await;
+        let room = room(&store, alice, RoomVisibility::Public, &[bob]).await;
+        let service = attachment_service(&store);
+
+        let notes = service
+            .upload(alice, room, "../../notes.txt", "text/plain; charset=utf-8", b"hello".to_vec())
+            .await
+            .unwrap();
+        assert_eq!((notes.file_name.as_str(), notes.content_type.as_str(), notes.size), ("notes.txt", "text/plain", 5));
+        let (path, _) = service.open(alice, notes.attachment_id).await.unwrap();
+        assert_eq!(tokio::fs::read(&path).await.unwrap(), b"hello");
+        assert_eq!(service.open(bob, notes.attachment_id).await, Err("Attachment not found".to_string()));
+
+        // Identical uploads share one file
+        let copy = service.upload(bob, room, "copy.txt", "text/plain", b"hello".to_vec()).await.unwrap();
+        assert_eq!(service.open(bob, copy.attachment_id).await.unwrap().0, path);
+
+        let message = MessageService::new(store.clone())
+            .post_message(room, alice, "alice".into(), String::new(), None, vec![notes.attachment_id])
+            .await
+            .unwrap();
+        assert_eq!(message.attachments, vec![notes.clone()]);
+        assert!(service.open(bob, notes.attachment_id).await.is_ok());
+        assert_eq!(
+            service.open(mallory, notes.attachment_id).await,
+            Err("Not a member of this chat room".to_string())
+        );
+
+        assert_eq!(
+            service.upload(alice, room, "run.sh", "application/x-sh", b"#!/bin/sh".to_vec()).await,
+            Err("Attachments of type application/x-sh are not allowed".to_string())
+        );
+        assert_eq!(
+            service.upload(alice, room, "big.txt", "text/plain", vec![b'a'; 1025]).await,
+            Err("The attachment is larger than 1024 bytes".to_string())
+        );
+        assert_eq!(
+            service.upload(alice, room, "cat.png", "image/png", b"not a png".to_vec()).await,
+            Err("The attachment is not a valid image/png file".to_string())
+        );
+        let _ = tokio::fs::remove_dir_all(&service.config.attachment_dir).await;
+    }
+}
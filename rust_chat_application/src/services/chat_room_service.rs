@@ -1,35 +1,792 @@
-use crate::repository::chat_room_repo::ChatRoomRepository;
+use chat_protocol::{
+    api::{
+        ChatRoomDetails, CreateInviteRequest, RoomInvite, RoomListQuery, RoomRole, RoomSummary, RoomVisibility,
+        UpdateChatRoomRequest,
+    },
+    ws::ChatMessage,
+};
+use rand::{distributions::Alphanumeric, Rng};
+
+use crate::repository::store::SharedStore;
+
+/// Largest page of history a client may ask for at once
+const MAX_HISTORY_PAGE_SIZE: u32 = 200;
+/// Rooms listed at once when the client does not ask for a page size
+const DEFAULT_ROOM_PAGE_SIZE: u32 = 20;
+/// Largest page of rooms a client may ask for at once
+const MAX_ROOM_PAGE_SIZE: u32 = 100;
+/// Longest room name, and room name search, as stored in `ChatRooms.room_name`
+const MAX_ROOM_NAME_CHARS: usize = 100;
+/// Longest description, as stored in `ChatRooms.description`
+const MAX_DESCRIPTION_CHARS: usize = 500;
+/// Longest topic, as stored in `ChatRooms.topic`
+const MAX_TOPIC_CHARS: usize = 255;
+/// Length of the token in an invite link, as stored in `RoomInvites.token`
+const INVITE_TOKEN_LEN: usize = 32;
+/// Longest an invite may stay valid when it expires at all, in seconds (30 days)
+const MAX_INVITE_LIFETIME_SECS: i64 = 30 * 24 * 60 * 60;
+/// Most users one link invite may let in, when it is limited at all
+const MAX_INVITE_USES: u32 = 10_000;
+
+/// Something a member may do in a room, as checked by `ChatRoomService::check_permission`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Permission {
+    /// Read the room's messages and members
+    View,
+    /// Post messages, react to them, and change one's own messages
+    Post,
+    /// Edit and delete other members' messages
+    ModerateMessages,
+    ManageInvites,
+    /// Kick, ban and mute members below one's own role, set slow mode and read the
+    /// moderation log
+    ModerateMembers,
+    /// Promote and demote members below one's own role
+    ManageRoles,
+    TransferOwnership,
+    /// Rename, describe, archive and delete the room
+    ManageRoom,
+}
+
+impl Permission {
+    /// The least privileged role with this permission
+    fn min_role(self) -> RoomRole {
+        match self {
+            Permission::View => RoomRole::ReadOnly,
+            Permission::Post => RoomRole::Member,
+            Permission::ModerateMessages | Permission::ModerateMembers => RoomRole::Moderator,
+            Permission::ManageInvites | Permission::ManageRoles => RoomRole::Admin,
+            Permission::TransferOwnership | Permission::ManageRoom => RoomRole::Owner,
+        }
+    }
+
+    /// The error returned to members without this permission
+    fn denied(self) -> String {
+        match self {
+            Permission::View => "Not a member of this chat room",
+            Permission::Post => "Not allowed to post in this chat room",
+            Permission::ModerateMessages => "Not allowed to change this message",
+            Permission::ModerateMembers => "Not allowed to moderate this chat room",
+            Permission::ManageInvites => "Not allowed to manage invites",
+            Permission::ManageRoles => "Not allowed to change roles",
+            Permission::TransferOwnership => "Only the owner can transfer the chat room",
+            Permission::ManageRoom => "Only the owner can change the chat room",
+        }
+        .to_string()
+    }
+}
 
 pub struct ChatRoomService {
-    repository: ChatRoomRepository,
+    repository: SharedStore,
 }
 
 impl ChatRoomService {
-    pub fn new() -> Self {
-        let repository = ChatRoomRepository::new();
+    pub fn new(repository: SharedStore) -> Self {
         ChatRoomService { repository }
     }
 
-    pub async fn create_chat_room(&self, room_name: String, created_by: i32) -> Result<i32, String> {
-        self.repository.create_chat_room(&room_name, created_by).await
+    /// Create a room owned by its creator
+    pub async fn create_chat_room(&self, room_name: String, created_by: i32, visibility: RoomVisibility) -> Result<i32, String> {
+        let room_name = validate_room_name(&room_name)?;
+        let private = visibility == RoomVisibility::Private;
+        let chatroom_id = self.repository.create_chat_room(room_name, created_by, private).await?;
+        self.repository.add_user_to_chat_room(created_by, chatroom_id, RoomRole::Owner).await?;
+        Ok(chatroom_id)
     }
 
+    /// Join a room, which for a private room uses up an invite for the user. Joining a
+    /// room again is not an error.
     pub async fn join_chat_room(&self, user_id: i32, chatroom_id: i32) -> Result<String, String> {
         // First check if the room exists
         if !self.repository.does_room_exist(chatroom_id).await? {
             return Err("Chat room not found".to_string());
         }
+        if self.repository.is_banned(chatroom_id, user_id).await? {
+            return Err("Banned from this chat room".to_string());
+        }
+
+        // Direct conversations are named after the other participant
+        if let Some(users) = self.repository.get_direct_chat_users(chatroom_id).await? {
+            self.check_access(user_id, chatroom_id).await?;
+            self.rejoin_chat_room(user_id, chatroom_id).await?;
+            let other = if users.0 == user_id { users.1 } else { users.0 };
+            return Ok(self.repository.get_username(other).await?.unwrap_or_default());
+        }
         
         // Get the room name
         let room_name = self.repository.get_room_name(chatroom_id).await?;
+        if self.repository.fetch_user_list(chatroom_id).await?.contains(&user_id) {
+            return Ok(room_name);
+        }
+
+        // Private rooms look like missing ones to users without an invite
+        if self.repository.is_room_private(chatroom_id).await? {
+            let role = self.joining_role(user_id, chatroom_id).await?;
+            if !self.repository.join_with_user_invite(chatroom_id, user_id, role).await? {
+                return Err("Chat room not found".to_string());
+            }
+            return Ok(room_name);
+        }
         
         // If room exists, proceed with joining and propagate any potential error
-        self.repository.add_user_to_chat_room(user_id, chatroom_id).await?;
+        self.rejoin_chat_room(user_id, chatroom_id).await?;
         
         Ok(room_name)
     }
 
+    /// Join the room an invite link points to and return its (chatroom_id, room_name).
+    /// Members and banned users do not use the invite up.
+    pub async fn accept_invite(&self, user_id: i32, token: &str) -> Result<(i32, String), String> {
+        let not_found = || "Invite not found or expired".to_string();
+        let chatroom_id = self.repository.find_invite_link(token).await?.ok_or_else(not_found)?;
+        if self.repository.is_banned(chatroom_id, user_id).await? {
+            return Err("Banned from this chat room".to_string());
+        }
+        if !self.repository.fetch_user_list(chatroom_id).await?.contains(&user_id)
+            && !self
+                .repository
+                .join_with_invite_link(token, user_id, self.joining_role(user_id, chatroom_id).await?)
+                .await?
+        {
+            // Used up or revoked since it was looked up
+            return Err(not_found());
+        }
+        Ok((chatroom_id, self.repository.get_room_name(chatroom_id).await?))
+    }
+
+    /// Add the user back to a room they were in, e.g. when a dropped socket reconnects
+    pub async fn rejoin_chat_room(&self, user_id: i32, chatroom_id: i32) -> Result<(), String> {
+        if self.repository.fetch_user_list(chatroom_id).await?.contains(&user_id) {
+            return Ok(());
+        }
+        let role = self.joining_role(user_id, chatroom_id).await?;
+        self.repository.add_user_to_chat_room(user_id, chatroom_id, role).await
+    }
+
+    /// The role a user joins a room with: a member, unless they were made read-only in it
+    /// before they left
+    async fn joining_role(&self, user_id: i32, chatroom_id: i32) -> Result<RoomRole, String> {
+        if self.repository.is_read_only(chatroom_id, user_id).await? {
+            return Ok(RoomRole::ReadOnly);
+        }
+        Ok(RoomRole::Member)
+    }
+
+    /// Leave a room. Its owner has to hand it over first, so no room is left without one.
     pub async fn leave_chat_room(&self, user_id: i32, chatroom_id: i32) -> Result<(), String> {
+        if self.repository.get_member_role(user_id, chatroom_id).await? == Some(RoomRole::Owner) {
+            return Err("Transfer the chat room before leaving it".to_string());
+        }
         self.repository.remove_user_from_chat_room(user_id, chatroom_id).await
     }
+
+    /// Fails unless the room exists and the user may open it. Direct conversations look
+    /// like missing rooms to anyone but their two participants, and private rooms to
+    /// anyone but their members. Banned users may not open the room at all.
+    pub async fn check_access(&self, user_id: i32, chatroom_id: i32) -> Result<(), String> {
+        if !self.repository.does_room_exist(chatroom_id).await? {
+            return Err("Chat room not found".to_string());
+        }
+        if self.repository.is_banned(chatroom_id, user_id).await? {
+            return Err("Banned from this chat room".to_string());
+        }
+        match self.repository.get_direct_chat_users(chatroom_id).await? {
+            Some((user_low, user_high)) if user_id != user_low && user_id != user_high => {
+                Err("Chat room not found".to_string())
+            }
+            Some(_) => Ok(()),
+            None if self.repository.is_room_private(chatroom_id).await?
+                && !self.repository.fetch_user_list(chatroom_id).await?.contains(&user_id) =>
+            {
+                Err("Chat room not found".to_string())
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Fails unless the room exists and the user's role in it grants `permission`, and
+    /// returns that role. The participants of a direct conversation are members of it
+    /// and nobody has a higher role there. Muted members lose `Permission::Post`.
+    pub async fn check_permission(&self, user_id: i32, chatroom_id: i32, permission: Permission) -> Result<RoomRole, String> {
+        self.check_member(user_id, chatroom_id).await?;
+        let role = self
+            .repository
+            .get_member_role(user_id, chatroom_id)
+            .await?
+            .unwrap_or(RoomRole::Member);
+        if role < permission.min_role() {
+            return Err(permission.denied());
+        }
+        if matches!(permission, Permission::Post | Permission::ModerateMessages)
+            && self.repository.is_room_archived(chatroom_id).await?
+        {
+            return Err("This chat room is archived".to_string());
+        }
+        if permission == Permission::Post && self.repository.is_muted(chatroom_id, user_id).await? {
+            return Err("You are muted in this chat room".to_string());
+        }
+        Ok(role)
+    }
+
+    /// The room's name, description, topic and state, for anyone who may open the room
+    pub async fn get_room_details(&self, user_id: i32, chatroom_id: i32) -> Result<ChatRoomDetails, String> {
+        self.check_access(user_id, chatroom_id).await?;
+        self.repository.fetch_room_details(chatroom_id).await
+    }
+
+    /// Rename the room or change its description or topic, and return the result
+    pub async fn update_chat_room(
+        &self,
+        user_id: i32,
+        chatroom_id: i32,
+        request: UpdateChatRoomRequest,
+    ) -> Result<ChatRoomDetails, String> {
+        self.check_permission(user_id, chatroom_id, Permission::ManageRoom).await?;
+        let mut details = self.repository.fetch_room_details(chatroom_id).await?;
+        if let Some(room_name) = &request.room_name {
+            details.room_name = validate_room_name(room_name)?.to_string();
+        }
+        if let Some(description) = &request.description {
+            details.description = optional_text(description, MAX_DESCRIPTION_CHARS, "Description is too long")?;
+        }
+        if let Some(topic) = &request.topic {
+            details.topic = optional_text(topic, MAX_TOPIC_CHARS, "Topic is too long")?;
+        }
+
+        self.repository
+            .update_chat_room(chatroom_id, &details.room_name, details.description.as_deref(), details.topic.as_deref())
+            .await
+            .map_err(|e| if e.contains("Duplicate entry") { "Chat room name already exists".to_string() } else { e })?;
+        Ok(details)
+    }
+
+    /// Archive the room, leaving it readable but closed to new messages, or reopen it
+    pub async fn set_archived(&self, user_id: i32, chatroom_id: i32, archived: bool) -> Result<ChatRoomDetails, String> {
+        self.check_permission(user_id, chatroom_id, Permission::ManageRoom).await?;
+        self.repository.set_room_archived(chatroom_id, archived).await?;
+        self.repository.fetch_room_details(chatroom_id).await
+    }
+
+    /// Delete the room and everything in it for good
+    pub async fn delete_chat_room(&self, user_id: i32, chatroom_id: i32) -> Result<(), String> {
+        self.check_permission(user_id, chatroom_id, Permission::ManageRoom).await?;
+        self.repository.delete_chat_room(chatroom_id).await
+    }
+
+    /// Every member of a room as (user_id, username, role, last_read_message_id), most
+    /// privileged first
+    pub async fn list_members(&self, user_id: i32, chatroom_id: i32) -> Result<Vec<(i32, String, RoomRole, Option<i32>)>, String> {
+        self.check_permission(user_id, chatroom_id, Permission::View).await?;
+        self.repository.fetch_members(chatroom_id).await
+    }
+
+    /// Promote or demote another member. Admins manage the roles below admin and the owner
+    /// also appoints admins; the owner itself only changes with `transfer_ownership`.
+    pub async fn set_member_role(&self, user_id: i32, chatroom_id: i32, member_id: i32, role: RoomRole) -> Result<(), String> {
+        let own_role = self.check_permission(user_id, chatroom_id, Permission::ManageRoles).await?;
+        if role == RoomRole::Owner {
+            return Err("Transfer the chat room to change its owner".to_string());
+        }
+        if member_id == user_id {
+            return Err("Cannot change your own role".to_string());
+        }
+        let current = self
+            .repository
+            .get_member_role(member_id, chatroom_id)
+            .await?
+            .ok_or_else(|| "User is not a member of this chat room".to_string())?;
+        if current >= own_role || role >= own_role {
+            return Err(Permission::ManageRoles.denied());
+        }
+
+        if !self.repository.set_member_role(member_id, chatroom_id, role).await? {
+            return Err("User is not a member of this chat room".to_string());
+        }
+        Ok(())
+    }
+
+    /// Make another member the owner of the room. The previous owner becomes an admin.
+    pub async fn transfer_ownership(&self, user_id: i32, chatroom_id: i32, new_owner: i32) -> Result<(), String> {
+        self.check_permission(user_id, chatroom_id, Permission::TransferOwnership).await?;
+        if new_owner == user_id {
+            return Err("You already own this chat room".to_string());
+        }
+        if self.repository.get_member_role(new_owner, chatroom_id).await?.is_none() {
+            return Err("User is not a member of this chat room".to_string());
+        }
+        self.repository.transfer_ownership(chatroom_id, user_id, new_owner).await
+    }
+
+    /// Fails unless the room exists and the user has joined it. The participants of a
+    /// direct conversation are always members.
+    async fn check_member(&self, user_id: i32, chatroom_id: i32) -> Result<(), String> {
+        self.check_access(user_id, chatroom_id).await?;
+        if self.repository.get_direct_chat_users(chatroom_id).await?.is_some() {
+            return Ok(());
+        }
+        if !self.repository.fetch_user_list(chatroom_id).await?.contains(&user_id) {
+            return Err("Not a member of this chat room".to_string());
+        }
+        Ok(())
+    }
+
+    /// Find or create the direct conversation between two users and join the caller to it.
+    /// Returns its chatroom_id and the other user's username.
+    pub async fn open_direct_chat(&self, user_id: i32, other_user_id: i32) -> Result<(i32, String), String> {
+        if user_id == other_user_id {
+            return Err("Cannot start a direct conversation with yourself".to_string());
+        }
+        let username = self
+            .repository
+            .get_username(other_user_id)
+            .await?
+            .ok_or_else(|| "User not found".to_string())?;
+
+        let chatroom_id = match self.repository.find_direct_chat(user_id, other_user_id).await? {
+            Some(chatroom_id) => chatroom_id,
+            None => match self.repository.create_direct_chat(user_id, other_user_id).await {
+                Ok(chatroom_id) => chatroom_id,
+                // The other user opened it at the same time
+                Err(e) if e.contains("Duplicate entry") => self
+                    .repository
+                    .find_direct_chat(user_id, other_user_id)
+                    .await?
+                    .ok_or(e)?,
+                Err(e) => return Err(e),
+            },
+        };
+        self.rejoin_chat_room(user_id, chatroom_id).await?;
+        Ok((chatroom_id, username))
+    }
+
+    /// Every direct conversation of a user as (chatroom_id, other user_id, other username)
+    pub async fn list_direct_chats(&self, user_id: i32) -> Result<Vec<(i32, i32, String)>, String> {
+        self.repository.fetch_direct_chats(user_id).await
+    }
+
+    /// One page of the public rooms matching `query`, and the number of matching rooms
+    pub async fn list_rooms(&self, query: RoomListQuery) -> Result<(Vec<RoomSummary>, u32), String> {
+        let search = query.search.as_deref().map(str::trim).filter(|search| !search.is_empty());
+        if search.is_some_and(|search| search.chars().count() > MAX_ROOM_NAME_CHARS) {
+            return Err("Search is too long".to_string());
+        }
+        let limit = query.limit.unwrap_or(DEFAULT_ROOM_PAGE_SIZE).clamp(1, MAX_ROOM_PAGE_SIZE);
+
+        self.repository
+            .list_public_rooms(search, query.sort.unwrap_or_default(), query.offset.unwrap_or_default(), limit)
+            .await
+    }
+
+    /// Every room the user has joined, including private ones but not direct conversations
+    pub async fn list_user_rooms(&self, user_id: i32) -> Result<Vec<RoomSummary>, String> {
+        self.repository.fetch_user_rooms(user_id).await
+    }
+
+    /// Invite a user to a room, or create a link invite when `request.user_id` is unset
+    pub async fn create_invite(&self, user_id: i32, chatroom_id: i32, request: CreateInviteRequest) -> Result<RoomInvite, String> {
+        self.check_can_manage_invites(user_id, chatroom_id).await?;
+        if request.expires_in.is_some_and(|secs| !(1..=MAX_INVITE_LIFETIME_SECS).contains(&secs))
+            || request.max_uses.is_some_and(|uses| !(1..=MAX_INVITE_USES).contains(&uses))
+        {
+            return Err("Invalid invite".to_string());
+        }
+
+        match request.user_id {
+            Some(invited) => {
+                if self.repository.get_username(invited).await?.is_none() {
+                    return Err("User not found".to_string());
+                }
+                self.repository
+                    .create_invite(chatroom_id, user_id, Some(invited), None, request.expires_in, Some(1))
+                    .await
+            }
+            None => {
+                let token: String = rand::thread_rng()
+                    .sample_iter(&Alphanumeric)
+                    .take(INVITE_TOKEN_LEN)
+                    .map(char::from)
+                    .collect();
+                self.repository
+                    .create_invite(chatroom_id, user_id, None, Some(&token), request.expires_in, request.max_uses)
+                    .await
+            }
+        }
+    }
+
+    pub async fn list_invites(&self, user_id: i32, chatroom_id: i32) -> Result<Vec<RoomInvite>, String> {
+        self.check_can_manage_invites(user_id, chatroom_id).await?;
+        self.repository.fetch_invites(chatroom_id).await
+    }
+
+    pub async fn revoke_invite(&self, user_id: i32, chatroom_id: i32, invite_id: i32) -> Result<(), String> {
+        self.check_can_manage_invites(user_id, chatroom_id).await?;
+        if !self.repository.delete_invite(chatroom_id, invite_id).await? {
+            return Err("Invite not found".to_string());
+        }
+        Ok(())
+    }
+
+    async fn check_can_manage_invites(&self, user_id: i32, chatroom_id: i32) -> Result<(), String> {
+        self.check_access(user_id, chatroom_id).await?;
+        if self.repository.get_direct_chat_users(chatroom_id).await?.is_some() {
+            return Err("Direct conversations have no invites".to_string());
+        }
+        self.check_permission(user_id, chatroom_id, Permission::ManageInvites).await?;
+        Ok(())
+    }
+
+    /// A page of the room's messages older than `before`, newest first, together with the
+    /// cursor for the page before it
+    pub async fn fetch_history(
+        &self,
+        chatroom_id: i32,
+        before: Option<i32>,
+        limit: u32,
+    ) -> Result<(Vec<ChatMessage>, Option<i32>), String> {
+        let limit = limit.clamp(1, MAX_HISTORY_PAGE_SIZE) as usize;
+
+        // Ask for one extra message to find out whether an older page exists
+        let mut messages = self.repository.fetch_chat_history(chatroom_id, before, limit as u32 + 1).await?;
+        let next_before = if messages.len() > limit {
+            messages.truncate(limit);
+            messages.last().map(|message| message.message_id)
+        } else {
+            None
+        };
+        Ok((messages, next_before))
+    }
+}
+
+/// The trimmed room name, if it is neither empty nor too long
+fn validate_room_name(room_name: &str) -> Result<&str, String> {
+    let room_name = room_name.trim();
+    if room_name.is_empty() {
+        return Err("Room name cannot be empty".to_string());
+    }
+    if room_name.chars().count() > MAX_ROOM_NAME_CHARS {
+        return Err("Room name is too long".to_string());
+    }
+    Ok(room_name)
+}
+
+/// The trimmed text, `None` if that leaves it empty, or `too_long` past `max_chars`
+fn optional_text(text: &str, max_chars: usize, too_long: &str) -> Result<Option<String>, String> {
+    let text = text.trim();
+    if text.chars().count() > max_chars {
+        return Err(too_long.to_string());
+    }
+    Ok(Some(text.to_string()).filter(|text| !text.is_empty()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chat_protocol::api::RoomSort;
+    use crate::services::test_support::{memory_store, post, room, sign_up};
+
+    #[tokio::test]
+    async fn creator_owns_the_room_and_others_join_it() {
+        let store = memory_store();
+        let alice = sign_up(&store, "alice").await;
+        let bob = sign_up(&store, "bob").await;
+        let service = ChatRoomService::new(store.clone());
+
+        let room = service.create_chat_room("  rust  ".into(), alice, RoomVisibility::Public).await.unwrap();
+        assert_eq!(store.get_room_name(room).await.unwrap(), "rust");
+        assert_eq!(store.get_member_role(alice, room).await.unwrap(), Some(RoomRole::Owner));
+
+        assert_eq!(service.join_chat_room(bob, room).await.unwrap(), "rust");
+        // Joining again is not an error
+        assert_eq!(service.join_chat_room(bob, room).await.unwrap(), "rust");
+        assert_eq!(store.get_member_role(bob, room).await.unwrap(), Some(RoomRole::Member));
+        assert_eq!(store.fetch_user_list(room).await.unwrap().len(), 2);
+
+        assert_eq!(service.join_chat_room(bob, room + 1).await, Err("Chat room not found".to_string()));
+        assert_eq!(
+            service.create_chat_room("   ".into(), alice, RoomVisibility::Public).await,
+            Err("Room name cannot be empty".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn owner_hands_the_room_over_before_leaving() {
+        let store = memory_store();
+        let alice = sign_up(&store, "alice").await;
+        let bob = sign_up(&store, "bob").await;
+        let service = ChatRoomService::new(store.clone());
+        let room = service.create_chat_room("rust".into(), alice, RoomVisibility::Public).await.unwrap();
+        service.join_chat_room(bob, room).await.unwrap();
+
+        service.leave_chat_room(bob, room).await.unwrap();
+        assert_eq!(store.fetch_user_list(room).await.unwrap(), vec![alice]);
+        assert_eq!(
+            service.leave_chat_room(alice, room).await,
+            Err("Transfer the chat room before leaving it".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn history_pages_back_from_the_newest_message() {
+        let store = memory_store();
+        let alice = sign_up(&store, "alice").await;
+        let service = ChatRoomService::new(store.clone());
+        let room = service.create_chat_room("rust".into(), alice, RoomVisibility::Public).await.unwrap();
+        let mut posted = Vec::new();
+        for n in 1..=5 {
+            posted.push(post(&store, room, alice, &format!("message {n}")).await);
+        }
+        let ids = |messages: &[ChatMessage]| messages.iter().map(|message| message.message_id).collect::<Vec<_>>();
+
+        let (page, next_before) = service.fetch_history(room, None, 2).await.unwrap();
+        assert_eq!(ids(&page), vec![posted[4], posted[3]]);
+        assert_eq!(next_before, Some(posted[3]));
+
+        let (page, next_before) = service.fetch_history(room, next_before, 2).await.unwrap();
+        assert_eq!(ids(&page), vec![posted[2], posted[1]]);
+
+        let (page, next_before) = service.fetch_history(room, next_before, 2).await.unwrap();
+        assert_eq!(ids(&page), vec![posted[0]]);
+        assert_eq!(next_before, None);
+
+        // The page size is kept within bounds
+        let (page, _) = service.fetch_history(room, None, 0).await.unwrap();
+        assert_eq!(page.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn direct_chats_are_shared_by_their_two_participants() {
+        let store = memory_store();
+        let alice = sign_up(&store, "alice").await;
+        let bob = sign_up(&store, "bob").await;
+        let mallory = sign_up(&store, "mallory").await;
+        let service = ChatRoomService::new(store.clone());
+
+        let (chat, other) = service.open_direct_chat(alice, bob).await.unwrap();
+        assert_eq!(other, "bob");
+        assert_eq!(service.open_direct_chat(bob, alice).await.unwrap(), (chat, "alice".to_string()));
+        assert_eq!(service.list_direct_chats(alice).await.unwrap(), vec![(chat, bob, "bob".to_string())]);
+        assert_eq!(service.join_chat_room(bob, chat).await.unwrap(), "alice");
+        assert_eq!(
+            service.open_direct_chat(alice, alice).await,
+            Err("Cannot start a direct conversation with yourself".to_string())
+        );
+
+        // To anyone else the conversation does not exist
+        assert_eq!(service.join_chat_room(mallory, chat).await, Err("Chat room not found".to_string()));
+        assert_eq!(
+            service.check_permission(mallory, chat, Permission::View).await,
+            Err("Chat room not found".to_string())
+        );
+        assert!(service.list_user_rooms(alice).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn invites_are_limited_and_only_used_by_newcomers() {
+        let store = memory_store();
+        let alice = sign_up(&store, "alice").await;
+        let bob = sign_up(&store, "bob").await;
+        let carol = sign_up(&store, "carol").await;
+        let mallory = sign_up(&store, "mallory").await;
+        let room = room(&store, alice, RoomVisibility::Private, &[bob]).await;
+        let service = ChatRoomService::new(store.clone());
+        let link = |expires_in, max_uses| CreateInviteRequest { user_id: None, expires_in, max_uses };
+
+        for request in [link(Some(0), None), link(Some(i64::MAX), None), link(None, Some(0)), link(None, Some(u32::MAX))] {
+            assert_eq!(service.create_invite(alice, room, request).await, Err("Invalid invite".to_string()));
+        }
+        assert_eq!(service.create_invite(bob, room, link(None, None)).await, Err("Not allowed to manage invites".to_string()));
+
+        let invite = service.create_invite(alice, room, link(Some(3600), Some(1))).await.unwrap();
+        let token = invite.token.unwrap();
+        // Neither a member nor a banned user takes the only use
+        assert_eq!(service.accept_invite(bob, &token).await.unwrap().0, room);
+        store.ban_user(room, mallory, alice, None).await.unwrap();
+        assert_eq!(service.accept_invite(mallory, &token).await, Err("Banned from this chat room".to_string()));
+        assert_eq!(service.list_invites(alice, room).await.unwrap()[0].uses, 0);
+
+        assert_eq!(service.accept_invite(carol, &token).await.unwrap().0, room);
+        assert!(store.fetch_user_list(room).await.unwrap().contains(&carol));
+        assert_eq!(service.list_invites(alice, room).await.unwrap()[0].uses, 1);
+        let dave = sign_up(&store, "dave").await;
+        assert_eq!(service.accept_invite(dave, &token).await, Err("Invite not found or expired".to_string()));
+
+        // A user invite lets its user join once
+        assert_eq!(service.join_chat_room(dave, room).await, Err("Chat room not found".to_string()));
+        service.create_invite(alice, room, CreateInviteRequest { user_id: Some(dave), ..link(None, None) }).await.unwrap();
+        assert!(service.join_chat_room(dave, room).await.is_ok());
+        assert_eq!(store.get_member_role(dave, room).await.unwrap(), Some(RoomRole::Member));
+
+        // A revoked invite's ID is not given to the next one
+        let revoked = service.create_invite(alice, room, link(None, None)).await.unwrap().invite_id;
+        service.revoke_invite(alice, room, revoked).await.unwrap();
+        let next = service.create_invite(alice, room, link(None, None)).await.unwrap().invite_id;
+        assert_ne!(next, revoked);
+        assert_eq!(service.revoke_invite(alice, room, revoked).await, Err("Invite not found".to_string()));
+        assert_eq!(service.list_invites(alice, room).await.unwrap().last().unwrap().invite_id, next);
+    }
+
+    #[tokio::test]
+    async fn roles_grant_permissions_from_their_level_up() {
+        let store = memory_store();
+        let alice = sign_up(&store, "alice").await;
+        let bob = sign_up(&store, "bob").await;
+        let carol = sign_up(&store, "carol").await;
+        let dave = sign_up(&store, "dave").await;
+        let room = room(&store, alice, RoomVisibility::Public, &[bob, carol, dave]).await;
+        let service = ChatRoomService::new(store.clone());
+
+        service.set_member_role(alice, room, bob, RoomRole::Admin).await.unwrap();
+        service.set_member_role(bob, room, carol, RoomRole::Moderator).await.unwrap();
+        assert_eq!(
+            service.set_member_role(carol, room, dave, RoomRole::ReadOnly).await,
+            Err("Not allowed to change roles".to_string())
+        );
+        service.set_member_role(bob, room, dave, RoomRole::ReadOnly).await.unwrap();
+
+        assert_eq!(service.check_permission(dave, room, Permission::View).await, Ok(RoomRole::ReadOnly));
+        assert_eq!(
+            service.check_permission(dave, room, Permission::Post).await,
+            Err("Not allowed to post in this chat room".to_string())
+        );
+        assert_eq!(service.check_permission(carol, room, Permission::ModerateMembers).await, Ok(RoomRole::Moderator));
+        assert_eq!(
+            service.check_permission(carol, room, Permission::ManageInvites).await,
+            Err("Not allowed to manage invites".to_string())
+        );
+        assert_eq!(
+            service.check_permission(bob, room, Permission::ManageRoom).await,
+            Err("Only the owner can change the chat room".to_string())
+        );
+
+        // Admins neither appoint admins nor touch each other
+        assert_eq!(
+            service.set_member_role(bob, room, carol, RoomRole::Admin).await,
+            Err("Not allowed to change roles".to_string())
+        );
+        assert_eq!(
+            service.set_member_role(bob, room, alice, RoomRole::Member).await,
+            Err("Not allowed to change roles".to_string())
+        );
+        assert_eq!(
+            service.set_member_role(alice, room, bob, RoomRole::Owner).await,
+            Err("Transfer the chat room to change its owner".to_string())
+        );
+
+        service.transfer_ownership(alice, room, bob).await.unwrap();
+        assert_eq!(store.get_member_role(bob, room).await.unwrap(), Some(RoomRole::Owner));
+        assert_eq!(store.get_member_role(alice, room).await.unwrap(), Some(RoomRole::Admin));
+        service.leave_chat_room(alice, room).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn read_only_members_stay_read_only_when_they_come_back() {
+        let store = memory_store();
+        let alice = sign_up(&store, "alice").await;
+        let bob = sign_up(&store, "bob").await;
+        let room = room(&store, alice, RoomVisibility::Public, &[bob]).await;
+        let service = ChatRoomService::new(store.clone());
+        service.set_member_role(alice, room, bob, RoomRole::ReadOnly).await.unwrap();
+
+        service.leave_chat_room(bob, room).await.unwrap();
+        service.join_chat_room(bob, room).await.unwrap();
+        assert_eq!(store.get_member_role(bob, room).await.unwrap(), Some(RoomRole::ReadOnly));
+        // Reconnecting a socket rejoins the room the same way
+        service.leave_chat_room(bob, room).await.unwrap();
+        service.rejoin_chat_room(bob, room).await.unwrap();
+        assert_eq!(
+            service.check_permission(bob, room, Permission::Post).await,
+            Err("Not allowed to post in this chat room".to_string())
+        );
+
+        // Giving them another role lifts it for good
+        service.set_member_role(alice, room, bob, RoomRole::Member).await.unwrap();
+        service.leave_chat_room(bob, room).await.unwrap();
+        service.join_chat_room(bob, room).await.unwrap();
+        assert_eq!(store.get_member_role(bob, room).await.unwrap(), Some(RoomRole::Member));
+    }
+
+    #[tokio::test]
+    async fn public_rooms_are_searched_sorted_and_paged() {
+        let store = memory_store();
+        let alice = sign_up(&store, "alice").await;
+        let bob = sign_up(&store, "bob").await;
+        let service = ChatRoomService::new(store.clone());
+        let rust = service.create_chat_room("Rust".into(), alice, RoomVisibility::Public).await.unwrap();
+        let rustaceans = service.create_chat_room("rustaceans".into(), alice, RoomVisibility::Public).await.unwrap();
+        let go = service.create_chat_room("go".into(), alice, RoomVisibility::Public).await.unwrap();
+        service.create_chat_room("rust-private".into(), alice, RoomVisibility::Private).await.unwrap();
+        service.join_chat_room(bob, rustaceans).await.unwrap();
+        post(&store, go, alice, "newest").await;
+        let list = |search: &str, sort, offset, limit| {
+            let query = RoomListQuery { search: Some(search.to_string()), sort: Some(sort), offset: Some(offset), limit: Some(limit) };
+            service.list_rooms(query)
+        };
+        let ids = |rooms: Vec<RoomSummary>| rooms.iter().map(|room| room.room_id).collect::<Vec<_>>();
+
+        let (rooms, total) = list(" RUST ", RoomSort::Name, 0, 10).await.unwrap();
+        assert_eq!((ids(rooms), total), (vec![rust, rustaceans], 2));
+        let (rooms, _) = list("", RoomSort::Members, 0, 1).await.unwrap();
+        assert_eq!(ids(rooms), vec![rustaceans]);
+        let (rooms, total) = list("", RoomSort::Activity, 1, 2).await.unwrap();
+        assert_eq!(total, 3);
+        assert_eq!(rooms.len(), 2);
+        assert_eq!(ids(list("", RoomSort::Activity, 0, 1).await.unwrap().0), vec![go]);
+
+        assert_eq!(
+            service.list_rooms(RoomListQuery { search: Some("r".repeat(200)), ..RoomListQuery::default() }).await,
+            Err("Search is too long".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn owners_update_archive_and_delete_their_rooms() {
+        let store = memory_store();
+        let alice = sign_up(&store, "alice").await;
+        let bob = sign_up(&store, "bob").await;
+        let service = ChatRoomService::new(store.clone());
+        let room = service.create_chat_room("rust".into(), alice, RoomVisibility::Public).await.unwrap();
+        let go = service.create_chat_room("go".into(), alice, RoomVisibility::Public).await.unwrap();
+        service.join_chat_room(bob, room).await.unwrap();
+        let update = |room_name: Option<&str>, topic: Option<&str>| UpdateChatRoomRequest {
+            room_name: room_name.map(str::to_string),
+            description: None,
+            topic: topic.map(str::to_string),
+        };
+
+        let details = service.update_chat_room(alice, room, update(Some(" rustaceans "), Some("Release day"))).await.unwrap();
+        assert_eq!((details.room_name.as_str(), details.topic.as_deref()), ("rustaceans", Some("Release day")));
+        // An empty topic clears it and a missing one leaves it alone
+        let details = service.update_chat_room(alice, room, update(None, Some(""))).await.unwrap();
+        assert_eq!((details.room_name.as_str(), details.topic), ("rustaceans", None));
+        assert_eq!(
+            service.update_chat_room(alice, room, update(Some("go"), None)).await,
+            Err("Chat room name already exists".to_string())
+        );
+        assert_eq!(
+            service.update_chat_room(bob, room, update(None, Some("mine now"))).await,
+            Err("Only the owner can change the chat room".to_string())
+        );
+
+        assert!(service.set_archived(alice, room, true).await.unwrap().archived);
+        assert_eq!(
+            service.check_permission(bob, room, Permission::Post).await,
+            Err("This chat room is archived".to_string())
+        );
+        assert_eq!(service.check_permission(bob, room, Permission::View).await, Ok(RoomRole::Member));
+        assert!(!service.set_archived(alice, room, false).await.unwrap().archived);
+
+        assert_eq!(service.delete_chat_room(bob, room).await, Err("Only the owner can change the chat room".to_string()));
+        let kept = post(&store, go, alice, "before").await;
+        post(&store, room, bob, "gone soon").await;
+        let last_deleted = post(&store, room, bob, "gone too").await;
+        service.delete_chat_room(alice, room).await.unwrap();
+        assert_eq!(service.get_room_details(alice, room).await, Err("Chat room not found".to_string()));
+
+        // Message IDs are not handed out again once a room's messages are gone
+        let after = post(&store, go, alice, "after").await;
+        assert!(after > last_deleted);
+        let (history, _) = service.fetch_history(go, None, 10).await.unwrap();
+        let ids: Vec<i32> = history.iter().map(|message| message.message_id).collect();
+        assert_eq!(ids, vec![after, kept]);
+    }
 }
@@ -0,0 +1,345 @@
+//! Processing of uploaded images: reading their dimensions, removing their metadata and
+//! scaling them down into thumbnails
+
+use std::io::Cursor;
+
+use image::{
+    codecs::jpeg::JpegEncoder, metadata::Orientation, DynamicImage, ImageDecoder, ImageFormat, ImageReader, Limits,
+};
+
+/// Widest and tallest image decoded, in pixels
+const MAX_IMAGE_DIMENSION: u32 = 12_000;
+/// Memory a decoder may allocate, in bytes
+const MAX_DECODE_BYTES: u64 = 256 * 1024 * 1024;
+/// JPEG quality of thumbnails and of photos turned upright
+const JPEG_QUALITY: u8 = 85;
+
+/// An uploaded image ready to be stored
+pub struct ProcessedImage {
+    /// The image without its metadata
+    pub contents: Vec<u8>,
+    pub width: u32,
+    pub height: u32,
+    /// Smallest first
+    pub thumbnails: Vec<ThumbnailFile>,
+}
+
+pub struct ThumbnailFile {
+    /// The configured size the thumbnail was made for, the longest side it may have
+    pub max_size: u32,
+    pub width: u32,
+    pub height: u32,
+    pub content_type: &'static str,
+    pub contents: Vec<u8>,
+}
+
+/// Decode an image of `content_type`, remove the metadata that cameras and phones put in
+/// it, such as EXIF with GPS positions, and make a thumbnail for each of `thumbnail_sizes`
+/// that is smaller than the image. This is CPU bound, so call it off the async runtime.
+pub fn process_image(content_type: &str, contents: &[u8], thumbnail_sizes: &[u32]) -> Result<ProcessedImage, String> {
+    let invalid = || format!("The attachment is not a valid {content_type} file");
+    let format = ImageFormat::from_mime_type(content_type).ok_or_else(invalid)?;
+
+    let mut reader = ImageReader::with_format(Cursor::new(contents), format);
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_IMAGE_DIMENSION);
+    limits.max_image_height = Some(MAX_IMAGE_DIMENSION);
+    limits.max_alloc = Some(MAX_DECODE_BYTES);
+    reader.limits(limits);
+    let mut decoder = reader.into_decoder().map_err(|_| invalid())?;
+    let orientation = decoder.orientation().unwrap_or(Orientation::NoTransforms);
+    let mut image = DynamicImage::from_decoder(decoder).map_err(|_| invalid())?;
+
+    let contents = if orientation == Orientation::NoTransforms {
+        match strip_metadata(format, contents) {
+            Some(contents) => contents,
+            None => encode(&image, format)?,
+        }
+    } else {
+        // Without its orientation tag the photo would show up sideways, so turn it upright
+        image.apply_orientation(orientation);
+        encode(&image, format)?
+    };
+
+    let mut sizes = thumbnail_sizes.to_vec();
+    sizes.sort_unstable();
+    sizes.dedup();
+    let mut thumbnails = Vec::new();
+    for max_size in sizes {
+        if image.width().max(image.height()) <= max_size {
+            break;
+        }
+        let thumbnail = image.thumbnail(max_size, max_size);
+        // Keep transparency where there is some, anything else is smaller as a JPEG
+        let (content_type, format) = if thumbnail.color().has_alpha() {
+            ("image/png", ImageFormat::Png)
+        } else {
+            ("image/jpeg", ImageFormat::Jpeg)
+        };
+        thumbnails.push(ThumbnailFile {
+            max_size,
+            width: thumbnail.width(),
+            height: thumbnail.height(),
+            content_type,
+            contents: encode(&thumbnail, format)?,
+        });
+    }
+
+    Ok(ProcessedImage { contents, width: image.width(), height: image.height(), thumbnails })
+}
+
+fn encode(image: &DynamicImage, format: ImageFormat) -> Result<Vec<u8>, String> {
+    let mut contents = Vec::new();
+    let result = match format {
+        ImageFormat::Jpeg => DynamicImage::ImageRgb8(image.to_rgb8())
+            .write_with_encoder(JpegEncoder::new_with_quality(&mut contents, JPEG_QUALITY)),
+        ImageFormat::WebP => DynamicImage::ImageRgba8(image.to_rgba8()).write_to(Cursor::new(&mut contents), format),
+        format => image.write_to(Cursor::new(&mut contents), format),
+    };
+    result.map_err(|e| format!("Failed to process image: {e}"))?;
+    Ok(contents)
+}
+
+/// The file without its metadata, leaving the image data untouched, so GIF animations
+/// survive. `None` if the file is laid out in a way this does not follow, in which case
+/// it has to be encoded again.
+fn strip_metadata(format: ImageFormat, contents: &[u8]) -> Option<Vec<u8>> {
+    match format {
+        ImageFormat::Jpeg => strip_jpeg(contents),
+        ImageFormat::Png => strip_png(contents),
+        ImageFormat::WebP => strip_webp(contents),
+        ImageFormat::Gif => strip_gif(contents),
+        _ => None,
+    }
+}
+
+/// Drop the APP1 (EXIF and XMP), APP13 (IPTC) and comment segments of a JPEG. The colour
+/// profile in APP2 and the Adobe segment in APP14 change how the image looks, so they stay.
+fn strip_jpeg(contents: &[u8]) -> Option<Vec<u8>> {
+    if !contents.starts_with(&[0xff, 0xd8]) {
+        return None;
+    }
+    let mut stripped = vec![0xff, 0xd8];
+    let mut position = 2;
+    loop {
+        if *contents.get(position)? != 0xff {
+            return None;
+        }
+        let marker = *contents.get(position + 1)?;
+        match marker {
+            // Padding before a marker
+            0xff => position += 1,
+            // Markers without a length
+            0x01 | 0xd0..=0xd7 => {
+                stripped.extend_from_slice(&contents[position..position + 2]);
+                position += 2;
+            }
+            // Start of scan: the compressed image data follows, up to the end of the file
+            0xda => {
+                stripped.extend_from_slice(&contents[position..]);
+                return Some(stripped);
+            }
+            _ => {
+                let length = u16::from_be_bytes([*contents.get(position + 2)?, *contents.get(position + 3)?]) as usize;
+                let end = position + 2 + length;
+                let segment = contents.get(position..end)?;
+                if !matches!(marker, 0xe1 | 0xed | 0xfe) {
+                    stripped.extend_from_slice(segment);
+                }
+                position = end;
+            }
+        }
+    }
+}
+
+/// Drop the EXIF, text and timestamp chunks of a PNG
+fn strip_png(contents: &[u8]) -> Option<Vec<u8>> {
+    const SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+    let mut stripped = SIGNATURE.to_vec();
+    let mut position = SIGNATURE.len();
+    loop {
+        let length = u32::from_be_bytes(contents.get(position..position + 4)?.try_into().ok()?) as usize;
+        let kind = contents.get(position + 4..position + 8)?;
+        // Length, type, data and CRC
+        let end = position + 12 + length;
+        let chunk = contents.get(position..end)?;
+        if !matches!(kind, b"eXIf" | b"tEXt" | b"zTXt" | b"iTXt" | b"tIME") {
+            stripped.extend_from_slice(chunk);
+        }
+        if kind == b"IEND" {
+            return Some(stripped);
+        }
+        position = end;
+    }
+}
+
+/// Drop the EXIF and XMP chunks of a WebP, and their flags in the extended header
+fn strip_webp(contents: &[u8]) -> Option<Vec<u8>> {
+    if contents.get(..4)? != b"RIFF" || contents.get(8..12)? != b"WEBP" {
+        return None;
+    }
+    let mut stripped = contents[..12].to_vec();
+    let mut position = 12;
+    while position < contents.len() {
+        let kind = contents.get(position..position + 4)?;
+        let length = u32::from_le_bytes(contents.get(position + 4..position + 8)?.try_into().ok()?) as usize;
+        // Chunks are padded to an even length
+        let end = (position + 8 + length + (length & 1)).min(contents.len());
+        let chunk = contents.get(position..end)?;
+        match kind {
+            b"EXIF" | b"XMP " => {}
+            b"VP8X" if chunk.len() > 8 => {
+                stripped.extend_from_slice(&chunk[..8]);
+                stripped.push(chunk[8] & !0b1100);
+                stripped.extend_from_slice(&chunk[9..]);
+            }
+            _ => stripped.extend_from_slice(chunk),
+        }
+        position = end;
+    }
+    let riff_size = u32::try_from(stripped.len() - 8).ok()?;
+    stripped[4..8].copy_from_slice(&riff_size.to_le_bytes());
+    Some(stripped)
+}
+
+/// Drop the comment extensions of a GIF and its application extensions, such as XMP, except
+/// the ones that loop animations or carry a colour profile
+fn strip_gif(contents: &[u8]) -> Option<Vec<u8>> {
+    if !contents.starts_with(b"GIF87a") && !contents.starts_with(b"GIF89a") {
+        return None;
+    }
+    // Header, screen descriptor and global colour table
+    let mut position = 13 + colour_table_len(*contents.get(10)?);
+    let mut stripped = contents.get(..position)?.to_vec();
+    loop {
+        match *contents.get(position)? {
+            // Extension: its label, then data sub-blocks
+            0x21 => {
+                let label = *contents.get(position + 1)?;
+                let end = skip_sub_blocks(contents, position + 2)?;
+                // The first sub-block of an application extension names the application
+                let application = contents.get(position + 3..position + 14);
+                let keep = match label {
+                    0xfe => false,
+                    0xff => matches!(application, Some(b"NETSCAPE2.0" | b"ANIMEXTS1.0" | b"ICCRGBG1012")),
+                    _ => true,
+                };
+                if keep {
+                    stripped.extend_from_slice(&contents[position..end]);
+                }
+                position = end;
+            }
+            // Image: its descriptor, local colour table, LZW code size and data sub-blocks
+            0x2c => {
+                let data = position + 10 + colour_table_len(*contents.get(position + 9)?) + 1;
+                let end = skip_sub_blocks(contents, data)?;
+                stripped.extend_from_slice(&contents[position..end]);
+                position = end;
+            }
+            // Trailer
+            0x3b => {
+                stripped.push(0x3b);
+                return Some(stripped);
+            }
+            _ => return None,
+        }
+    }
+}
+
+/// Length of the colour table a GIF descriptor's packed field announces
+fn colour_table_len(packed: u8) -> usize {
+    if packed & 0x80 == 0 {
+        0
+    } else {
+        3 << ((packed & 0x07) + 1)
+    }
+}
+
+/// Where the GIF sub-blocks starting at `position` end, after their empty terminator
+fn skip_sub_blocks(contents: &[u8], mut position: usize) -> Option<usize> {
+    loop {
+        let length = *contents.get(position)? as usize;
+        position += 1 + length;
+        if length == 0 {
+            return Some(position);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::codecs::gif::{GifDecoder, GifEncoder, Repeat};
+    use image::{AnimationDecoder, Frame, Rgba, RgbaImage};
+
+    fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+        haystack.windows(needle.len()).any(|window| window == needle)
+    }
+
+    #[test]
+    fn png_text_is_dropped_and_thumbnails_made() {
+        let mut png = Vec::new();
+        DynamicImage::ImageRgb8(image::RgbImage::new(64, 48))
+            .write_to(Cursor::new(&mut png), ImageFormat::Png)
+            .unwrap();
+        // A tEXt chunk right after IHDR
+        let text = b"Commentshot at home";
+        let mut chunk = (text.len() as u32).to_be_bytes().to_vec();
+        chunk.extend_from_slice(b"tEXt");
+        chunk.extend_from_slice(text);
+        chunk.extend_from_slice(&crc32(&chunk[4..]).to_be_bytes());
+        png.splice(33..33, chunk);
+
+        let processed = process_image("image/png", &png, &[100, 16, 32, 16]).unwrap();
+        assert!(!contains(&processed.contents, b"shot at home"));
+        assert_eq!((processed.width, processed.height), (64, 48));
+        let sizes: Vec<(u32, u32, u32)> =
+            processed.thumbnails.iter().map(|thumbnail| (thumbnail.max_size, thumbnail.width, thumbnail.height)).collect();
+        assert_eq!(sizes, vec![(16, 16, 12), (32, 32, 24)]);
+        assert_eq!(processed.thumbnails[0].content_type, "image/jpeg");
+
+        assert_eq!(
+            process_image("image/png", b"\x89PNG\r\n\x1a\nnope", &[]).err(),
+            Some("The attachment is not a valid image/png file".to_string())
+        );
+    }
+
+    #[test]
+    fn gif_comments_and_xmp_are_dropped_but_frames_kept() {
+        let frames = [Rgba([255, 0, 0, 255]), Rgba([0, 0, 255, 255])]
+            .map(|colour| Frame::new(RgbaImage::from_pixel(8, 8, colour)));
+        let mut gif = Vec::new();
+        {
+            let mut encoder = GifEncoder::new(&mut gif);
+            encoder.set_repeat(Repeat::Infinite).unwrap();
+            encoder.encode_frames(frames).unwrap();
+        }
+        // A comment and an XMP extension after the screen descriptor and colour table
+        let mut metadata = vec![0x21, 0xfe, 12];
+        metadata.extend_from_slice(b"shot at home");
+        metadata.extend_from_slice(&[0x00, 0x21, 0xff, 11]);
+        metadata.extend_from_slice(b"XMP DataXMP");
+        metadata.extend_from_slice(&[4, b'<', b'x', b'/', b'>', 0x00]);
+        let header = 13 + colour_table_len(gif[10]);
+        gif.splice(header..header, metadata);
+
+        let processed = process_image("image/gif", &gif, &[]).unwrap();
+        assert!(!contains(&processed.contents, b"shot at home"));
+        assert!(!contains(&processed.contents, b"XMP DataXMP"));
+        assert!(contains(&processed.contents, b"NETSCAPE2.0"));
+        let decoder = GifDecoder::new(Cursor::new(&processed.contents)).unwrap();
+        assert_eq!(decoder.into_frames().count(), 2);
+    }
+
+    /// The CRC of a PNG chunk's type and data
+    fn crc32(bytes: &[u8]) -> u32 {
+        let mut crc = !0u32;
+        for byte in bytes {
+            crc ^= u32::from(*byte);
+            for _ in 0..8 {
+                crc = if crc & 1 == 1 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+            }
+        }
+        !crc
+    }
+}
@@ -0,0 +1,543 @@
+use std::collections::{HashMap, HashSet};
+
+use chat_protocol::{
+    api::{MessageSearchHit, MessageSearchQuery, RoomRole, SnippetPart},
+    ws::{Attachment, ChatMessage},
+};
+use chrono::{DateTime, Utc};
+
+use crate::repository::store::{matches_search_term, search_words, MessageSearch, SharedStore};
+use crate::services::chat_room_service::{ChatRoomService, Permission};
+
+/// Most attachments one message may carry
+const MAX_MESSAGE_ATTACHMENTS: usize = 10;
+/// Longest reaction accepted, in characters. Emoji built from several code points, such as
+/// flags and family groups, need more than one.
+const MAX_REACTION_CHARS: usize = 16;
+/// Longest search accepted, in characters
+const MAX_SEARCH_CHARS: usize = 200;
+/// Shortest word a search matches on, as MySQL's `innodb_ft_min_token_size`
+const MIN_SEARCH_TERM_CHARS: usize = 3;
+/// Page size of search results when the client does not ask for one
+const DEFAULT_SEARCH_PAGE_SIZE: u32 = 20;
+/// Largest page of search results a client may ask for at once
+const MAX_SEARCH_PAGE_SIZE: u32 = 100;
+/// Characters shown before the first match in a snippet
+const SNIPPET_LEAD_CHARS: usize = 40;
+/// Length of a snippet, in characters
+const SNIPPET_CHARS: usize = 160;
+
+pub struct MessageService {
+    repository: SharedStore,
+}
+
+impl MessageService {
+    pub fn new(repository: SharedStore) -> Self {
+        MessageService { repository }
+    }
+
+    /// Store a message posted to a room, as a reply when `reply_to` is set, and return it
+    /// with its server-assigned `message_id` and timestamp. `attachments` are files the
+    /// user uploaded to the room and has not sent yet.
+    pub async fn post_message(
+        &self,
+        chatroom_id: i32,
+        user_id: i32,
+        username: String,
+        content: String,
+        reply_to: Option<i32>,
+        attachments: Vec<i32>,
+    ) -> Result<ChatMessage, String> {
+        if content.trim().is_empty() && attachments.is_empty() {
+            return Err("Message cannot be empty".to_string());
+        }
+        let role = self.rooms().check_permission(user_id, chatroom_id, Permission::Post).await?;
+        if role < RoomRole::Moderator {
+            self.check_slow_mode(chatroom_id, user_id).await?;
+        }
+        let thread_root_id = match reply_to {
+            Some(reply_to) => Some(self.thread_root_of(chatroom_id, reply_to).await?),
+            None => None,
+        };
+        let attachments = self.unsent_attachments(chatroom_id, user_id, attachments).await?;
+
+        let timestamp = Utc::now();
+        let message_id = self
+            .repository
+            .insert_message(chatroom_id, user_id, &content, timestamp, reply_to, thread_root_id)
+            .await?;
+        let attachment_ids: Vec<i32> = attachments.iter().map(|attachment| attachment.attachment_id).collect();
+        self.repository.link_attachments(message_id, &attachment_ids).await?;
+        Ok(ChatMessage {
+            message_id,
+            user_id,
+            username,
+            content,
+            timestamp,
+            edited_at: None,
+            deleted: false,
+            reply_to,
+            thread_root_id,
+            reply_count: 0,
+            reactions: Vec::new(),
+            attachments,
+        })
+    }
+
+    /// A thread as (chatroom_id, root, replies), given its root or any reply in it
+    pub async fn fetch_thread(&self, message_id: i32) -> Result<(i32, ChatMessage, Vec<ChatMessage>), String> {
+        let (chatroom_id, message) = self
+            .repository
+            .fetch_message(message_id)
+            .await?
+            .ok_or_else(|| "Message not found".to_string())?;
+        let root = match message.thread_root_id {
+            Some(root_id) => self
+                .repository
+                .fetch_message(root_id)
+                .await?
+                .map(|(_, root)| root)
+                .ok_or_else(|| "Message not found".to_string())?,
+            None => message,
+        };
+
+        let replies = self.repository.fetch_thread_replies(root.message_id).await?;
+        Ok((chatroom_id, root, replies))
+    }
+
+    /// Replace the content of a message and return (chatroom_id, edited_at)
+    pub async fn edit_message(
+        &self,
+        user_id: i32,
+        message_id: i32,
+        content: String,
+    ) -> Result<(i32, DateTime<Utc>), String> {
+        if content.trim().is_empty() {
+            return Err("Message cannot be empty".to_string());
+        }
+        let chatroom_id = self.check_can_modify(user_id, message_id).await?;
+
+        let edited_at = Utc::now();
+        self.repository.edit_message(message_id, user_id, &content, edited_at).await?;
+        Ok((chatroom_id, edited_at))
+    }
+
+    /// Delete a message, leaving a tombstone, and return its chatroom_id
+    pub async fn delete_message(&self, user_id: i32, message_id: i32) -> Result<i32, String> {
+        let chatroom_id = self.check_can_modify(user_id, message_id).await?;
+        self.repository.delete_message(message_id, user_id, Utc::now()).await?;
+        Ok(chatroom_id)
+    }
+
+    /// Earlier versions of a message as (chatroom_id, edits), for members of its room
+    pub async fn fetch_message_edits(
+        &self,
+        message_id: i32,
+    ) -> Result<(i32, Vec<(String, Option<i32>, DateTime<Utc>)>), String> {
+        let (chatroom_id, _, _) = self
+            .repository
+            .get_message(message_id)
+            .await?
+            .ok_or_else(|| "Message not found".to_string())?;
+        Ok((chatroom_id, self.repository.fetch_message_edits(message_id).await?))
+    }
+
+    /// React to a message in `chatroom_id`. Returns false if the user had already reacted
+    /// with this emoji, in which case there is nothing to broadcast.
+    pub async fn add_reaction(&self, chatroom_id: i32, user_id: i32, message_id: i32, emoji: &str) -> Result<bool, String> {
+        if emoji.is_empty() || emoji.chars().count() > MAX_REACTION_CHARS || emoji.contains(char::is_whitespace) {
+            return Err("Invalid reaction".to_string());
+        }
+        self.rooms().check_permission(user_id, chatroom_id, Permission::Post).await?;
+        match self.repository.get_message(message_id).await? {
+            Some((room, _, false)) if room == chatroom_id => {}
+            Some((room, _, true)) if room == chatroom_id => return Err("Message has been deleted".to_string()),
+            _ => return Err("Message not found".to_string()),
+        }
+        self.repository.add_reaction(message_id, user_id, emoji).await
+    }
+
+    /// Take back a reaction to a message in `chatroom_id`. Returns false if there was none.
+    pub async fn remove_reaction(&self, chatroom_id: i32, user_id: i32, message_id: i32, emoji: &str) -> Result<bool, String> {
+        self.rooms().check_permission(user_id, chatroom_id, Permission::Post).await?;
+        match self.repository.get_message(message_id).await? {
+            Some((room, _, _)) if room == chatroom_id => self.repository.remove_reaction(message_id, user_id, emoji).await,
+            _ => Err("Message not found".to_string()),
+        }
+    }
+
+    /// Record that the user has read `chatroom_id` up to a message in it. Returns false if
+    /// they had already read that far, in which case there is no receipt to broadcast.
+    pub async fn mark_read(&self, chatroom_id: i32, user_id: i32, message_id: i32) -> Result<bool, String> {
+        self.rooms().check_permission(user_id, chatroom_id, Permission::View).await?;
+        match self.repository.get_message(message_id).await? {
+            Some((room, _, _)) if room == chatroom_id => self.repository.mark_read(user_id, chatroom_id, message_id).await,
+            _ => Err("Message not found".to_string()),
+        }
+    }
+
+    /// One page of the messages matching `query` in the rooms the user belongs to, newest
+    /// first, and the number of matching messages
+    pub async fn search_messages(&self, user_id: i32, query: MessageSearchQuery) -> Result<(Vec<MessageSearchHit>, u32), String> {
+        let terms = search_terms(&query.q)?;
+        if query.from.zip(query.to).is_some_and(|(from, to)| from > to) {
+            return Err("The date range ends before it starts".to_string());
+        }
+        if let Some(chatroom_id) = query.room_id {
+            self.rooms().check_permission(user_id, chatroom_id, Permission::View).await?;
+        }
+
+        let search = MessageSearch {
+            terms: &terms,
+            chatroom_id: query.room_id,
+            sender_id: query.sender_id,
+            from: query.from,
+            to: query.to,
+        };
+        let limit = query.limit.unwrap_or(DEFAULT_SEARCH_PAGE_SIZE).clamp(1, MAX_SEARCH_PAGE_SIZE);
+        let (messages, total) = self
+            .repository
+            .search_messages(user_id, &search, query.offset.unwrap_or_default(), limit)
+            .await?;
+
+        // Direct conversations are not listed and keep no name
+        let room_names: HashMap<i32, String> = self
+            .repository
+            .fetch_user_rooms(user_id)
+            .await?
+            .into_iter()
+            .map(|room| (room.room_id, room.room_name))
+            .collect();
+        let hits = messages
+            .into_iter()
+            .map(|(room_id, message)| MessageSearchHit {
+                room_id,
+                room_name: room_names.get(&room_id).cloned(),
+                snippet: snippet(&message.content, &terms),
+                message,
+            })
+            .collect();
+        Ok((hits, total))
+    }
+
+    /// Fails if slow mode is on and the user posted to the room too recently
+    async fn check_slow_mode(&self, chatroom_id: i32, user_id: i32) -> Result<(), String> {
+        let interval = self.repository.get_slow_mode(chatroom_id).await?;
+        if interval == 0 {
+            return Ok(());
+        }
+        if let Some(last) = self.repository.last_message_at(chatroom_id, user_id).await? {
+            let wait = i64::from(interval) - (Utc::now() - last).num_seconds();
+            if wait > 0 {
+                return Err(format!("Slow mode is on, wait {wait} more seconds"));
+            }
+        }
+        Ok(())
+    }
+
+    /// The thread a reply to `reply_to` belongs to. Replies to a reply join the thread of
+    /// the message it replies to, so threads stay one level deep.
+    async fn thread_root_of(&self, chatroom_id: i32, reply_to: i32) -> Result<i32, String> {
+        match self.repository.fetch_message(reply_to).await? {
+            Some((room, parent)) if room == chatroom_id => Ok(parent.thread_root_id.unwrap_or(parent.message_id)),
+            _ => Err("The message being replied to is not in this chat room".to_string()),
+        }
+    }
+
+    /// The attachments to send with a message, which the user must have uploaded to the room
+    /// and not sent yet
+    async fn unsent_attachments(&self, chatroom_id: i32, user_id: i32, mut attachment_ids: Vec<i32>) -> Result<Vec<Attachment>, String> {
+        let mut seen = HashSet::new();
+        attachment_ids.retain(|attachment_id| seen.insert(*attachment_id));
+        if attachment_ids.len() > MAX_MESSAGE_ATTACHMENTS {
+            return Err(format!("A message can carry at most {MAX_MESSAGE_ATTACHMENTS} attachments"));
+        }
+
+        let mut attachments = Vec::with_capacity(attachment_ids.len());
+        for attachment_id in attachment_ids {
+            match self.repository.fetch_attachment(attachment_id).await? {
+                Some(stored) if stored.chatroom_id == chatroom_id && stored.uploader_id == Some(user_id) => {
+                    if stored.message_id.is_some() {
+                        return Err("The attachment has already been sent".to_string());
+                    }
+                    attachments.push(stored.attachment);
+                }
+                _ => return Err("Attachment not found".to_string()),
+            }
+        }
+        Ok(attachments)
+    }
+
+    /// Authors who may still post can change their messages, and moderators can change
+    /// anyone's. Returns the message's chatroom_id.
+    async fn check_can_modify(&self, user_id: i32, message_id: i32) -> Result<i32, String> {
+        let (chatroom_id, sender_id, deleted) = self
+            .repository
+            .get_message(message_id)
+            .await?
+            .ok_or_else(|| "Message not found".to_string())?;
+        if deleted {
+            return Err("Message has been deleted".to_string());
+        }
+        let permission = if sender_id == user_id { Permission::Post } else { Permission::ModerateMessages };
+        self.rooms().check_permission(user_id, chatroom_id, permission).await?;
+        Ok(chatroom_id)
+    }
+
+    /// Permission checks go through the chat room service
+    fn rooms(&self) -> ChatRoomService {
+        ChatRoomService::new(self.repository.clone())
+    }
+}
+
+/// The distinct lowercase words of a search that are long enough to match on
+fn search_terms(search: &str) -> Result<Vec<String>, String> {
+    if search.chars().count() > MAX_SEARCH_CHARS {
+        return Err("Search is too long".to_string());
+    }
+    let mut terms: Vec<String> = Vec::new();
+    for (_, word) in search_words(search) {
+        let term = word.to_lowercase();
+        if term.chars().count() >= MIN_SEARCH_TERM_CHARS && !terms.contains(&term) {
+            terms.push(term);
+        }
+    }
+    if terms.is_empty() {
+        return Err(format!("Search for words of at least {MIN_SEARCH_TERM_CHARS} characters"));
+    }
+    Ok(terms)
+}
+
+/// Up to `SNIPPET_CHARS` of `text` starting a little before the first match, split into
+/// plain and highlighted parts
+fn snippet(text: &str, terms: &[String]) -> Vec<SnippetPart> {
+    let matches: Vec<(usize, usize)> = search_words(text)
+        .filter(|(_, word)| matches_search_term(word, terms))
+        .map(|(start, word)| (start, start + word.len()))
+        .collect();
+    // Byte offsets of the part shown
+    let first = matches.first().map_or(0, |(start, _)| *start);
+    let start = text[..first].char_indices().rev().nth(SNIPPET_LEAD_CHARS - 1).map_or(0, |(i, _)| i);
+    let end = text[start..].char_indices().nth(SNIPPET_CHARS).map_or(text.len(), |(i, _)| start + i);
+
+    let part = |text: &str, highlight: bool| SnippetPart { text: text.to_string(), highlight };
+    let mut parts = Vec::new();
+    if start > 0 {
+        parts.push(part("…", false));
+    }
+    let mut position = start;
+    for (match_start, match_end) in matches.into_iter().take_while(|(match_start, _)| *match_start < end) {
+        if match_start > position {
+            parts.push(part(&text[position..match_start], false));
+        }
+        let match_end = match_end.min(end);
+        parts.push(part(&text[match_start..match_end], true));
+        position = match_end;
+    }
+    if position < end {
+        parts.push(part(&text[position..end], false));
+    }
+    if end < text.len() {
+        parts.push(part("…", false));
+    }
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chat_protocol::api::RoomVisibility;
+    use crate::services::test_support::{memory_store, post, room, sign_up};
+
+    #[tokio::test]
+    async fn server_assigns_message_ids_and_timestamps() {
+        let store = memory_store();
+        let alice = sign_up(&store, "alice").await;
+        let room = room(&store, alice, RoomVisibility::Public, &[]).await;
+        let service = MessageService::new(store.clone());
+
+        let before = Utc::now();
+        let first = service.post_message(room, alice, "alice".into(), "hi".into(), None, Vec::new()).await.unwrap();
+        let second = service.post_message(room, alice, "alice".into(), "again".into(), None, Vec::new()).await.unwrap();
+        assert!(second.message_id > first.message_id);
+        assert!(first.timestamp >= before && first.timestamp <= Utc::now());
+        assert_eq!((first.user_id, first.username.as_str()), (alice, "alice"));
+
+        assert_eq!(
+            service.post_message(room, alice, "alice".into(), "  ".into(), None, Vec::new()).await,
+            Err("Message cannot be empty".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn only_members_post() {
+        let store = memory_store();
+        let alice = sign_up(&store, "alice").await;
+        let mallory = sign_up(&store, "mallory").await;
+        let room = room(&store, alice, RoomVisibility::Public, &[]).await;
+        let service = MessageService::new(store.clone());
+
+        assert_eq!(
+            service.post_message(room, mallory, "mallory".into(), "hi".into(), None, Vec::new()).await,
+            Err("Not a member of this chat room".to_string())
+        );
+        assert!(store.fetch_chat_history(room, None, 10).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn authors_edit_and_delete_their_messages() {
+        let store = memory_store();
+        let alice = sign_up(&store, "alice").await;
+        let bob = sign_up(&store, "bob").await;
+        let carol = sign_up(&store, "carol").await;
+        let room = room(&store, alice, RoomVisibility::Public, &[bob, carol]).await;
+        let service = MessageService::new(store.clone());
+        let message_id = post(&store, room, bob, "helo").await;
+
+        assert_eq!(service.edit_message(bob, message_id, "hello".into()).await.unwrap().0, room);
+        service.edit_message(bob, message_id, "hello!".into()).await.unwrap();
+        assert_eq!(
+            service.edit_message(carol, message_id, "hijacked".into()).await,
+            Err("Not allowed to change this message".to_string())
+        );
+
+        let (_, edits) = service.fetch_message_edits(message_id).await.unwrap();
+        let previous: Vec<&str> = edits.iter().map(|(text, _, _)| text.as_str()).collect();
+        assert_eq!(previous, vec!["helo", "hello"]);
+        let message = store.fetch_chat_history(room, None, 1).await.unwrap().remove(0);
+        assert_eq!(message.content, "hello!");
+        assert!(message.edited_at.is_some());
+
+        assert_eq!(service.delete_message(carol, message_id).await, Err("Not allowed to change this message".to_string()));
+        service.delete_message(bob, message_id).await.unwrap();
+        assert!(store.fetch_chat_history(room, None, 1).await.unwrap()[0].deleted);
+        assert_eq!(
+            service.edit_message(bob, message_id, "back".into()).await,
+            Err("Message has been deleted".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn replies_join_the_thread_of_their_root() {
+        let store = memory_store();
+        let alice = sign_up(&store, "alice").await;
+        let bob = sign_up(&store, "bob").await;
+        let other_room = room(&store, alice, RoomVisibility::Public, &[]).await;
+        let room = room(&store, alice, RoomVisibility::Public, &[bob]).await;
+        let service = MessageService::new(store.clone());
+        let root = post(&store, room, alice, "lunch?").await;
+
+        let reply = service.post_message(room, bob, "bob".into(), "sure".into(), Some(root), Vec::new()).await.unwrap();
+        assert_eq!((reply.reply_to, reply.thread_root_id), (Some(root), Some(root)));
+        let nested = service
+            .post_message(room, alice, "alice".into(), "noon".into(), Some(reply.message_id), Vec::new())
+            .await
+            .unwrap();
+        assert_eq!((nested.reply_to, nested.thread_root_id), (Some(reply.message_id), Some(root)));
+        assert_eq!(
+            service.post_message(other_room, alice, "alice".into(), "hm".into(), Some(root), Vec::new()).await,
+            Err("The message being replied to is not in this chat room".to_string())
+        );
+
+        let (chatroom_id, thread_root, replies) = service.fetch_thread(nested.message_id).await.unwrap();
+        assert_eq!((chatroom_id, thread_root.message_id, thread_root.reply_count), (room, root, 2));
+        let reply_ids: Vec<i32> = replies.iter().map(|message| message.message_id).collect();
+        assert_eq!(reply_ids, vec![reply.message_id, nested.message_id]);
+    }
+
+    #[tokio::test]
+    async fn reactions_are_counted_once_per_user() {
+        let store = memory_store();
+        let alice = sign_up(&store, "alice").await;
+        let bob = sign_up(&store, "bob").await;
+        let room = room(&store, alice, RoomVisibility::Public, &[bob]).await;
+        let service = MessageService::new(store.clone());
+        let message_id = post(&store, room, alice, "shipped").await;
+
+        assert_eq!(service.add_reaction(room, alice, message_id, "🎉").await, Ok(true));
+        assert_eq!(service.add_reaction(room, alice, message_id, "🎉").await, Ok(false));
+        assert_eq!(service.add_reaction(room, bob, message_id, "🎉").await, Ok(true));
+        assert_eq!(service.add_reaction(room, bob, message_id, "two words").await, Err("Invalid reaction".to_string()));
+        let reactions = store.fetch_chat_history(room, None, 1).await.unwrap().remove(0).reactions;
+        assert_eq!(reactions.len(), 1);
+        assert_eq!((reactions[0].emoji.as_str(), reactions[0].count), ("🎉", 2));
+
+        assert_eq!(service.remove_reaction(room, bob, message_id, "🎉").await, Ok(true));
+        assert_eq!(service.remove_reaction(room, bob, message_id, "🎉").await, Ok(false));
+        let reactions = store.fetch_chat_history(room, None, 1).await.unwrap().remove(0).reactions;
+        assert_eq!(reactions[0].user_ids, vec![alice]);
+
+        service.delete_message(alice, message_id).await.unwrap();
+        assert_eq!(service.add_reaction(room, bob, message_id, "👍").await, Err("Message has been deleted".to_string()));
+    }
+
+    #[tokio::test]
+    async fn search_finds_word_prefixes_in_the_users_rooms() {
+        let store = memory_store();
+        let alice = sign_up(&store, "alice").await;
+        let bob = sign_up(&store, "bob").await;
+        let mallory = sign_up(&store, "mallory").await;
+        let elsewhere = room(&store, mallory, RoomVisibility::Public, &[]).await;
+        let room = room(&store, alice, RoomVisibility::Public, &[bob]).await;
+        let service = MessageService::new(store.clone());
+        let notes = post(&store, room, alice, "The release NOTES are out").await;
+        let older = post(&store, room, bob, "notes on the release party").await;
+        post(&store, room, bob, "unrelated").await;
+        post(&store, elsewhere, mallory, "release notes, leaked").await;
+        let search = |q: &str| MessageSearchQuery { q: q.to_string(), ..MessageSearchQuery::default() };
+        let ids = |hits: &[MessageSearchHit]| hits.iter().map(|hit| hit.message.message_id).collect::<Vec<_>>();
+
+        let (hits, total) = service.search_messages(alice, search("rele note")).await.unwrap();
+        assert_eq!((ids(&hits), total), (vec![older, notes], 2));
+        let highlighted: Vec<&str> = hits[1].snippet.iter().filter(|part| part.highlight).map(|part| part.text.as_str()).collect();
+        assert_eq!(highlighted, vec!["release", "NOTES"]);
+
+        let from_alice = MessageSearchQuery { sender_id: Some(alice), ..search("release") };
+        assert_eq!(ids(&service.search_messages(bob, from_alice).await.unwrap().0), vec![notes]);
+        let paged = MessageSearchQuery { offset: Some(1), limit: Some(1), ..search("release") };
+        let (hits, total) = service.search_messages(alice, paged).await.unwrap();
+        assert_eq!((ids(&hits), total), (vec![notes], 2));
+
+        assert_eq!(
+            service.search_messages(alice, MessageSearchQuery { room_id: Some(elsewhere), ..search("release") }).await,
+            Err("Not a member of this chat room".to_string())
+        );
+        assert_eq!(
+            service.search_messages(alice, search("of an")).await,
+            Err("Search for words of at least 3 characters".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn unread_counts_follow_what_members_have_read() {
+        let store = memory_store();
+        let alice = sign_up(&store, "alice").await;
+        let bob = sign_up(&store, "bob").await;
+        let carol = sign_up(&store, "carol").await;
+        let room = room(&store, alice, RoomVisibility::Public, &[bob]).await;
+        let service = MessageService::new(store.clone());
+        let rooms = &ChatRoomService::new(store.clone());
+        let unread = |user_id| async move { rooms.list_user_rooms(user_id).await.unwrap()[0].unread_count };
+
+        let first = post(&store, room, alice, "one").await;
+        let second = post(&store, room, alice, "two").await;
+        let third = post(&store, room, alice, "three").await;
+        // Own messages, thread replies and deleted messages are not waiting to be read
+        post(&store, room, bob, "mine").await;
+        service.post_message(room, alice, "alice".into(), "aside".into(), Some(first), Vec::new()).await.unwrap();
+        service.delete_message(alice, third).await.unwrap();
+        assert_eq!(unread(bob).await, Some(2));
+
+        assert_eq!(service.mark_read(room, bob, second).await, Ok(true));
+        assert_eq!(unread(bob).await, Some(0));
+        assert_eq!(service.mark_read(room, bob, first).await, Ok(false));
+        assert_eq!(unread(bob).await, Some(0));
+        assert_eq!(service.mark_read(room, carol, second).await, Err("Not a member of this chat room".to_string()));
+
+        // Members who join later have read everything before them
+        rooms.join_chat_room(carol, room).await.unwrap();
+        assert_eq!(unread(carol).await, Some(0));
+        post(&store, room, alice, "welcome").await;
+        assert_eq!(unread(carol).await, Some(1));
+    }
+}
@@ -1,2 +1,10 @@
+pub mod attachment_service;
 pub mod chat_room_service;
+pub mod media;
+pub mod message_service;
+pub mod moderation_service;
+pub mod presence;
+#[cfg(test)]
+mod test_support;
+pub mod typing;
 pub mod user_auth_service;
@@ -0,0 +1,201 @@
+use chat_protocol::api::{ModerationAction, ModerationLogEntry, ModerationRequest};
+use chrono::{DateTime, Duration, Utc};
+
+use crate::repository::store::SharedStore;
+use crate::services::chat_room_service::{ChatRoomService, Permission};
+
+/// Longest reason a moderator can give, as stored in `ModerationLog.reason`
+const MAX_REASON_CHARS: usize = 255;
+/// Longest slow mode interval, in seconds
+const MAX_SLOW_MODE_SECS: u32 = 6 * 60 * 60;
+/// Longest timed mute, in seconds (30 days). Mutes without a duration last until unmuted.
+const MAX_MUTE_SECS: i64 = 30 * 24 * 60 * 60;
+/// Most moderation log entries returned at once
+const MODERATION_LOG_LIMIT: u32 = 100;
+
+pub struct ModerationService {
+    repository: SharedStore,
+}
+
+impl ModerationService {
+    pub fn new(repository: SharedStore) -> Self {
+        Self { repository }
+    }
+
+    /// Remove a member from the room. They may come back like any other user.
+    pub async fn kick(&self, moderator_id: i32, chatroom_id: i32, request: &ModerationRequest) -> Result<(), String> {
+        self.check_can_moderate(moderator_id, chatroom_id, request).await?;
+        if self.repository.get_member_role(request.user_id, chatroom_id).await?.is_none() {
+            return Err("User is not a member of this chat room".to_string());
+        }
+
+        self.repository.remove_user_from_chat_room(request.user_id, chatroom_id).await?;
+        self.log(chatroom_id, ModerationAction::Kick, moderator_id, request).await
+    }
+
+    /// Remove a user from the room and keep them out until they are unbanned. Users can
+    /// be banned before they ever join.
+    pub async fn ban(&self, moderator_id: i32, chatroom_id: i32, request: &ModerationRequest) -> Result<(), String> {
+        self.check_can_moderate(moderator_id, chatroom_id, request).await?;
+        if self.repository.get_username(request.user_id).await?.is_none() {
+            return Err("User not found".to_string());
+        }
+
+        self.repository
+            .ban_user(chatroom_id, request.user_id, moderator_id, request.reason.as_deref())
+            .await?;
+        self.repository.remove_user_from_chat_room(request.user_id, chatroom_id).await?;
+        self.log(chatroom_id, ModerationAction::Ban, moderator_id, request).await
+    }
+
+    pub async fn unban(&self, moderator_id: i32, chatroom_id: i32, user_id: i32) -> Result<(), String> {
+        self.rooms().check_permission(moderator_id, chatroom_id, Permission::ModerateMembers).await?;
+        if !self.repository.unban_user(chatroom_id, user_id).await? {
+            return Err("User is not banned".to_string());
+        }
+        self.repository
+            .log_moderation(chatroom_id, ModerationAction::Unban, moderator_id, Some(user_id), None, None)
+            .await
+    }
+
+    /// Stop a member from posting, for `request.duration` seconds or until they are
+    /// unmuted. Returns when the mute ends.
+    pub async fn mute(
+        &self,
+        moderator_id: i32,
+        chatroom_id: i32,
+        request: &ModerationRequest,
+    ) -> Result<Option<DateTime<Utc>>, String> {
+        self.check_can_moderate(moderator_id, chatroom_id, request).await?;
+        let ends_at = match request.duration {
+            Some(duration) if (1..=MAX_MUTE_SECS).contains(&duration) => Duration::try_seconds(duration)
+                .and_then(|duration| Utc::now().checked_add_signed(duration))
+                .map(Some)
+                .ok_or_else(|| "Invalid duration".to_string())?,
+            Some(_) => return Err("Invalid duration".to_string()),
+            None => None,
+        };
+        if self.repository.get_member_role(request.user_id, chatroom_id).await?.is_none() {
+            return Err("User is not a member of this chat room".to_string());
+        }
+
+        self.repository
+            .mute_user(chatroom_id, request.user_id, moderator_id, request.duration)
+            .await?;
+        self.log(chatroom_id, ModerationAction::Mute, moderator_id, request).await?;
+        Ok(ends_at)
+    }
+
+    pub async fn unmute(&self, moderator_id: i32, chatroom_id: i32, user_id: i32) -> Result<(), String> {
+        self.rooms().check_permission(moderator_id, chatroom_id, Permission::ModerateMembers).await?;
+        if !self.repository.unmute_user(chatroom_id, user_id).await? {
+            return Err("User is not muted".to_string());
+        }
+        self.repository
+            .log_moderation(chatroom_id, ModerationAction::Unmute, moderator_id, Some(user_id), None, None)
+            .await
+    }
+
+    /// Limit members below moderator to one message every `interval` seconds. Zero
+    /// turns slow mode off.
+    pub async fn set_slow_mode(&self, moderator_id: i32, chatroom_id: i32, interval: u32) -> Result<(), String> {
+        self.rooms().check_permission(moderator_id, chatroom_id, Permission::ModerateMembers).await?;
+        if interval > MAX_SLOW_MODE_SECS {
+            return Err("Invalid interval".to_string());
+        }
+
+        self.repository.set_slow_mode(chatroom_id, interval).await?;
+        self.repository
+            .log_moderation(chatroom_id, ModerationAction::SlowMode, moderator_id, None, None, Some(i64::from(interval)))
+            .await
+    }
+
+    /// The latest moderation actions in the room, newest first
+    pub async fn fetch_log(&self, user_id: i32, chatroom_id: i32) -> Result<Vec<ModerationLogEntry>, String> {
+        self.rooms().check_permission(user_id, chatroom_id, Permission::ModerateMembers).await?;
+        self.repository.fetch_moderation_log(chatroom_id, MODERATION_LOG_LIMIT).await
+    }
+
+    /// Fails unless the moderator may act on `request.user_id`: only users with a lower
+    /// role than the moderator's own can be kicked, banned or muted.
+    async fn check_can_moderate(&self, moderator_id: i32, chatroom_id: i32, request: &ModerationRequest) -> Result<(), String> {
+        let own_role = self
+            .rooms()
+            .check_permission(moderator_id, chatroom_id, Permission::ModerateMembers)
+            .await?;
+        if request.reason.as_ref().is_some_and(|reason| reason.chars().count() > MAX_REASON_CHARS) {
+            return Err("Reason is too long".to_string());
+        }
+        if request.user_id == moderator_id {
+            return Err("Cannot moderate yourself".to_string());
+        }
+        if let Some(role) = self.repository.get_member_role(request.user_id, chatroom_id).await? {
+            if role >= own_role {
+                return Err("Not allowed to moderate this user".to_string());
+            }
+        }
+        Ok(())
+    }
+
+    async fn log(&self, chatroom_id: i32, action: ModerationAction, moderator_id: i32, request: &ModerationRequest) -> Result<(), String> {
+        self.repository
+            .log_moderation(
+                chatroom_id,
+                action,
+                moderator_id,
+                Some(request.user_id),
+                request.reason.as_deref(),
+                request.duration,
+            )
+            .await
+    }
+
+    /// Permission checks go through the chat room service
+    fn rooms(&self) -> ChatRoomService {
+        ChatRoomService::new(self.repository.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chat_protocol::api::{RoomRole, RoomVisibility};
+    use crate::services::test_support::{memory_store, room, sign_up};
+
+    fn mute_for(user_id: i32, duration: Option<i64>) -> ModerationRequest {
+        ModerationRequest { user_id, reason: None, duration }
+    }
+
+    #[tokio::test]
+    async fn mutes_end_after_their_duration() {
+        let store = memory_store();
+        let alice = sign_up(&store, "alice").await;
+        let bob = sign_up(&store, "bob").await;
+        let room = room(&store, alice, RoomVisibility::Public, &[bob]).await;
+        let service = ModerationService::new(store.clone());
+        let rooms = ChatRoomService::new(store.clone());
+
+        for duration in [0, -1, MAX_MUTE_SECS + 1, i64::MAX] {
+            assert_eq!(service.mute(alice, room, &mute_for(bob, Some(duration))).await, Err("Invalid duration".to_string()));
+        }
+        assert!(!store.is_muted(room, bob).await.unwrap());
+
+        let ends_at = service.mute(alice, room, &mute_for(bob, Some(MAX_MUTE_SECS))).await.unwrap().unwrap();
+        assert!(ends_at > Utc::now() + Duration::days(29));
+        assert_eq!(
+            rooms.check_permission(bob, room, Permission::Post).await,
+            Err("You are muted in this chat room".to_string())
+        );
+        assert_eq!(service.mute(bob, room, &mute_for(alice, None)).await, Err("Not allowed to moderate this chat room".to_string()));
+
+        // A mute that has run out no longer counts
+        store.mute_user(room, bob, alice, Some(-60)).await.unwrap();
+        assert_eq!(rooms.check_permission(bob, room, Permission::Post).await, Ok(RoomRole::Member));
+        assert_eq!(service.unmute(alice, room, bob).await, Err("User is not muted".to_string()));
+
+        assert_eq!(service.mute(alice, room, &mute_for(bob, None)).await, Ok(None));
+        assert!(store.is_muted(room, bob).await.unwrap());
+        service.unmute(alice, room, bob).await.unwrap();
+        assert!(!store.is_muted(room, bob).await.unwrap());
+    }
+}
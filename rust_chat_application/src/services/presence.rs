@@ -0,0 +1,104 @@
+//! Presence of users, derived from their open WebSocket connections. A user is online while
+//! any of their devices is connected and active, away while every connection is idle, and
+//! offline once the last one closes.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use chat_protocol::ws::{PresenceStatus, UserStatus};
+
+#[derive(Default)]
+pub struct PresenceTracker {
+    /// The open connections of each user, and whether each of them is idle
+    connections: Mutex<HashMap<i32, HashMap<u64, bool>>>,
+    next_connection_id: AtomicU64,
+}
+
+impl PresenceTracker {
+    /// Register a new, active connection of `user_id`. Returns the id to report it by, and
+    /// the user's new status if it changed.
+    pub fn connect(&self, user_id: i32) -> (u64, Option<PresenceStatus>) {
+        let connection_id = self.next_connection_id.fetch_add(1, Ordering::Relaxed);
+        let mut connections = self.connections.lock().unwrap();
+        let before = status_of(connections.get(&user_id));
+        connections.entry(user_id).or_default().insert(connection_id, false);
+        (connection_id, changed(before, status_of(connections.get(&user_id))))
+    }
+
+    /// Mark a connection as idle or active again. Returns the user's new status if it changed.
+    pub fn set_idle(&self, user_id: i32, connection_id: u64, idle: bool) -> Option<PresenceStatus> {
+        let mut connections = self.connections.lock().unwrap();
+        let before = status_of(connections.get(&user_id));
+        if let Some(connection) = connections.get_mut(&user_id).and_then(|user| user.get_mut(&connection_id)) {
+            *connection = idle;
+        }
+        changed(before, status_of(connections.get(&user_id)))
+    }
+
+    /// Forget a closed connection. Returns the user's new status if it changed.
+    pub fn disconnect(&self, user_id: i32, connection_id: u64) -> Option<PresenceStatus> {
+        let mut connections = self.connections.lock().unwrap();
+        let before = status_of(connections.get(&user_id));
+        if let Some(user) = connections.get_mut(&user_id) {
+            user.remove(&connection_id);
+            if user.is_empty() {
+                connections.remove(&user_id);
+            }
+        }
+        changed(before, status_of(connections.get(&user_id)))
+    }
+
+    /// The status of each of `user_ids`, in the same order
+    pub fn statuses(&self, user_ids: &[i32]) -> Vec<UserStatus> {
+        let connections = self.connections.lock().unwrap();
+        user_ids
+            .iter()
+            .map(|&user_id| UserStatus { user_id, status: status_of(connections.get(&user_id)) })
+            .collect()
+    }
+}
+
+fn status_of(connections: Option<&HashMap<u64, bool>>) -> PresenceStatus {
+    match connections {
+        None => PresenceStatus::Offline,
+        Some(connections) if connections.is_empty() => PresenceStatus::Offline,
+        Some(connections) if connections.values().any(|idle| !idle) => PresenceStatus::Online,
+        Some(_) => PresenceStatus::Away,
+    }
+}
+
+fn changed(before: PresenceStatus, after: PresenceStatus) -> Option<PresenceStatus> {
+    (before != after).then_some(after)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn users_are_online_while_any_connection_is_active() {
+        let presence = PresenceTracker::default();
+        let (phone, status) = presence.connect(7);
+        assert_eq!(status, Some(PresenceStatus::Online));
+        let (laptop, status) = presence.connect(7);
+        assert_eq!(status, None);
+
+        assert_eq!(presence.set_idle(7, phone, true), None);
+        assert_eq!(presence.set_idle(7, laptop, true), Some(PresenceStatus::Away));
+        assert_eq!(presence.set_idle(7, laptop, true), None);
+        assert_eq!(presence.set_idle(7, phone, false), Some(PresenceStatus::Online));
+
+        assert_eq!(presence.disconnect(7, phone), Some(PresenceStatus::Away));
+        assert_eq!(
+            presence.statuses(&[7, 8]),
+            vec![
+                UserStatus { user_id: 7, status: PresenceStatus::Away },
+                UserStatus { user_id: 8, status: PresenceStatus::Offline },
+            ]
+        );
+        assert_eq!(presence.disconnect(7, laptop), Some(PresenceStatus::Offline));
+        // Closing a connection twice changes nothing
+        assert_eq!(presence.disconnect(7, laptop), None);
+    }
+}
@@ -0,0 +1,45 @@
+//! Helpers shared by the service tests, which run against `MemoryStore`
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use chat_protocol::api::RoomVisibility;
+
+use crate::repository::memory_store::MemoryStore;
+use crate::repository::store::SharedStore;
+use crate::services::chat_room_service::ChatRoomService;
+use crate::services::message_service::MessageService;
+
+pub fn memory_store() -> SharedStore {
+    Arc::new(MemoryStore::new())
+}
+
+/// Sign up a user straight in the store, skipping password hashing, and return their user_id
+pub async fn sign_up(store: &SharedStore, username: &str) -> i32 {
+    let email = format!("{username}@example.com");
+    store.user_sign_up(&email, username, "unused", 0).await.unwrap();
+    store.user_query(&email).await.unwrap().unwrap().0
+}
+
+/// Post a message to a room as `user_id` and return its message_id
+pub async fn post(store: &SharedStore, chatroom_id: i32, user_id: i32, content: &str) -> i32 {
+    let username = store.get_username(user_id).await.unwrap().unwrap_or_default();
+    MessageService::new(store.clone())
+        .post_message(chatroom_id, user_id, username, content.to_string(), None, Vec::new())
+        .await
+        .unwrap()
+        .message_id
+}
+
+/// Create a room of `visibility` owned by `owner` and add `members` to it
+pub async fn room(store: &SharedStore, owner: i32, visibility: RoomVisibility, members: &[i32]) -> i32 {
+    // Room names are unique
+    static ROOMS: AtomicUsize = AtomicUsize::new(0);
+    let name = format!("room {}", ROOMS.fetch_add(1, Ordering::Relaxed));
+    let rooms = ChatRoomService::new(store.clone());
+    let chatroom_id = rooms.create_chat_room(name, owner, visibility).await.unwrap();
+    for &member in members {
+        rooms.rejoin_chat_room(member, chatroom_id).await.unwrap();
+    }
+    chatroom_id
+}
@@ -0,0 +1,76 @@
+//! The typing indicator of one connection. Clients repeat the `typing` event while the user
+//! keeps typing; the room only hears when the user starts or stops, and a stop is sent for
+//! them if neither a repeat nor a stop arrives in time.
+
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+/// How long a user shows as typing after their last `typing` event
+pub const TYPING_TIMEOUT: Duration = Duration::from_secs(6);
+
+#[derive(Default)]
+pub struct TypingIndicator {
+    /// When the indicator runs out, while the user is shown as typing
+    until: Option<Instant>,
+}
+
+impl TypingIndicator {
+    /// Apply a `typing` event received at `now`. Repeated starts only push back the timeout.
+    /// Returns whether the room should hear about it.
+    pub fn update(&mut self, is_typing: bool, now: Instant) -> bool {
+        let was_typing = self.until.is_some();
+        self.until = is_typing.then(|| now + TYPING_TIMEOUT);
+        is_typing != was_typing
+    }
+
+    /// When the indicator runs out, `None` while the user is not typing
+    pub fn expires_at(&self) -> Option<Instant> {
+        self.until
+    }
+
+    /// Turn the indicator off if it has run out by `now`. Returns whether the room should
+    /// hear that the user stopped typing.
+    pub fn expire(&mut self, now: Instant) -> bool {
+        if self.until.is_some_and(|until| until <= now) {
+            self.until = None;
+            return true;
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn typing_expires_when_no_stop_arrives() {
+        let start = Instant::now();
+        let mut typing = TypingIndicator::default();
+        assert!(typing.update(true, start));
+        assert_eq!(typing.expires_at(), Some(start + TYPING_TIMEOUT));
+
+        // A repeat is not announced again but keeps the indicator on for longer
+        let repeat = start + Duration::from_secs(4);
+        assert!(!typing.update(true, repeat));
+        assert!(!typing.expire(start + TYPING_TIMEOUT));
+        assert!(typing.expire(repeat + TYPING_TIMEOUT));
+        assert_eq!(typing.expires_at(), None);
+        assert!(!typing.expire(repeat + TYPING_TIMEOUT * 2));
+
+        // Typing again after running out is a new start
+        assert!(typing.update(true, repeat + TYPING_TIMEOUT * 2));
+    }
+
+    #[test]
+    fn stops_are_announced_once() {
+        let now = Instant::now();
+        let mut typing = TypingIndicator::default();
+        assert!(!typing.update(false, now));
+        assert!(typing.update(true, now));
+        assert!(typing.update(false, now));
+        assert!(!typing.update(false, now));
+        assert!(!typing.expire(now + TYPING_TIMEOUT));
+    }
+}
@@ -1,18 +1,21 @@
-use crate::repository::user_repo::UserRepository;
-use lazy_static::lazy_static;
+use crate::repository::store::SharedStore;
+use argon2::password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use rand::{distributions::Alphanumeric, Rng};
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
 
-lazy_static! {
-    static ref USER_AUTH_SERVICE: UserAuthService = UserAuthService::new();
-}
+/// How long a session token issued at login stays valid
+const SESSION_TTL_SECS: i64 = 7 * 24 * 60 * 60;
+const SESSION_TOKEN_LEN: usize = 64;
 
 pub struct UserAuthService {
-    repository: UserRepository,
+    repository: SharedStore,
 }
 
 
 impl UserAuthService {
-    pub fn new() -> Self {
-        let repository = UserRepository::new();
+    pub fn new(repository: SharedStore) -> Self {
         UserAuthService { repository }
     }
 
@@ -31,9 +34,10 @@ impl UserAuthService {
         &self,
         email: String,
         user_name: String,
-        password_hash: String,
+        password: String,
         created_at: i64,
     ) -> Result<(), String> {
+        let password_hash = hash_password(password).await?;
         self.repository
             .user_sign_up(&email, &user_name, &password_hash, created_at)
             .await
@@ -42,21 +46,158 @@ impl UserAuthService {
     pub async fn user_query(
         &self,
         email: String,
-        password_hash: String,
+        password: String,
     ) -> Result<Option<(i32, String)>, String> {
-        self.repository.user_query(&email, &password_hash).await
+        let (user_id, username, stored_hash) = match self.repository.user_query(&email).await? {
+            Some(row) => row,
+            None => return Ok(None),
+        };
+
+        match verify_password(password.clone(), stored_hash).await? {
+            PasswordCheck::Invalid => return Ok(None),
+            PasswordCheck::Valid => {}
+            PasswordCheck::ValidLegacy => {
+                // Rows created before hashing was introduced hold the plaintext password,
+                // so upgrade them to an Argon2id hash now that we know the password
+                let password_hash = hash_password(password).await?;
+                self.repository.update_password_hash(user_id, &password_hash).await?;
+            }
+        }
+
+        Ok(Some((user_id, username)))
+    }
+
+    /// Issue a new session token for a user who has just logged in
+    pub async fn create_session(&self, user_id: i32) -> Result<(String, i64), String> {
+        let token: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(SESSION_TOKEN_LEN)
+            .map(char::from)
+            .collect();
+        self.repository.create_session(&token, user_id, SESSION_TTL_SECS).await?;
+        Ok((token, SESSION_TTL_SECS))
+    }
+
+    /// Resolve a session token to the (user_id, username) it belongs to
+    pub async fn authenticate(&self, token: &str) -> Result<Option<(i32, String)>, String> {
+        self.repository.find_session(token).await
     }
 
-    pub async fn user_logout(&self, user_id: i32) -> Result<(), String> {
-        self.repository.user_logout(user_id).await
+    pub async fn user_logout(&self, token: &str) -> Result<(), String> {
+        self.repository.delete_session(token).await
     }
 
 
     pub async fn fetch_user_list(&self, room_id:i32) -> Result<Vec<i32>, String> {
         self.repository.fetch_user_list(room_id).await
     }
+}
+
+/// Result of checking a password against the value stored in `Users.password_hash`
+enum PasswordCheck {
+    Valid,
+    /// The stored value is a legacy plaintext password that matched
+    ValidLegacy,
+    Invalid,
+}
+
+/// Hash a password into a salted Argon2id PHC string.
+/// Hashing is CPU-bound, so it runs on the blocking thread pool.
+async fn hash_password(password: String) -> Result<String, String> {
+    tokio::task::spawn_blocking(move || {
+        let salt = SaltString::generate(&mut OsRng);
+        Argon2::default()
+            .hash_password(password.as_bytes(), &salt)
+            .map(|hash| hash.to_string())
+            .map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+async fn verify_password(password: String, stored_hash: String) -> Result<PasswordCheck, String> {
+    tokio::task::spawn_blocking(move || match PasswordHash::new(&stored_hash) {
+        Ok(parsed) => match Argon2::default().verify_password(password.as_bytes(), &parsed) {
+            Ok(()) => PasswordCheck::Valid,
+            Err(_) => PasswordCheck::Invalid,
+        },
+        // Anything that is not a PHC string was stored before hashing was introduced. Their
+        // digests are compared in constant time, so neither timing nor length gives it away.
+        Err(_) if bool::from(Sha256::digest(&stored_hash).ct_eq(&Sha256::digest(&password))) => {
+            PasswordCheck::ValidLegacy
+        }
+        Err(_) => PasswordCheck::Invalid,
+    })
+    .await
+    .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::test_support::memory_store;
+
+    async fn signed_up_service() -> UserAuthService {
+        let service = UserAuthService::new(memory_store());
+        service
+            .user_sign_up("alice@example.com".into(), "alice".into(), "correct horse".into(), 0)
+            .await
+            .unwrap();
+        service
+    }
+
+    #[tokio::test]
+    async fn login_checks_the_password() {
+        let service = signed_up_service().await;
+
+        let (user_id, username) = service
+            .user_query("alice@example.com".into(), "correct horse".into())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(username, "alice");
+        assert!(user_id > 0);
+        assert_eq!(service.user_query("alice@example.com".into(), "wrong".into()).await.unwrap(), None);
+        assert_eq!(service.user_query("bob@example.com".into(), "correct horse".into()).await.unwrap(), None);
+        assert_eq!(service.user_check_exist("alice@example.com".into()).await, Err("User exist".to_string()));
+    }
+
+    #[tokio::test]
+    async fn sessions_last_until_logout() {
+        let service = signed_up_service().await;
+        let (user_id, _) = service
+            .user_query("alice@example.com".into(), "correct horse".into())
+            .await
+            .unwrap()
+            .unwrap();
+
+        let (token, ttl) = service.create_session(user_id).await.unwrap();
+        assert_eq!(ttl, SESSION_TTL_SECS);
+        assert_eq!(token.len(), SESSION_TOKEN_LEN);
+        assert_eq!(service.authenticate(&token).await.unwrap(), Some((user_id, "alice".to_string())));
+        assert_eq!(service.authenticate("not-a-token").await.unwrap(), None);
+
+        service.user_logout(&token).await.unwrap();
+        assert_eq!(service.authenticate(&token).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn legacy_plaintext_passwords_are_rehashed_on_login() {
+        let store = memory_store();
+        store.user_sign_up("bob@example.com", "bob", "hunter22", 0).await.unwrap();
+        let service = UserAuthService::new(store.clone());
+
+        assert_eq!(service.user_query("bob@example.com".into(), "hunter2".into()).await.unwrap(), None);
+        assert_eq!(store.user_query("bob@example.com").await.unwrap().unwrap().2, "hunter22");
 
-    pub async fn fetch_user_status(&self, user_id_list: Vec<i32>) -> Result<Vec<(i32,String)>, String> {
-        self.repository.fetch_user_status(user_id_list).await
+        let (user_id, _) = service.user_query("bob@example.com".into(), "hunter22".into()).await.unwrap().unwrap();
+        let (_, _, stored_hash) = store.user_query("bob@example.com").await.unwrap().unwrap();
+        assert!(stored_hash.starts_with("$argon2id$"));
+        // Sending the stored hash itself no longer matches as a legacy password
+        assert_eq!(service.user_query("bob@example.com".into(), stored_hash).await.unwrap(), None);
+        assert_eq!(
+            service.user_query("bob@example.com".into(), "hunter22".into()).await.unwrap().map(|(id, _)| id),
+            Some(user_id)
+        );
     }
 }